@@ -0,0 +1,35 @@
+// Prints the leaves of a tree one at a time, pulled from a generator built with call/cc
+// in examples/generator.scm:
+//
+//     cargo run --example generator -- "((a b) (c (d)) e)"
+use scheme_interp_rs::interpreter::{Interpreter, Options};
+
+const PROGRAM: &str = include_str!("generator.scm");
+const DEFAULT_TREE: &str = "((1 2) (3 (4)) 5)";
+
+fn main() {
+    let tree = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| DEFAULT_TREE.to_string());
+    let mut interp = Interpreter::new(Options::default());
+    let source = format!(
+        "{}\n(define leaves (make-generator (lambda (yield) (walk-tree (quote {}) yield))))",
+        PROGRAM, tree
+    );
+    if let Err(err) = interp.eval(&source) {
+        eprintln!("Error: {}", err);
+        std::process::exit(1);
+    }
+
+    // each call resumes the walk where the last one left off
+    loop {
+        match interp.call_global("leaves", &[]) {
+            Ok(leaf) if interp.to_string(&leaf) == "done" => break,
+            Ok(leaf) => println!("{}", interp.to_string(&leaf)),
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                std::process::exit(1);
+            }
+        }
+    }
+}
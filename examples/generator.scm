@@ -0,0 +1,65 @@
+;; Generators built from call/cc: a walk over some data hands each item to a yield procedure,
+;; and the generator suspends the walk there until it is asked for the next item.
+;;
+;; Run the examples below with: scheme-interp-rs test-docs examples/generator.scm
+
+;; make-generator turns walk, a procedure calling yield on each item it visits,
+;; into a procedure returning the next item on each call, then done once the walk ends
+;; the continuations are boxed in vectors so yield and the generator can update them
+(define (make-generator walk)
+  ;; where to resume the walk, and where to return the next item to
+  (define resume (vector #f))
+  (define return (vector #f))
+  (define (yield item)
+    (call/cc
+      (lambda (k)
+        (vector-set! resume 0 k)
+        ((vector-ref return 0) item))))
+  (define (start)
+    (walk yield)
+    ;; the walk has ended, so every later call resumes here
+    (vector-set! resume 0 (lambda (ignored) ((vector-ref return 0) (quote done))))
+    ((vector-ref return 0) (quote done)))
+  (lambda ()
+    (call/cc
+      (lambda (k)
+        (vector-set! return 0 k)
+        (if (vector-ref resume 0)
+            ((vector-ref resume 0) #f)
+            (start))))))
+
+;; walk-tree yields the leaves of tree from left to right
+(define (walk-tree tree yield)
+  (define (walk-pair)
+    (walk-tree (car tree) yield)
+    (walk-tree (cdr tree) yield))
+  (if (pair? tree)
+      (walk-pair)
+      (if (null? tree) #f (yield tree))))
+
+;; count-from yields n, n + 1, ... without end, so only a generator can consume it
+(define (count-from n yield)
+  (yield n)
+  (count-from (+ n 1) yield))
+
+;; take returns the next n items of generator
+(define (take generator n)
+  (if (i= n 0)
+      (quote ())
+      (cons (generator) (take generator (- n 1)))))
+
+;;> (define leaves (make-generator (lambda (yield) (walk-tree (quote ((a b) (c (d)) e)) yield))))
+;;> (take leaves 7)
+;;= (a b c d e done done)
+
+;;> (define naturals (make-generator (lambda (yield) (count-from 0 yield))))
+;;> (take naturals 3)
+;;> (take naturals 4)
+;;= (3 4 5 6)
+
+;; two generators over the same data advance independently
+;;> (define walk-abc (lambda (yield) (walk-tree (quote (a b c)) yield)))
+;;> (define first (make-generator walk-abc))
+;;> (define second (make-generator walk-abc))
+;;> (list (first) (first) (second) (first) (second))
+;;= (a b a c b)
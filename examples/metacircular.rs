@@ -0,0 +1,31 @@
+// Runs a program in the evaluator written in Scheme in examples/metacircular.scm,
+// one form per argument, printing the value of the last:
+//
+//     cargo run --example metacircular -- "(define sq (lambda (x) (* x x)))" "(sq 12)"
+use scheme_interp_rs::interpreter::{Interpreter, Options};
+
+const EVALUATOR: &str = include_str!("metacircular.scm");
+const DEFAULT_PROGRAM: &[&str] = &[
+    "(define fact (lambda (n) (if (i= n 0) 1 (* n (fact (- n 1))))))",
+    "(fact 20)",
+];
+
+fn main() {
+    let mut program = std::env::args().skip(1).collect::<Vec<_>>();
+    if program.is_empty() {
+        program = DEFAULT_PROGRAM
+            .iter()
+            .map(|form| form.to_string())
+            .collect();
+    }
+
+    let mut interp = Interpreter::new(Options::default());
+    let source = format!("{}\n(m-run (quote ({})))", EVALUATOR, program.join(" "));
+    match interp.eval(&source) {
+        Ok(res) => println!("{}", interp.to_string(&res)),
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,131 @@
+;; A metacircular evaluator: a small Scheme written in the Scheme this interpreter runs.
+;;
+;; It understands integers, booleans, symbols, (quote x), (if c t e), (lambda (params) body),
+;; top-level (define name expr) and applications. Closures are represented as
+;; (closure params body env) lists, and primitives are borrowed from the host.
+;;
+;; Run the examples below with: scheme-interp-rs test-docs examples/metacircular.scm
+;; or run a program of your own in it with:
+;;     cargo run --example metacircular -- "(define sq (lambda (x) (* x x)))" "(sq 12)"
+
+(define cadr (lambda (x) (car (cdr x))))
+(define cddr (lambda (x) (cdr (cdr x))))
+(define caddr (lambda (x) (car (cddr x))))
+(define cadddr (lambda (x) (car (cdr (cddr x)))))
+
+(define assq
+  (lambda (key alist)
+    (if (null? alist)
+        #f
+        (if (eq? (car (car alist)) key)
+            (car alist)
+            (assq key (cdr alist))))))
+
+;; definitions go in a mutable global frame, boxed in a vector;
+;; local environments are lists of association lists, innermost first
+(define globals
+  (vector (list (cons (quote +) +)
+                (cons (quote -) -)
+                (cons (quote *) *)
+                (cons (quote i=) i=)
+                (cons (quote i<) i<)
+                (cons (quote cons) cons)
+                (cons (quote car) car)
+                (cons (quote cdr) cdr)
+                (cons (quote null?) null?))))
+
+(define lookup-global
+  (lambda (name binding)
+    (if binding (cdr binding) (list (quote unbound) name))))
+
+(define lookup
+  (lambda (name env)
+    (if (null? env)
+        (lookup-global name (assq name (vector-ref globals 0)))
+        (lookup-in-frame name (assq name (car env)) (cdr env)))))
+
+(define lookup-in-frame
+  (lambda (name binding outer)
+    (if binding (cdr binding) (lookup name outer))))
+
+(define m-define
+  (lambda (name value)
+    (last (vector-set! globals 0 (cons (cons name value) (vector-ref globals 0)))
+          name)))
+
+(define m-eval
+  (lambda (expr env)
+    (if (symbol? expr)
+        (lookup expr env)
+        (if (pair? expr)
+            (m-eval-form (car expr) expr env)
+            expr))))
+
+(define m-eval-form
+  (lambda (head expr env)
+    (if (eq? head (quote quote))
+        (cadr expr)
+        (if (eq? head (quote if))
+            (if (m-eval (cadr expr) env)
+                (m-eval (caddr expr) env)
+                (m-eval (cadddr expr) env))
+            (if (eq? head (quote lambda))
+                (list (quote closure) (cadr expr) (caddr expr) env)
+                (if (eq? head (quote define))
+                    (m-define (cadr expr) (m-eval (caddr expr) env))
+                    (m-apply (m-eval head env) (m-eval-list (cdr expr) env))))))))
+
+(define m-eval-list
+  (lambda (exprs env)
+    (if (null? exprs)
+        nil
+        (cons (m-eval (car exprs) env) (m-eval-list (cdr exprs) env)))))
+
+(define bind
+  (lambda (params args)
+    (if (null? params)
+        nil
+        (cons (cons (car params) (car args)) (bind (cdr params) (cdr args))))))
+
+(define apply-primitive
+  (lambda (f args)
+    (if (null? args)
+        (f)
+        (if (null? (cdr args))
+            (f (car args))
+            (f (car args) (cadr args))))))
+
+(define m-apply
+  (lambda (f args)
+    (if (procedure? f)
+        (apply-primitive f args)
+        (m-eval (caddr f) (cons (bind (cadr f) args) (cadddr f))))))
+
+;; m-run evaluates each form of program in turn, returning the value of the last
+(define m-run (lambda (program) (m-run-forms program nil)))
+
+(define m-run-forms
+  (lambda (forms value)
+    (if (null? forms)
+        value
+        (m-run-forms (cdr forms) (m-eval (car forms) nil)))))
+
+;;> (m-run (quote ((define fact (lambda (n) (if (i= n 0) 1 (* n (fact (- n 1))))))
+;;>                (fact 10))))
+;;= 3628800
+
+;;> (m-run (quote ((define make-adder (lambda (n) (lambda (x) (+ x n))))
+;;>                ((make-adder 3) 4))))
+;;= 7
+
+;; tail calls in the evaluated program stay tail calls in the evaluator
+;;> (m-run (quote ((define count (lambda (n acc) (if (i= n 0) acc (count (- n 1) (+ acc 1)))))
+;;>                (count 1000 0))))
+;;= 1000
+
+;;> (m-run (quote ((define map (lambda (f l) (if (null? l) (quote ()) (cons (f (car l)) (map f (cdr l))))))
+;;>                (map (lambda (x) (* x x)) (quote (1 2 3))))))
+;;= (1 4 9)
+
+;;> (m-run (quote ((if (i< 1 2) (quote yes) (quote no)))))
+;;= yes
@@ -0,0 +1,72 @@
+// A todo list CLI whose list lives in a Scheme program, examples/todo.scm.
+// Commands are taken from the arguments, then the list is printed:
+//
+//     cargo run --example todo -- add "buy milk" add "walk the dog" done 1
+//
+// It shows embedding the interpreter: loading a program, calling its procedures
+// and converting their results to Rust values.
+use scheme_interp_rs::interpreter::{
+    Error, FromScheme, Interpreter, IntoScheme, Options, ValueHandle,
+};
+
+const PROGRAM: &str = include_str!("todo.scm");
+const USAGE: &str = "usage: todo [add <text> | done <id>]...";
+
+// call calls the global procedure name with args, converting its result to T
+fn call<T: FromScheme>(
+    interp: &mut Interpreter,
+    name: &str,
+    args: &[&ValueHandle],
+) -> Result<T, Error> {
+    let res = interp.call_global(name, args)?;
+    T::from_scheme(interp, res.ptr())
+}
+
+// handle converts val to a Scheme value to pass to call
+fn handle(interp: &mut Interpreter, val: impl IntoScheme) -> ValueHandle {
+    let ptr = val.into_scheme(interp);
+    interp.handle(ptr)
+}
+
+fn run(interp: &mut Interpreter, args: &[String]) -> Result<(), Error> {
+    let mut args = args.iter();
+    while let Some(command) = args.next() {
+        match (command.as_str(), args.next()) {
+            ("add", Some(text)) => {
+                let text = handle(interp, text.as_str());
+                let id: i64 = call(interp, "todo-add!", &[&text])?;
+                println!("added {}", id);
+            }
+            ("done", Some(id)) if id.parse::<i64>().is_ok() => {
+                let id = handle(interp, id.parse::<i64>().unwrap());
+                if !call::<bool>(interp, "todo-done!", &[&id])? {
+                    println!("no item {}", interp.to_string(&id));
+                }
+            }
+            _ => {
+                eprintln!("{}", USAGE);
+                std::process::exit(64);
+            }
+        }
+    }
+
+    let ids: Vec<i64> = call(interp, "todo-ids", &[])?;
+    for id in ids {
+        let handle = handle(interp, id);
+        let text: String = call(interp, "todo-text", &[&handle])?;
+        let done: bool = call(interp, "todo-done?", &[&handle])?;
+        println!("{} [{}] {}", id, if done { "x" } else { " " }, text);
+    }
+    let pending: i64 = call(interp, "todo-pending", &[])?;
+    println!("{} pending", pending);
+    Ok(())
+}
+
+fn main() {
+    let mut interp = Interpreter::new(Options::default());
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if let Err(err) = interp.eval(PROGRAM).and_then(|_| run(&mut interp, &args)) {
+        eprintln!("Error: {}", err);
+        std::process::exit(1);
+    }
+}
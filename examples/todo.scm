@@ -0,0 +1,58 @@
+;; A todo list, kept in order of entry. examples/todo.rs drives it from the command line:
+;;
+;;     cargo run --example todo -- add "buy milk" add "walk the dog" done 1
+;;
+;; Run the examples below with: scheme-interp-rs test-docs examples/todo.scm
+
+;; each item is a vector of its id, its text and whether it is done
+(define todos (make-queue))
+(define next-id (vector 1))
+
+;; todo-add! adds an item with text, returning its id
+(define (todo-add! text)
+  (define id (vector-ref next-id 0))
+  (vector-set! next-id 0 (+ id 1))
+  (enqueue! todos (vector id text #f))
+  id)
+
+(define (find-item id items)
+  (if (null? items)
+      #f
+      (if (i= (vector-ref (car items) 0) id)
+          (car items)
+          (find-item id (cdr items)))))
+
+;; todo-done! marks the item with id as done, returning whether there is such an item
+(define (todo-done! id)
+  (define item (find-item id (queue->list todos)))
+  (define (mark-done)
+    (vector-set! item 2 #t)
+    #t)
+  (if item (mark-done) #f))
+
+;; the ids of every item, and the parts of an item given its id
+(define (todo-ids) (map (lambda (item) (vector-ref item 0)) (queue->list todos)))
+(define (todo-text id) (vector-ref (find-item id (queue->list todos)) 1))
+(define (todo-done? id) (vector-ref (find-item id (queue->list todos)) 2))
+
+;; todo-pending counts the items not yet done
+(define (todo-pending)
+  (define (count items)
+    (if (null? items)
+        0
+        (+ (if (vector-ref (car items) 2) 0 1) (count (cdr items)))))
+  (count (queue->list todos)))
+
+;;> (todo-add! "buy milk")
+;;> (todo-add! "walk the dog")
+;;= 2
+
+;;> (todo-done! 1)
+;;> (list (todo-done? 1) (todo-done? 2) (todo-pending))
+;;= (#t #f 1)
+
+;;> (todo-done! 7)
+;;= #f
+
+;;> (todo-text 2)
+;;= "walk the dog"
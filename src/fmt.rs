@@ -0,0 +1,346 @@
+use crate::lex::{Comment, Lexer, Span, StrSource};
+use crate::parse::{self, Node, AST};
+
+// lists are kept on one line if they fit within this many columns
+const WIDTH: usize = 80;
+
+// forms which keep their first few arguments on the line with their name,
+// and indent the rest of their body by two spaces
+const SPECIAL_FORMS: &[(&str, usize)] = &[
+    ("begin", 0),
+    ("case", 1),
+    ("define", 1),
+    ("define-enumeration", 2),
+    ("define-record-type", 2),
+    ("define-syntax", 1),
+    ("define-values", 1),
+    ("do", 2),
+    ("guard", 1),
+    ("lambda", 1),
+    ("let", 1),
+    ("let*", 1),
+    ("let-values", 1),
+    ("letrec", 1),
+    ("letrec*", 1),
+    ("parameterize", 1),
+    ("syntax-rules", 1),
+    ("unless", 1),
+    ("when", 1),
+];
+
+// the reader shorthands for quote, quasiquote, unquote and unquote-splicing
+const SHORTHANDS: &[&str] = &["'", "`", ",", ",@"];
+
+// format pretty-prints source as canonically indented Scheme, keeping its comments
+pub fn format(source: &str) -> Result<String, String> {
+    let mut lexer = Lexer::new(StrSource::new(source)).keeping_comments();
+    let tokens = lexer
+        .by_ref()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| err.to_string())?;
+    let nodes = parse::parse(&tokens).map_err(|err| err.to_string())?;
+
+    let mut formatter = Formatter {
+        source,
+        comments: lexer.take_comments(),
+        next_comment: 0,
+        out: String::new(),
+    };
+    formatter.write_top_level(&nodes);
+    Ok(formatter.out)
+}
+
+struct Formatter<'a> {
+    source: &'a str,
+    comments: Vec<Comment>,
+    // the first comment not yet written
+    next_comment: usize,
+    out: String,
+}
+
+impl<'a> Formatter<'a> {
+    fn text(&self, span: Span) -> &'a str {
+        &self.source[span.start..span.end]
+    }
+
+    // column is the column the next character written will be in
+    fn column(&self) -> usize {
+        self.out
+            .chars()
+            .rev()
+            .take_while(|&chr| chr != '\n')
+            .count()
+    }
+
+    fn newline(&mut self, column: usize) {
+        self.out.push('\n');
+        self.out.push_str(&" ".repeat(column));
+    }
+
+    // take_comment returns the next comment not yet written, if it starts before end
+    fn take_comment(&mut self, end: usize) -> Option<Span> {
+        let span = self
+            .comments
+            .get(self.next_comment)
+            .filter(|comment| comment.span.start < end)?
+            .span;
+        self.next_comment += 1;
+        Some(span)
+    }
+
+    // has_comments checks whether a comment not yet written starts within span
+    fn has_comments(&self, span: Span) -> bool {
+        self.comments[self.next_comment..]
+            .iter()
+            .any(|comment| comment.span.start >= span.start && comment.span.start < span.end)
+    }
+
+    // write_comment writes the comment at span after an item ending at prev_end,
+    // on the same line if it was written there and otherwise on a new line at column
+    fn write_comment(&mut self, span: Span, prev_end: Option<usize>, column: usize) {
+        if let Some(end) = prev_end {
+            if self.source[end..span.start].contains('\n') {
+                self.newline(column);
+            } else {
+                self.out.push(' ');
+            }
+        }
+        self.out.push_str(self.text(span));
+    }
+
+    // write_top_level writes each form on its own line,
+    // collapsing any blank lines between forms into one
+    fn write_top_level(&mut self, nodes: &[Node]) {
+        let mut prev_end = None;
+        for node in nodes {
+            let start = node.location.span.start;
+            while let Some(span) = self.take_comment(start) {
+                self.separate(prev_end, span.start, true);
+                self.out.push_str(self.text(span));
+                prev_end = Some(span.end);
+            }
+            self.separate(prev_end, start, false);
+            self.write(node);
+            prev_end = Some(node.location.span.end);
+        }
+        while let Some(span) = self.take_comment(self.source.len()) {
+            self.separate(prev_end, span.start, true);
+            self.out.push_str(self.text(span));
+            prev_end = Some(span.end);
+        }
+        if prev_end.is_some() {
+            self.out.push('\n');
+        }
+    }
+
+    // separate separates a top-level item starting at start from the one ending at prev_end
+    // a comment on the same line as the item before it stays there
+    fn separate(&mut self, prev_end: Option<usize>, start: usize, comment: bool) {
+        let between = match prev_end {
+            Some(end) => &self.source[end..start],
+            None => return,
+        };
+        match between.matches('\n').count() {
+            0 if comment => self.out.push(' '),
+            0 | 1 => self.out.push('\n'),
+            _ => self.out.push_str("\n\n"),
+        }
+    }
+
+    // shorthand returns the quote mark a list was written with, if it was
+    fn shorthand(&self, entries: &[Node]) -> Option<&'a str> {
+        match entries {
+            [keyword, _] => {
+                let text = self.text(keyword.location.span);
+                SHORTHANDS.contains(&text).then_some(text)
+            }
+            _ => None,
+        }
+    }
+
+    // delimiters returns the brackets a list or vector was written with
+    fn delimiters(&self, node: &Node) -> (&'static str, &'static str) {
+        match &node.ast {
+            AST::Vector(_) => ("#(", ")"),
+            _ if self.text(node.location.span).starts_with('[') => ("[", "]"),
+            _ => ("(", ")"),
+        }
+    }
+
+    // flat renders node on a single line
+    fn flat(&self, node: &Node) -> String {
+        match &node.ast {
+            AST::List(entries) | AST::Vector(entries) => {
+                if let Some(mark) = self.shorthand(entries) {
+                    return format!("{}{}", mark, self.flat(&entries[1]));
+                }
+                let (open, close) = self.delimiters(node);
+                let entries = entries
+                    .iter()
+                    .map(|entry| self.flat(entry))
+                    .collect::<Vec<_>>();
+                format!("{}{}{}", open, entries.join(" "), close)
+            }
+            AST::Labeled(label, datum) => format!("#{}={}", label, self.flat(datum)),
+            _ => self.text(node.location.span).to_string(),
+        }
+    }
+
+    // write writes node starting at the current column
+    fn write(&mut self, node: &Node) {
+        let span = node.location.span;
+        match &node.ast {
+            AST::List(entries) | AST::Vector(entries) => {
+                if let Some(mark) = self.shorthand(entries) {
+                    self.out.push_str(mark);
+                    self.write(&entries[1]);
+                    return;
+                }
+                let flat = self.flat(node);
+                let fits = self.column() + flat.chars().count() <= WIDTH;
+                if fits && !flat.contains('\n') && !self.has_comments(span) {
+                    self.out.push_str(&flat);
+                } else {
+                    self.write_broken(node, entries);
+                }
+            }
+            AST::Labeled(label, datum) => {
+                self.out.push_str(&format!("#{}=", label));
+                self.write(datum);
+            }
+            _ => {
+                // other nodes are written as they were, comments inside bytevectors included
+                self.out.push_str(self.text(span));
+                while self.take_comment(span.end).is_some() {}
+            }
+        }
+    }
+
+    // write_broken writes a list across several lines
+    fn write_broken(&mut self, node: &Node, entries: &[Node]) {
+        let span = node.location.span;
+        let (open, close) = self.delimiters(node);
+        let start = self.column();
+        let inner = start + open.len();
+        let (first_line, indent) = match (&node.ast, entries.first().map(|entry| &entry.ast)) {
+            (AST::List(_), Some(AST::Symbol(name))) => self.layout(name, entries, start, inner),
+            _ => (1, inner),
+        };
+        self.out.push_str(open);
+
+        let mut prev_end = None;
+        // once a comment is written the remaining entries each start a new line
+        let mut broken = false;
+        for (i, entry) in entries.iter().enumerate() {
+            let column = if i == 0 { inner } else { indent };
+            while let Some(comment) = self.take_comment(entry.location.span.start) {
+                self.write_comment(comment, prev_end, column);
+                prev_end = Some(comment.end);
+                broken = true;
+            }
+            if prev_end.is_some() {
+                if i < first_line && !broken {
+                    self.out.push(' ');
+                } else {
+                    self.newline(column);
+                }
+            }
+            self.write(entry);
+            prev_end = Some(entry.location.span.end);
+        }
+
+        let column = if entries.is_empty() { inner } else { indent };
+        let mut line_comment = false;
+        while let Some(comment) = self.take_comment(span.end) {
+            self.write_comment(comment, prev_end, column);
+            prev_end = Some(comment.end);
+            line_comment = self.text(comment).starts_with(';');
+        }
+        if line_comment {
+            self.newline(column);
+        }
+        self.out.push_str(close);
+    }
+
+    // layout gives how many entries of a list named name starting at column start
+    // go on its first line, and the column the rest line up in
+    fn layout(&self, name: &str, entries: &[Node], start: usize, inner: usize) -> (usize, usize) {
+        match SPECIAL_FORMS.iter().find(|(form, _)| *form == name) {
+            Some(&(_, args)) => {
+                // a named let has its name before its bindings
+                let named = name == "let"
+                    && matches!(
+                        entries.get(1),
+                        Some(Node {
+                            ast: AST::Symbol(_),
+                            ..
+                        })
+                    );
+                (1 + args + named as usize, start + 2)
+            }
+            // arguments to a procedure line up after the first
+            None => (
+                2,
+                inner + self.text(entries[0].location.span).chars().count() + 1,
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::format;
+
+    #[test]
+    fn indents_forms() {
+        let source = "(define (fact n)\n(if (= n 0)\n1 (* n (fact (- n 1)))))  \n\n\n\n(fact 5)";
+        assert_eq!(
+            format(source),
+            Ok("(define (fact n) (if (= n 0) 1 (* n (fact (- n 1)))))\n\n(fact 5)\n".to_string())
+        );
+
+        let source = "(define (long-function-name argument) (let loop ((i 0) (acc '())) \
+                      (if (< i argument) (loop (+ i 1) (cons i acc)) (reverse acc))))";
+        assert_eq!(
+            format(source).unwrap(),
+            "(define (long-function-name argument)\n\
+             \x20 (let loop ((i 0) (acc '()))\n\
+             \x20   (if (< i argument) (loop (+ i 1) (cons i acc)) (reverse acc))))\n"
+        );
+
+        let source =
+            "(list \"a fairly long string\" \"another string which is even longer\" [#(1 2) #x1F #\\a])";
+        assert_eq!(
+            format(source).unwrap(),
+            "(list \"a fairly long string\"\n      \"another string which is even longer\"\n      [#(1 2) #x1F #\\a])\n"
+        );
+    }
+
+    #[test]
+    fn keeps_comments() {
+        let source =
+            ";; factorial\n(define (fact n) ; recursive\n  #| base case |# (if (= n 0) 1\n\
+                      (* n (fact (- n 1))) ; step\n)) #;(fact 5)\n; end";
+        let formatted = "\
+;; factorial
+(define (fact n) ; recursive
+  #| base case |#
+  (if (= n 0)
+      1
+      (* n (fact (- n 1))) ; step
+      )) #;(fact 5)
+; end
+";
+        assert_eq!(format(source).unwrap(), formatted);
+        // formatting is idempotent
+        assert_eq!(format(formatted).unwrap(), formatted);
+    }
+
+    #[test]
+    fn reports_syntax_errors() {
+        assert_eq!(
+            format("(a\n(b)"),
+            Err("Syntax error at line 0, col 0: missing matching ')'".to_string())
+        );
+    }
+}
@@ -1,8 +1,13 @@
-use crate::interpreter::value::Value;
-use std::collections::HashMap;
+use crate::interpreter::value::{eq, Guardian, Value};
+use crate::parse::Location;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::marker::PhantomData;
+use std::rc::{Rc, Weak};
+use std::time::{Duration, Instant};
 
-pub(super) struct Ptr<T> {
+pub struct Ptr<T> {
     index: usize,
     generation: usize,
     phantom: PhantomData<T>,
@@ -20,16 +25,27 @@ impl<T> Ptr<T> {
 
 impl<T> Clone for Ptr<T> {
     fn clone(&self) -> Self {
-        Ptr {
-            index: self.index,
-            generation: self.generation,
-            phantom: PhantomData,
-        }
+        *self
     }
 }
 
 impl<T> Copy for Ptr<T> {}
 
+impl<T> PartialEq for Ptr<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index && self.generation == other.generation
+    }
+}
+
+impl<T> Eq for Ptr<T> {}
+
+impl<T> Hash for Ptr<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+        self.generation.hash(state);
+    }
+}
+
 struct GcNode<T> {
     item: T,
     marked: bool,
@@ -52,6 +68,10 @@ struct ItemAllocator<T> {
     values: Vec<GcNode<T>>,
     free: Vec<usize>,
     generation: usize,
+    // total number of allocations made
+    allocated: u64,
+    // the capacity values may not grow past, if pinned
+    pinned: Option<usize>,
 }
 
 impl<T> ItemAllocator<T> {
@@ -60,10 +80,25 @@ impl<T> ItemAllocator<T> {
             values: Vec::new(),
             free: Vec::new(),
             generation: 0,
+            allocated: 0,
+            pinned: None,
         }
     }
 
+    // reserve makes room for n more items without growing values
+    fn reserve(&mut self, n: usize) {
+        self.values.reserve(n.saturating_sub(self.free.len()));
+    }
+
+    // exceeds_pin checks whether more items are live than the pinned capacity holds
+    // items are counted until they are collected, not just while they are reachable
+    fn exceeds_pin(&self) -> bool {
+        self.pinned
+            .is_some_and(|capacity| self.values.len() - self.free.len() > capacity)
+    }
+
     fn alloc(&mut self, item: T) -> Ptr<T> {
+        self.allocated += 1;
         match self.free.pop() {
             None => {
                 let index = self.values.len();
@@ -103,15 +138,34 @@ impl<T> ItemAllocator<T> {
         }
     }
 
+    fn is_marked(&self, ptr: Ptr<T>) -> bool {
+        self.values[ptr.index].marked
+    }
+
     // returns whether the item was previously marked
     fn mark(&mut self, ptr: Ptr<T>) -> bool {
         std::mem::replace(&mut self.values[ptr.index].marked, true)
     }
 }
 
-pub(super) struct Environment {
+pub struct Environment {
     parent: Option<Ptr<Environment>>,
     bindings: HashMap<String, Ptr<Value>>,
+    // names of bindings which cannot be redefined
+    constants: HashSet<String>,
+    // incremented on every mutation of bindings
+    version: u64,
+    // for each binding set since creation, the versions it was added and last modified at
+    versions: HashMap<String, (u64, u64)>,
+    // names declared by internal definitions whose values are not yet evaluated
+    uninitialized: HashSet<String>,
+}
+
+// BindingChange describes a binding set since some environment version
+#[derive(Debug, PartialEq)]
+pub(super) enum BindingChange {
+    Added(String),
+    Modified(String),
 }
 
 impl Environment {
@@ -122,6 +176,10 @@ impl Environment {
         Self {
             parent: Some(parent),
             bindings,
+            constants: HashSet::new(),
+            version: 0,
+            versions: HashMap::new(),
+            uninitialized: HashSet::new(),
         }
     }
 
@@ -129,6 +187,10 @@ impl Environment {
         Self {
             parent: None,
             bindings,
+            constants: HashSet::new(),
+            version: 0,
+            versions: HashMap::new(),
+            uninitialized: HashSet::new(),
         }
     }
 
@@ -137,9 +199,111 @@ impl Environment {
     }
 }
 
+impl BindingChange {
+    pub(super) fn name(&self) -> &str {
+        match self {
+            BindingChange::Added(name) | BindingChange::Modified(name) => name,
+        }
+    }
+}
+
+// HeapStats is a snapshot of the allocator's counters
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HeapStats {
+    pub values_heap_size: usize,
+    pub values_heap_free: usize,
+    pub values_allocated: u64,
+    pub values_heap_capacity: usize,
+    pub environments_heap_size: usize,
+    pub environments_heap_free: usize,
+    pub environments_allocated: u64,
+    pub environments_heap_capacity: usize,
+    pub collections: u64,
+}
+
+// GcCause is the reason a garbage collection ran
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GcCause {
+    // requested by the program, e.g. with gc-run
+    Explicit,
+    // a failed evaluation left the pinned heap full
+    PinnedHeap,
+}
+
+impl fmt::Display for GcCause {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GcCause::Explicit => write!(f, "explicit"),
+            GcCause::PinnedHeap => write!(f, "pinned-heap"),
+        }
+    }
+}
+
+// GcEvent describes a completed garbage collection
+#[derive(Clone, Copy, Debug)]
+pub struct GcEvent {
+    pub cause: GcCause,
+    pub duration: Duration,
+    pub values_freed: usize,
+    pub environments_freed: usize,
+    // the state of the heap after the collection
+    pub heap: HeapStats,
+}
+
+// Metrics receives allocator telemetry, e.g. to export it from a long-running host
+pub trait Metrics {
+    fn collected(&mut self, event: &GcEvent);
+}
+
+// GC_LOG_VAR names the environment variable which enables GcLog when set to anything but 0
+pub(super) const GC_LOG_VAR: &str = "SCHEME_INTERP_GC_LOG";
+
+// GcLog is the Metrics hook installed by GC_LOG_VAR, printing each collection to stderr
+pub(super) struct GcLog;
+
+impl GcLog {
+    pub(super) fn enabled() -> bool {
+        std::env::var_os(GC_LOG_VAR).is_some_and(|val| !val.is_empty() && val != "0")
+    }
+
+    fn format(event: &GcEvent) -> String {
+        format!(
+            "gc: cause={} duration={:?} values_freed={} environments_freed={} values_live={} environments_live={}",
+            event.cause,
+            event.duration,
+            event.values_freed,
+            event.environments_freed,
+            event.heap.values_heap_size - event.heap.values_heap_free,
+            event.heap.environments_heap_size - event.heap.environments_heap_free,
+        )
+    }
+}
+
+impl Metrics for GcLog {
+    fn collected(&mut self, event: &GcEvent) {
+        eprintln!("{}", GcLog::format(event));
+    }
+}
+
+// key/value pairs attached to an object
+type Properties = Vec<(Ptr<Value>, Ptr<Value>)>;
+
 pub(super) struct Allocator {
     values: ItemAllocator<Value>,
     environments: ItemAllocator<Environment>,
+    // object properties, keyed by object identity
+    // entries don't keep their object alive and are dropped once it is collected
+    properties: HashMap<Ptr<Value>, Properties>,
+    // where values read as code came from in the source, dropped once they are collected
+    locations: HashMap<Ptr<Value>, Location>,
+    collections: u64,
+    metrics: Option<Box<dyn Metrics>>,
+    // values held by the host, which stay alive while their token has strong references
+    roots: Vec<(Weak<()>, Ptr<Value>)>,
+    // every guardian, dropped once it is collected
+    guardians: Vec<Ptr<Value>>,
+    // the name of every symbol ever allocated, so that gensym can avoid them
+    symbols: HashSet<String>,
 }
 
 impl Allocator {
@@ -147,13 +311,71 @@ impl Allocator {
         Self {
             values: ItemAllocator::new(),
             environments: ItemAllocator::new(),
+            properties: HashMap::new(),
+            locations: HashMap::new(),
+            collections: 0,
+            metrics: None,
+            roots: Vec::new(),
+            guardians: Vec::new(),
+            symbols: HashSet::new(),
         }
     }
 
+    pub(super) fn set_metrics(&mut self, metrics: Option<Box<dyn Metrics>>) {
+        self.metrics = metrics;
+    }
+
     pub(super) fn new_val(&mut self, val: Value) -> Ptr<Value> {
+        if let Value::Symbol(name) = &val {
+            if !self.symbols.contains(name) {
+                self.symbols.insert(name.clone());
+            }
+        }
         self.values.alloc(val)
     }
 
+    // is_symbol_used returns whether a symbol with the given name was ever allocated
+    pub(super) fn is_symbol_used(&self, name: &str) -> bool {
+        self.symbols.contains(name)
+    }
+
+    // values_allocated returns the total number of values ever allocated
+    pub(super) fn values_allocated(&self) -> u64 {
+        self.values.allocated
+    }
+
+    // reserve makes room for the given numbers of values and environments,
+    // so that allocating them does not reallocate the heap
+    pub(super) fn reserve(&mut self, values: usize, environments: usize) {
+        self.values.reserve(values);
+        self.environments.reserve(environments);
+    }
+
+    // pin fixes the heap at its current capacity, or unfixes it
+    pub(super) fn pin(&mut self, pinned: bool) {
+        self.values.pinned = pinned.then_some(self.values.values.capacity());
+        self.environments.pinned = pinned.then_some(self.environments.values.capacity());
+    }
+
+    // exceeds_pin checks whether the heap has grown past the capacity it was pinned at
+    pub(super) fn exceeds_pin(&self) -> bool {
+        self.values.exceeds_pin() || self.environments.exceeds_pin()
+    }
+
+    pub(super) fn new_guardian(&mut self) -> Ptr<Value> {
+        let guardian = self.new_val(Value::Guardian(Guardian::default()));
+        self.guardians.push(guardian);
+        guardian
+    }
+
+    pub(super) fn set_location(&mut self, val: Ptr<Value>, location: Location) {
+        self.locations.insert(val, location);
+    }
+
+    pub(super) fn location(&self, val: Ptr<Value>) -> Option<Location> {
+        self.locations.get(&val).copied()
+    }
+
     pub(super) fn new_env(&mut self, env: Environment) -> Ptr<Environment> {
         self.environments.alloc(env)
     }
@@ -162,6 +384,10 @@ impl Allocator {
         self.values.get(ptr)
     }
 
+    pub(super) fn get_val_mut(&mut self, ptr: Ptr<Value>) -> &mut Value {
+        self.values.get_mut(ptr)
+    }
+
     pub(super) fn get_bound_ptr(&self, env: Ptr<Environment>, name: &str) -> Option<Ptr<Value>> {
         let mut env_ptr = env;
         loop {
@@ -169,18 +395,213 @@ impl Allocator {
             if let Some(&ptr) = env.bindings.get(name) {
                 return Some(ptr);
             }
+            if env.uninitialized.contains(name) {
+                return None;
+            }
             env_ptr = env.parent?;
         }
     }
 
+    // declare binds name in env without a value, hiding any outer binding
+    // until its value is set
+    pub(super) fn declare(&mut self, env: Ptr<Environment>, name: &str) {
+        let env = self.environments.get_mut(env);
+        if !env.bindings.contains_key(name) {
+            env.uninitialized.insert(name.to_string());
+        }
+    }
+
+    // is_uninitialized reports whether name refers to a declared binding without a value
+    pub(super) fn is_uninitialized(&self, env: Ptr<Environment>, name: &str) -> bool {
+        let mut env_ptr = env;
+        loop {
+            let env = self.environments.get(env_ptr);
+            if env.uninitialized.contains(name) {
+                return true;
+            }
+            if env.bindings.contains_key(name) {
+                return false;
+            }
+            match env.parent {
+                Some(parent) => env_ptr = parent,
+                None => return false,
+            }
+        }
+    }
+
+    // binds reports whether name is bound directly in env, ignoring its parents
+    pub(super) fn binds(&self, env: Ptr<Environment>, name: &str) -> bool {
+        self.environments.get(env).bindings.contains_key(name)
+    }
+
+    // binds_locally reports whether name is bound in env or one of its parents,
+    // other than the top-level environment
+    pub(super) fn binds_locally(&self, env: Ptr<Environment>, name: &str) -> bool {
+        let mut env_ptr = env;
+        loop {
+            let env = self.environments.get(env_ptr);
+            match env.parent {
+                Some(_) if env.bindings.contains_key(name) || env.uninitialized.contains(name) => {
+                    return true
+                }
+                Some(parent) => env_ptr = parent,
+                None => return false,
+            }
+        }
+    }
+
+    pub(super) fn parent(&self, env: Ptr<Environment>) -> Option<Ptr<Environment>> {
+        self.environments.get(env).parent
+    }
+
+    pub(super) fn binding_names(&self, env: Ptr<Environment>) -> Vec<String> {
+        self.environments
+            .get(env)
+            .bindings
+            .keys()
+            .cloned()
+            .collect()
+    }
+
+    // set_bound_value binds name in env, failing if name is bound to a constant there
     pub(super) fn set_bound_value(
         &mut self,
         env: Ptr<Environment>,
         name: String,
         value: Ptr<Value>,
-    ) {
+    ) -> Result<(), String> {
         let env = self.environments.get_mut(env);
-        env.bindings.insert(name, value);
+        if env.constants.contains(&name) {
+            return Err(format!("cannot redefine constant binding: {}", name));
+        }
+        env.uninitialized.remove(&name);
+        env.version += 1;
+        let version = env.version;
+        let added = if env.bindings.insert(name.clone(), value).is_some() {
+            env.versions.get(&name).map_or(0, |&(added, _)| added)
+        } else {
+            version
+        };
+        env.versions.insert(name, (added, version));
+        Ok(())
+    }
+
+    pub(super) fn version(&self, env: Ptr<Environment>) -> u64 {
+        self.environments.get(env).version
+    }
+
+    // changes_since lists the bindings in env set after version, sorted by name
+    pub(super) fn changes_since(&self, env: Ptr<Environment>, version: u64) -> Vec<BindingChange> {
+        let mut changes = self
+            .environments
+            .get(env)
+            .versions
+            .iter()
+            .filter(|(_, &(_, modified))| modified > version)
+            .map(|(name, &(added, _))| {
+                if added > version {
+                    BindingChange::Added(name.clone())
+                } else {
+                    BindingChange::Modified(name.clone())
+                }
+            })
+            .collect::<Vec<_>>();
+        changes.sort_by(|a, b| a.name().cmp(b.name()));
+        changes
+    }
+
+    pub(super) fn set_constant_value(
+        &mut self,
+        env: Ptr<Environment>,
+        name: String,
+        value: Ptr<Value>,
+    ) -> Result<(), String> {
+        self.set_bound_value(env, name.clone(), value)?;
+        self.environments.get_mut(env).constants.insert(name);
+        Ok(())
+    }
+
+    // make_constant marks every binding currently in env as constant
+    pub(super) fn make_constant(&mut self, env: Ptr<Environment>) {
+        let env = self.environments.get_mut(env);
+        env.constants.extend(env.bindings.keys().cloned());
+    }
+
+    // root keeps val alive until the returned token is dropped
+    pub(super) fn root(&mut self, val: Ptr<Value>) -> Rc<()> {
+        let token = Rc::new(());
+        self.roots.push((Rc::downgrade(&token), val));
+        token
+    }
+
+    pub(super) fn get_property(&self, obj: Ptr<Value>, key: Ptr<Value>) -> Option<Ptr<Value>> {
+        let props = self.properties.get(&obj)?;
+        props
+            .iter()
+            .find(|(k, _)| eq(self, *k, key))
+            .map(|(_, val)| *val)
+    }
+
+    pub(super) fn set_property(&mut self, obj: Ptr<Value>, key: Ptr<Value>, val: Ptr<Value>) {
+        let existing = self
+            .properties
+            .get(&obj)
+            .and_then(|props| props.iter().position(|(k, _)| eq(self, *k, key)));
+        let props = self.properties.entry(obj).or_default();
+        match existing {
+            Some(i) => props[i].1 = val,
+            None => props.push((key, val)),
+        }
+    }
+
+    // mark_properties marks the properties of every reachable object
+    // repeating until no newly reachable objects have properties
+    fn mark_properties(&mut self) {
+        let mut visited = HashSet::new();
+        loop {
+            let reachable = self
+                .properties
+                .iter()
+                .filter(|(obj, _)| !visited.contains(*obj) && self.values.is_marked(**obj))
+                .map(|(obj, props)| (*obj, props.clone()))
+                .collect::<Vec<_>>();
+            if reachable.is_empty() {
+                return;
+            }
+
+            for (obj, props) in reachable {
+                visited.insert(obj);
+                for (key, val) in props {
+                    self.mark_val(key);
+                    self.mark_val(val);
+                }
+            }
+        }
+    }
+
+    // mark_guarded moves the unreachable objects registered with live guardians
+    // to their ready queues, then marks them so they survive until popped
+    fn mark_guarded(&mut self) {
+        let values = &self.values;
+        self.guardians
+            .retain(|guardian| values.is_marked(*guardian));
+
+        for guardian in self.guardians.clone() {
+            let registered = match self.values.get(guardian) {
+                Value::Guardian(g) => g.registered.clone(),
+                _ => unreachable!(),
+            };
+            let (live, dead): (Vec<_>, Vec<_>) = registered
+                .into_iter()
+                .partition(|obj| self.values.is_marked(*obj));
+            if let Value::Guardian(g) = self.values.get_mut(guardian) {
+                g.registered = live;
+                g.ready.extend(&dead);
+            }
+            for obj in dead {
+                self.mark_val(obj);
+            }
+        }
     }
 
     fn mark_env(&mut self, env: Ptr<Environment>) {
@@ -221,6 +642,62 @@ impl Allocator {
                 for sr in &c.saved_results {
                     all_vals.extend_from_slice(sr)
                 }
+                for frame in &c.frames {
+                    all_vals.push(frame.func);
+                    all_vals.extend(frame.call);
+                }
+
+                for val in all_vals {
+                    self.mark_val(val)
+                }
+            }
+            // registered objects are held weakly, see mark_guarded
+            Value::Guardian(g) => {
+                for item in g.ready.clone() {
+                    self.mark_val(item)
+                }
+            }
+            Value::Set(set) => {
+                for item in set.items.clone() {
+                    self.mark_val(item)
+                }
+            }
+            Value::Vector(items) => {
+                for item in items.clone() {
+                    self.mark_val(item)
+                }
+            }
+            Value::Array(array) => {
+                for item in array.items.clone() {
+                    self.mark_val(item)
+                }
+            }
+            Value::Queue(items) => {
+                for item in items.clone() {
+                    self.mark_val(item)
+                }
+            }
+            Value::Heap(heap) => {
+                let mut all_vals = heap.items.clone();
+                all_vals.push(heap.less);
+
+                for val in all_vals {
+                    self.mark_val(val)
+                }
+            }
+            Value::Comparator(c) => {
+                let (equality, hash) = (c.equality, c.hash);
+                self.mark_val(equality);
+                if let Some(hash) = hash {
+                    self.mark_val(hash);
+                }
+            }
+            Value::HashTable(table) => {
+                let mut all_vals = vec![table.comparator];
+                for &(key, val) in table.entries() {
+                    all_vals.push(key);
+                    all_vals.push(val);
+                }
 
                 for val in all_vals {
                     self.mark_val(val)
@@ -230,25 +707,200 @@ impl Allocator {
         }
     }
 
-    pub(super) fn gc(&mut self, leaf: Ptr<Environment>) {
+    pub(super) fn gc(&mut self, leaf: Ptr<Environment>, cause: GcCause) {
+        let start = Instant::now();
+        let before = self.profile();
+
         self.mark_env(leaf);
+        self.roots.retain(|(token, _)| token.strong_count() > 0);
+        for (_, root) in self.roots.clone() {
+            self.mark_val(root);
+        }
+        self.mark_properties();
+        self.mark_guarded();
+        // the properties of objects guardians kept alive are kept too
+        self.mark_properties();
+        let values = &self.values;
+        self.properties.retain(|obj, _| values.is_marked(*obj));
+        self.locations.retain(|val, _| values.is_marked(*val));
         self.values.sweep();
         self.environments.sweep();
+        self.collections += 1;
+
+        let heap = self.profile();
+        if let Some(metrics) = &mut self.metrics {
+            metrics.collected(&GcEvent {
+                cause,
+                duration: start.elapsed(),
+                values_freed: heap.values_heap_free - before.values_heap_free,
+                environments_freed: heap.environments_heap_free - before.environments_heap_free,
+                heap,
+            });
+        }
     }
 
-    pub(super) fn profile(&self) -> GCInfo {
-        GCInfo {
+    pub(super) fn profile(&self) -> HeapStats {
+        HeapStats {
             values_heap_size: self.values.values.len(),
             values_heap_free: self.values.free.len(),
+            values_allocated: self.values.allocated,
+            values_heap_capacity: self.values.values.capacity(),
             environments_heap_size: self.environments.values.len(),
             environments_heap_free: self.environments.free.len(),
+            environments_allocated: self.environments.allocated,
+            environments_heap_capacity: self.environments.values.capacity(),
+            collections: self.collections,
         }
     }
 }
 
-pub(super) struct GCInfo {
-    pub(super) values_heap_size: usize,
-    pub(super) values_heap_free: usize,
-    pub(super) environments_heap_size: usize,
-    pub(super) environments_heap_free: usize,
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn drops_properties_of_collected_objects() {
+        let mut alloc = Allocator::new();
+        let key = alloc.new_val(Value::Symbol("doc".to_string()));
+        let live = alloc.new_val(Value::Integer(1));
+        let dead = alloc.new_val(Value::Integer(2));
+        alloc.set_property(live, key, live);
+        alloc.set_property(dead, key, dead);
+
+        let mut bindings = HashMap::new();
+        bindings.insert("live".to_string(), live);
+        let env = alloc.new_env(Environment::new_with_bindings(bindings));
+        alloc.gc(env, GcCause::Explicit);
+
+        assert!(alloc.get_property(live, key) == Some(live));
+        assert!(!alloc.properties.contains_key(&dead));
+    }
+
+    #[test]
+    fn guardians_keep_collected_objects() {
+        let mut alloc = Allocator::new();
+        let guardian = alloc.new_guardian();
+        let live = alloc.new_val(Value::Integer(1));
+        let dead = alloc.new_val(Value::Symbol("dead".to_string()));
+        let key = alloc.new_val(Value::Symbol("doc".to_string()));
+        alloc.set_property(dead, key, key);
+        if let Value::Guardian(g) = alloc.get_val_mut(guardian) {
+            g.registered = vec![live, dead];
+        }
+
+        let mut bindings = HashMap::new();
+        bindings.insert("guardian".to_string(), guardian);
+        bindings.insert("live".to_string(), live);
+        let env = alloc.new_env(Environment::new_with_bindings(bindings));
+        alloc.gc(env, GcCause::Explicit);
+
+        match alloc.get_val(guardian) {
+            Value::Guardian(g) => {
+                assert!(g.registered == vec![live]);
+                assert!(g.ready.iter().eq(&[dead]));
+            }
+            _ => panic!("expected a guardian"),
+        }
+        assert!(eq(&alloc, alloc.get_property(dead, key).unwrap(), key));
+        // once popped, the object is collected as usual
+        if let Value::Guardian(g) = alloc.get_val_mut(guardian) {
+            g.ready.clear();
+        }
+        alloc.gc(env, GcCause::Explicit);
+        assert!(!alloc.properties.contains_key(&dead));
+    }
+
+    #[test]
+    fn reports_collections_to_metrics() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        struct Recorder(Rc<RefCell<Vec<GcEvent>>>);
+
+        impl Metrics for Recorder {
+            fn collected(&mut self, event: &GcEvent) {
+                self.0.borrow_mut().push(*event);
+            }
+        }
+
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let mut alloc = Allocator::new();
+        alloc.set_metrics(Some(Box::new(Recorder(events.clone()))));
+        alloc.new_val(Value::Integer(1));
+        let env = alloc.new_env(Environment::new_with_bindings(HashMap::new()));
+        alloc.gc(env, GcCause::Explicit);
+
+        let events = events.borrow();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].values_freed, 1);
+        assert_eq!(events[0].heap.values_allocated, 1);
+        assert_eq!(events[0].heap.collections, 1);
+    }
+
+    #[test]
+    fn keeps_rooted_values_alive() {
+        let mut alloc = Allocator::new();
+        let rooted = alloc.new_val(Value::Integer(1));
+        let dropped = alloc.new_val(Value::Integer(2));
+        // both values are unreachable from the environment
+        let token = alloc.root(rooted);
+        drop(alloc.root(dropped));
+        let env = alloc.new_env(Environment::new_with_bindings(HashMap::new()));
+
+        alloc.gc(env, GcCause::Explicit);
+        assert_eq!(alloc.profile().values_heap_free, 1);
+
+        drop(token);
+        alloc.gc(env, GcCause::Explicit);
+        assert_eq!(alloc.profile().values_heap_free, 2);
+        assert!(alloc.roots.is_empty());
+    }
+
+    #[test]
+    fn formats_gc_log_lines() {
+        let event = GcEvent {
+            cause: GcCause::Explicit,
+            duration: Duration::from_micros(15),
+            values_freed: 3,
+            environments_freed: 1,
+            heap: HeapStats {
+                values_heap_size: 10,
+                values_heap_free: 4,
+                environments_heap_size: 2,
+                environments_heap_free: 1,
+                ..HeapStats::default()
+            },
+        };
+        assert_eq!(
+            GcLog::format(&event),
+            "gc: cause=explicit duration=15µs values_freed=3 environments_freed=1 values_live=6 environments_live=1"
+        );
+    }
+
+    #[test]
+    fn tracks_binding_changes() {
+        let mut alloc = Allocator::new();
+        let one = alloc.new_val(Value::Integer(1));
+        let mut bindings = HashMap::new();
+        bindings.insert("builtin".to_string(), one);
+        let env = alloc.new_env(Environment::new_with_bindings(bindings));
+
+        alloc.set_bound_value(env, "a".to_string(), one).unwrap();
+        let version = alloc.version(env);
+        alloc.set_bound_value(env, "a".to_string(), one).unwrap();
+        alloc.set_bound_value(env, "b".to_string(), one).unwrap();
+        alloc
+            .set_bound_value(env, "builtin".to_string(), one)
+            .unwrap();
+
+        assert_eq!(
+            alloc.changes_since(env, version),
+            vec![
+                BindingChange::Modified("a".to_string()),
+                BindingChange::Added("b".to_string()),
+                BindingChange::Modified("builtin".to_string()),
+            ]
+        );
+        assert_eq!(alloc.changes_since(env, alloc.version(env)), vec![]);
+    }
 }
@@ -0,0 +1,325 @@
+use crate::interpreter::allocator::Ptr;
+use crate::interpreter::host::ValueHandle;
+use crate::interpreter::value::{list_items, Value};
+use crate::interpreter::{Error, Interpreter};
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+
+// IntoScheme converts a Rust value into an interpreter value
+// sequences become proper lists, None becomes #f and maps become association lists
+// with symbol keys
+pub trait IntoScheme {
+    fn into_scheme(self, interp: &mut Interpreter) -> Ptr<Value>;
+}
+
+// FromScheme converts an interpreter value into a Rust value, the inverse of IntoScheme
+pub trait FromScheme: Sized {
+    fn from_scheme(interp: &Interpreter, ptr: Ptr<Value>) -> Result<Self, Error>;
+}
+
+fn conversion_error(interp: &Interpreter, expected: &str, ptr: Ptr<Value>) -> Error {
+    Error {
+        message: format!(
+            "expected {}, got {}",
+            expected,
+            interp.alloc.get_val(ptr).to_string(&interp.alloc)
+        ),
+        location: None,
+    }
+}
+
+impl IntoScheme for Value {
+    fn into_scheme(self, interp: &mut Interpreter) -> Ptr<Value> {
+        self.gc(&mut interp.alloc)
+    }
+}
+
+impl IntoScheme for Ptr<Value> {
+    fn into_scheme(self, _interp: &mut Interpreter) -> Ptr<Value> {
+        self
+    }
+}
+
+impl IntoScheme for &ValueHandle {
+    fn into_scheme(self, _interp: &mut Interpreter) -> Ptr<Value> {
+        self.ptr()
+    }
+}
+
+impl IntoScheme for i64 {
+    fn into_scheme(self, interp: &mut Interpreter) -> Ptr<Value> {
+        Value::Integer(self).gc(&mut interp.alloc)
+    }
+}
+
+impl FromScheme for i64 {
+    fn from_scheme(interp: &Interpreter, ptr: Ptr<Value>) -> Result<Self, Error> {
+        match interp.alloc.get_val(ptr) {
+            Value::Integer(i) => Ok(*i),
+            _ => Err(conversion_error(interp, "an integer", ptr)),
+        }
+    }
+}
+
+impl IntoScheme for f64 {
+    fn into_scheme(self, interp: &mut Interpreter) -> Ptr<Value> {
+        Value::Float(self).gc(&mut interp.alloc)
+    }
+}
+
+// integers convert to f64 as well, since Scheme code freely mixes the two
+impl FromScheme for f64 {
+    fn from_scheme(interp: &Interpreter, ptr: Ptr<Value>) -> Result<Self, Error> {
+        match interp.alloc.get_val(ptr) {
+            Value::Float(f) => Ok(*f),
+            Value::Integer(i) => Ok(*i as f64),
+            _ => Err(conversion_error(interp, "a number", ptr)),
+        }
+    }
+}
+
+impl IntoScheme for char {
+    fn into_scheme(self, interp: &mut Interpreter) -> Ptr<Value> {
+        Value::Char(self).gc(&mut interp.alloc)
+    }
+}
+
+impl FromScheme for char {
+    fn from_scheme(interp: &Interpreter, ptr: Ptr<Value>) -> Result<Self, Error> {
+        match interp.alloc.get_val(ptr) {
+            Value::Char(c) => Ok(*c),
+            _ => Err(conversion_error(interp, "a character", ptr)),
+        }
+    }
+}
+
+impl IntoScheme for bool {
+    fn into_scheme(self, interp: &mut Interpreter) -> Ptr<Value> {
+        Value::Bool(self).gc(&mut interp.alloc)
+    }
+}
+
+impl FromScheme for bool {
+    fn from_scheme(interp: &Interpreter, ptr: Ptr<Value>) -> Result<Self, Error> {
+        match interp.alloc.get_val(ptr) {
+            Value::Bool(b) => Ok(*b),
+            _ => Err(conversion_error(interp, "a boolean", ptr)),
+        }
+    }
+}
+
+impl IntoScheme for String {
+    fn into_scheme(self, interp: &mut Interpreter) -> Ptr<Value> {
+        Value::String(self).gc(&mut interp.alloc)
+    }
+}
+
+impl IntoScheme for &str {
+    fn into_scheme(self, interp: &mut Interpreter) -> Ptr<Value> {
+        self.to_string().into_scheme(interp)
+    }
+}
+
+impl FromScheme for String {
+    fn from_scheme(interp: &Interpreter, ptr: Ptr<Value>) -> Result<Self, Error> {
+        match interp.alloc.get_val(ptr) {
+            Value::String(s) => Ok(s.clone()),
+            _ => Err(conversion_error(interp, "a string", ptr)),
+        }
+    }
+}
+
+impl<T: IntoScheme> IntoScheme for Vec<T> {
+    fn into_scheme(self, interp: &mut Interpreter) -> Ptr<Value> {
+        let items = self
+            .into_iter()
+            .map(|item| item.into_scheme(interp))
+            .collect::<Vec<_>>();
+        Value::from_vec(&items, &mut interp.alloc)
+    }
+}
+
+impl<T: FromScheme> FromScheme for Vec<T> {
+    fn from_scheme(interp: &Interpreter, ptr: Ptr<Value>) -> Result<Self, Error> {
+        list_items(&interp.alloc, ptr)
+            .ok_or_else(|| conversion_error(interp, "a list", ptr))?
+            .into_iter()
+            .map(|item| T::from_scheme(interp, item))
+            .collect()
+    }
+}
+
+impl<T: IntoScheme> IntoScheme for Option<T> {
+    fn into_scheme(self, interp: &mut Interpreter) -> Ptr<Value> {
+        match self {
+            Some(val) => val.into_scheme(interp),
+            None => false.into_scheme(interp),
+        }
+    }
+}
+
+impl<T: FromScheme> FromScheme for Option<T> {
+    fn from_scheme(interp: &Interpreter, ptr: Ptr<Value>) -> Result<Self, Error> {
+        match interp.alloc.get_val(ptr) {
+            Value::Bool(false) => Ok(None),
+            _ => T::from_scheme(interp, ptr).map(Some),
+        }
+    }
+}
+
+impl<T: IntoScheme, S: BuildHasher> IntoScheme for HashMap<String, T, S> {
+    fn into_scheme(self, interp: &mut Interpreter) -> Ptr<Value> {
+        let mut entries = self.into_iter().collect::<Vec<_>>();
+        // sort for a deterministic order
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let pairs = entries
+            .into_iter()
+            .map(|(key, val)| {
+                let key = Value::Symbol(key).gc(&mut interp.alloc);
+                let val = val.into_scheme(interp);
+                Value::Cons(key, val).gc(&mut interp.alloc)
+            })
+            .collect::<Vec<_>>();
+        Value::from_vec(&pairs, &mut interp.alloc)
+    }
+}
+
+impl<T: FromScheme, S: BuildHasher + Default> FromScheme for HashMap<String, T, S> {
+    fn from_scheme(interp: &Interpreter, ptr: Ptr<Value>) -> Result<Self, Error> {
+        let pairs = list_items(&interp.alloc, ptr)
+            .ok_or_else(|| conversion_error(interp, "an association list", ptr))?;
+
+        let mut map = HashMap::default();
+        for pair in pairs {
+            match interp.alloc.get_val(pair) {
+                Value::Cons(key, val) => match interp.alloc.get_val(*key) {
+                    Value::Symbol(key) => {
+                        map.insert(key.clone(), T::from_scheme(interp, *val)?);
+                    }
+                    _ => return Err(conversion_error(interp, "a symbol key", *key)),
+                },
+                _ => return Err(conversion_error(interp, "a key/value pair", pair)),
+            }
+        }
+        Ok(map)
+    }
+}
+
+// tuples convert to and from lists of their elements
+macro_rules! tuple_conversions {
+    ($len:expr, $($name:ident),+) => {
+        impl<$($name: IntoScheme),+> IntoScheme for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn into_scheme(self, interp: &mut Interpreter) -> Ptr<Value> {
+                let ($($name,)+) = self;
+                let items = [$($name.into_scheme(interp)),+];
+                Value::from_vec(&items, &mut interp.alloc)
+            }
+        }
+
+        impl<$($name: FromScheme),+> FromScheme for ($($name,)+) {
+            fn from_scheme(interp: &Interpreter, ptr: Ptr<Value>) -> Result<Self, Error> {
+                let items = match list_items(&interp.alloc, ptr) {
+                    Some(items) if items.len() == $len => items,
+                    _ => {
+                        return Err(conversion_error(
+                            interp,
+                            concat!("a list of ", $len, " elements"),
+                            ptr,
+                        ))
+                    }
+                };
+                let mut items = items.into_iter();
+                Ok(($($name::from_scheme(interp, items.next().unwrap())?,)+))
+            }
+        }
+    };
+}
+
+tuple_conversions!(1, A);
+tuple_conversions!(2, A, B);
+tuple_conversions!(3, A, B, C);
+tuple_conversions!(4, A, B, C, D);
+
+impl Interpreter {
+    // convert converts the value a handle refers to into a Rust value
+    pub fn convert<T: FromScheme>(&self, handle: &ValueHandle) -> Result<T, Error> {
+        T::from_scheme(self, handle.ptr())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::interpreter::{Arity, Plugin, StdlibRegistry};
+    use crate::interpreter::{Environment, Options};
+
+    #[test]
+    fn round_trips_values() {
+        let mut interp = Interpreter::new(Options::default());
+        let mut scores = HashMap::new();
+        scores.insert("alice".to_string(), vec![1, 2]);
+        scores.insert("bob".to_string(), vec![]);
+        interp.define_global("scores", scores.clone()).unwrap();
+        interp
+            .define_global("pair", (7, Some(true), None::<i64>))
+            .unwrap();
+
+        let res = interp.lookup_global("scores").unwrap();
+        assert_eq!(interp.to_string(&res), "((alice 1 2) (bob))");
+        assert_eq!(
+            interp.convert::<HashMap<String, Vec<i64>>>(&res).unwrap(),
+            scores
+        );
+
+        let res = interp.lookup_global("pair").unwrap();
+        assert_eq!(
+            interp
+                .convert::<(i64, Option<bool>, Option<i64>)>(&res)
+                .unwrap(),
+            (7, Some(true), None)
+        );
+        assert_eq!(
+            interp.convert::<(i64, bool)>(&res).err().unwrap().message,
+            "expected a list of 2 elements, got (7 #t #f)"
+        );
+
+        interp.define_global("greeting", "hi \"there\"").unwrap();
+        let res = interp.lookup_global("greeting").unwrap();
+        assert_eq!(interp.to_string(&res), "\"hi \\\"there\\\"\"");
+        assert_eq!(interp.convert::<String>(&res).unwrap(), "hi \"there\"");
+
+        interp.define_global("ratio", 0.5).unwrap();
+        let res = interp.eval("(+ ratio 1)").unwrap();
+        assert_eq!(interp.to_string(&res), "1.5");
+        assert_eq!(interp.convert::<f64>(&res).unwrap(), 1.5);
+    }
+
+    #[test]
+    fn converts_native_arguments() {
+        struct Sum;
+
+        fn sum(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+            match Vec::<i64>::from_scheme(interp, args[0]) {
+                Ok(items) => interp.return_value(items.iter().sum::<i64>()),
+                Err(err) => interp.raise(err.message),
+            }
+        }
+
+        impl Plugin for Sum {
+            fn register(&self, registry: &mut StdlibRegistry) {
+                registry.native("sum", sum, Arity::exactly(1));
+            }
+        }
+
+        let mut interp = Interpreter::new(Options::default());
+        interp.add_plugin(Box::new(Sum));
+        let res = interp.eval("(sum (quote (1 2 3)))").unwrap();
+        assert_eq!(interp.convert::<i64>(&res).unwrap(), 6);
+        assert_eq!(
+            interp.eval("(sum (quote (1 a)))").err().unwrap().message,
+            "expected an integer, got a"
+        );
+    }
+}
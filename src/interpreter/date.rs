@@ -0,0 +1,331 @@
+use std::fmt;
+
+const SECONDS_PER_DAY: i64 = 86400;
+// the largest year string->date accepts, keeping conversions to seconds in range
+const MAX_YEAR: i64 = 999_999;
+
+const WEEK_DAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+// Date is a moment in UTC, to the second, broken down into calendar fields
+// the calendar is the proleptic Gregorian one, extended before its introduction
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Date {
+    pub(super) year: i64,
+    // 1 to 12
+    pub(super) month: u32,
+    // 1 to 31
+    pub(super) day: u32,
+    pub(super) hour: u32,
+    pub(super) minute: u32,
+    pub(super) second: u32,
+}
+
+fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// days_from_civil returns the number of days from 1970-01-01 to the given date
+// the algorithms are Howard Hinnant's, counting in 400 year eras starting on March 1st
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+// civil_from_days returns the year, month and day days after 1970-01-01
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+impl Date {
+    // from_seconds returns the date the given number of seconds after the Unix epoch
+    pub(super) fn from_seconds(seconds: i64) -> Self {
+        let (year, month, day) = civil_from_days(seconds.div_euclid(SECONDS_PER_DAY));
+        let time = seconds.rem_euclid(SECONDS_PER_DAY) as u32;
+        Date {
+            year,
+            month,
+            day,
+            hour: time / 3600,
+            minute: time / 60 % 60,
+            second: time % 60,
+        }
+    }
+
+    // to_seconds returns the number of seconds from the Unix epoch to the date
+    pub(super) fn to_seconds(self) -> i64 {
+        days_from_civil(self.year, self.month, self.day) * SECONDS_PER_DAY
+            + (self.hour * 3600 + self.minute * 60 + self.second) as i64
+    }
+
+    // week_day returns the day of the week, 0 being Sunday
+    pub(super) fn week_day(self) -> u32 {
+        // 1970-01-01 was a Thursday
+        (days_from_civil(self.year, self.month, self.day) + 4).rem_euclid(7) as u32
+    }
+
+    // year_day returns the day of the year, 1 being January 1st
+    pub(super) fn year_day(self) -> u32 {
+        (days_from_civil(self.year, self.month, self.day) - days_from_civil(self.year, 1, 1) + 1)
+            as u32
+    }
+
+    // format writes the date following fmt, whose directives are
+    // %Y year, %m month, %d day, %H hour, %M minute, %S second, %j day of the year,
+    // %a abbreviated week day, %b abbreviated month, %s seconds since the epoch,
+    // %F for %Y-%m-%d, %T for %H:%M:%S and %% for %
+    pub(super) fn format(self, fmt: &str) -> Result<String, String> {
+        let mut out = String::new();
+        let mut chars = fmt.chars();
+        while let Some(chr) = chars.next() {
+            if chr != '%' {
+                out.push(chr);
+                continue;
+            }
+            match chars.next() {
+                Some('Y') => out.push_str(&format!("{:04}", self.year)),
+                Some('m') => out.push_str(&format!("{:02}", self.month)),
+                Some('d') => out.push_str(&format!("{:02}", self.day)),
+                Some('H') => out.push_str(&format!("{:02}", self.hour)),
+                Some('M') => out.push_str(&format!("{:02}", self.minute)),
+                Some('S') => out.push_str(&format!("{:02}", self.second)),
+                Some('j') => out.push_str(&format!("{:03}", self.year_day())),
+                Some('a') => out.push_str(WEEK_DAYS[self.week_day() as usize]),
+                Some('b') => out.push_str(MONTHS[self.month as usize - 1]),
+                Some('s') => out.push_str(&self.to_seconds().to_string()),
+                Some('F') => out.push_str(&self.format("%Y-%m-%d")?),
+                Some('T') => out.push_str(&self.format("%H:%M:%S")?),
+                Some('%') => out.push('%'),
+                Some(chr) => return Err(format!("unknown directive %{}", chr)),
+                None => return Err("format ends with a lone %".to_string()),
+            }
+        }
+        Ok(out)
+    }
+
+    // parse reads a date from s following fmt, which has the directives of format apart from
+    // %j, %a and %s
+    // fields fmt doesn't mention are taken from 1970-01-01 00:00:00
+    pub(super) fn parse(s: &str, fmt: &str) -> Result<Self, String> {
+        let fmt = fmt.replace("%F", "%Y-%m-%d").replace("%T", "%H:%M:%S");
+        let mut date = Date::from_seconds(0);
+        let mut input = Parser {
+            chars: s.chars().collect(),
+            pos: 0,
+        };
+        let mut chars = fmt.chars();
+        while let Some(chr) = chars.next() {
+            if chr != '%' {
+                input.expect(chr)?;
+                continue;
+            }
+            match chars.next() {
+                Some('Y') => {
+                    let negative = input.peek() == Some('-');
+                    if negative {
+                        input.pos += 1;
+                    }
+                    let year = input.number(6, "year")?;
+                    date.year = if negative { -year } else { year };
+                }
+                Some('m') => date.month = input.field(1, 12, "month")?,
+                Some('d') => date.day = input.field(1, 31, "day")?,
+                Some('H') => date.hour = input.field(0, 23, "hour")?,
+                Some('M') => date.minute = input.field(0, 59, "minute")?,
+                Some('S') => date.second = input.field(0, 59, "second")?,
+                Some('b') => {
+                    let name = input
+                        .chars
+                        .iter()
+                        .skip(input.pos)
+                        .take(3)
+                        .collect::<String>();
+                    match MONTHS
+                        .iter()
+                        .position(|month| month.eq_ignore_ascii_case(&name))
+                    {
+                        Some(i) => date.month = i as u32 + 1,
+                        None => return Err(input.error("month name")),
+                    }
+                    input.pos += 3;
+                }
+                Some('%') => input.expect('%')?,
+                Some(chr) => return Err(format!("unknown directive %{}", chr)),
+                None => return Err("format ends with a lone %".to_string()),
+            }
+        }
+        if input.pos != input.chars.len() {
+            return Err(format!(
+                "unexpected text at position {}: {}",
+                input.pos,
+                input.chars[input.pos..].iter().collect::<String>()
+            ));
+        }
+        if date.year.abs() > MAX_YEAR {
+            return Err(format!("year {} out of range", date.year));
+        }
+        if date.day > days_in_month(date.year, date.month) {
+            return Err(format!(
+                "day {} out of range for {}-{:02}",
+                date.day, date.year, date.month
+            ));
+        }
+        Ok(date)
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+// Parser is the input being read by Date::parse
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn error(&self, expected: &str) -> String {
+        format!("expected {} at position {}", expected, self.pos)
+    }
+
+    fn expect(&mut self, chr: char) -> Result<(), String> {
+        if self.peek() != Some(chr) {
+            return Err(self.error(&format!("'{}'", chr)));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    // number reads up to max_digits digits, at least one
+    fn number(&mut self, max_digits: usize, name: &str) -> Result<i64, String> {
+        let start = self.pos;
+        while self.pos - start < max_digits && self.peek().is_some_and(|chr| chr.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        if self.pos == start {
+            return Err(self.error(name));
+        }
+        Ok(self.chars[start..self.pos]
+            .iter()
+            .collect::<String>()
+            .parse()
+            .unwrap())
+    }
+
+    // field reads a one or two digit number from min to max
+    fn field(&mut self, min: u32, max: u32, name: &str) -> Result<u32, String> {
+        let start = self.pos;
+        let val = self.number(2, name)? as u32;
+        if val < min || val > max {
+            return Err(format!(
+                "{} {} out of range at position {}",
+                name, val, start
+            ));
+        }
+        Ok(val)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn converts_seconds_to_dates() {
+        let date = Date::from_seconds(1_700_000_000);
+        assert_eq!(date.to_string(), "2023-11-14T22:13:20Z");
+        assert_eq!(date.week_day(), 2);
+        assert_eq!(date.year_day(), 318);
+        assert_eq!(date.to_seconds(), 1_700_000_000);
+
+        assert_eq!(Date::from_seconds(0).to_string(), "1970-01-01T00:00:00Z");
+        assert_eq!(Date::from_seconds(0).week_day(), 4);
+        assert_eq!(Date::from_seconds(-1).to_string(), "1969-12-31T23:59:59Z");
+        assert_eq!(
+            Date::from_seconds(951_782_400).to_string(),
+            "2000-02-29T00:00:00Z"
+        );
+        for seconds in [-62_135_596_800, -1, 0, 68_169_600, 4_102_444_800] {
+            assert_eq!(Date::from_seconds(seconds).to_seconds(), seconds);
+        }
+    }
+
+    #[test]
+    fn formats_dates() {
+        let date = Date::from_seconds(1_700_000_000);
+        assert_eq!(
+            date.format("%a %d %b %Y %H:%M:%S, day %j, 100%%").unwrap(),
+            "Tue 14 Nov 2023 22:13:20, day 318, 100%"
+        );
+        assert_eq!(date.format("%FT%T").unwrap(), "2023-11-14T22:13:20");
+        assert_eq!(date.format("%s").unwrap(), "1700000000");
+        assert_eq!(date.format("%q").unwrap_err(), "unknown directive %q");
+    }
+
+    #[test]
+    fn parses_dates() {
+        let date = Date::parse("14/Nov/2023:22:13:20", "%d/%b/%Y:%T").unwrap();
+        assert_eq!(date.to_seconds(), 1_700_000_000);
+        let date = Date::parse("2024-2-29", "%Y-%m-%d").unwrap();
+        assert_eq!(date.to_string(), "2024-02-29T00:00:00Z");
+
+        assert_eq!(
+            Date::parse("2023-02-29", "%F").unwrap_err(),
+            "day 29 out of range for 2023-02"
+        );
+        assert_eq!(
+            Date::parse("2023-13-01", "%F").unwrap_err(),
+            "month 13 out of range at position 5"
+        );
+        assert_eq!(
+            Date::parse("2023-11-14 x", "%F").unwrap_err(),
+            "unexpected text at position 10:  x"
+        );
+        assert_eq!(
+            Date::parse("2023/11", "%Y-%m").unwrap_err(),
+            "expected '-' at position 4"
+        );
+    }
+}
@@ -0,0 +1,409 @@
+use crate::interpreter::allocator::{Environment, Ptr};
+use crate::interpreter::syntax;
+use crate::interpreter::value::{list_items, Value};
+use crate::interpreter::{Error, Interpreter, Step, StepKind};
+
+const DEFINITIONS: &[&str] = &["define", "define-constant", "define-enumeration"];
+const DEFINITION_ORDER_ERROR: &str = "definitions must come before the expressions of a lambda \
+     body; move this definition to the start of the body";
+const MISSING_BODY_EXPRESSION_ERROR: &str = "lambda body must end with an expression";
+// expansions are limited in depth so that a file including itself is an error, not a crash
+const MAX_EXPANSION_DEPTH: usize = 64;
+const EXPANSION_DEPTH_ERROR: &str =
+    "cond-expand and include forms are nested too deeply, a file may be including itself";
+
+// definitions are only allowed at the top level and at the start of a lambda body,
+// where they bind in the body's own environment, so they never reach outside the body
+// anywhere else a definition is an error, reported by eval_node
+// internal definitions follow letrec*: every name they define is declared when the body
+// starts, so they can refer to each other, and their values are evaluated in order,
+// with reading a name before its value is evaluated being an error
+impl Interpreter {
+    // definition_keyword returns the keyword of node if it is a definition in env
+    fn definition_keyword(&self, node: Ptr<Value>, env: Ptr<Environment>) -> Option<String> {
+        let hd = match self.alloc.get_val(node) {
+            Value::Cons(hd, _) => *hd,
+            _ => return None,
+        };
+        match self.alloc.get_val(hd) {
+            Value::Symbol(s) if DEFINITIONS.contains(&s.as_str()) => {
+                // like other special forms, definitions can be shadowed by local bindings
+                if self.alloc.binds_locally(env, s) {
+                    None
+                } else {
+                    Some(s.clone())
+                }
+            }
+            _ => None,
+        }
+    }
+
+    // eval_form evaluates a top-level form or a form of a lambda body, either of which
+    // may be a definition
+    pub(super) fn eval_form(&mut self, node: Ptr<Value>, env: Ptr<Environment>) {
+        if self.is_cond_expand(node, env) || self.is_include(node, env) {
+            return self.eval_expansion(node, env, true);
+        }
+        let keyword = match self.definition_keyword(node, env) {
+            Some(keyword) => keyword,
+            None => return self.eval_node(node, env),
+        };
+        let nodes = match list_items(&self.alloc, node) {
+            Some(nodes) => nodes,
+            None => return self.eval_node(node, env),
+        };
+        self.eval_definition(&keyword, &nodes, env);
+    }
+
+    // eval_body evaluates the forms of a lambda body in order, producing the value of the last
+    // the body is any number of definitions followed by at least one expression
+    pub(super) fn eval_body(&mut self, body: Ptr<Value>, env: Ptr<Environment>) {
+        let forms = list_items(&self.alloc, body).unwrap();
+        let forms = match self.splice(forms, env, 0) {
+            Ok(forms) => forms,
+            Err(message) => {
+                self.error = Some(Error {
+                    message,
+                    location: None,
+                });
+                return;
+            }
+        };
+
+        let mut seen_expression = false;
+        for &form in &forms {
+            match self.definition_keyword(form, env) {
+                Some(keyword) if seen_expression => {
+                    self.error = Some(Error {
+                        message: format!("{}: {}", keyword, DEFINITION_ORDER_ERROR),
+                        location: None,
+                    });
+                    return;
+                }
+                Some(_) => {}
+                None => seen_expression = true,
+            }
+        }
+        if !seen_expression {
+            self.error = Some(Error {
+                message: MISSING_BODY_EXPRESSION_ERROR.to_string(),
+                location: None,
+            });
+            return;
+        }
+
+        for &form in &forms {
+            match self.definition_keyword(form, env).as_deref() {
+                Some("define-enumeration") => {}
+                Some(_) => {
+                    if let Some(name) = self.defined_name(form) {
+                        self.alloc.declare(env, &name);
+                    }
+                }
+                None => break,
+            }
+        }
+
+        // each later form discards the value of the one before it
+        for &form in forms[1..].iter().rev() {
+            self.next_steps
+                .push(Step::new(StepKind::Eval(form), move |interp| {
+                    interp.results.pop();
+                    interp.eval_form(form, env);
+                }));
+        }
+        self.eval_form(forms[0], env);
+    }
+
+    // expand returns the forms node expands to if it is a use of cond-expand or include
+    fn expand(
+        &mut self,
+        node: Ptr<Value>,
+        env: Ptr<Environment>,
+    ) -> Option<Result<Vec<Ptr<Value>>, String>> {
+        if self.is_cond_expand(node, env) {
+            Some(self.expand_cond_expand(node))
+        } else if self.is_include(node, env) {
+            Some(self.read_include(node))
+        } else {
+            None
+        }
+    }
+
+    // splice replaces each use of cond-expand or include in forms with the forms it expands to,
+    // expanding those in turn
+    fn splice(
+        &mut self,
+        forms: Vec<Ptr<Value>>,
+        env: Ptr<Environment>,
+        depth: usize,
+    ) -> Result<Vec<Ptr<Value>>, String> {
+        let mut res = Vec::with_capacity(forms.len());
+        for form in forms {
+            match self.expand(form, env) {
+                Some(_) if depth == MAX_EXPANSION_DEPTH => {
+                    return Err(EXPANSION_DEPTH_ERROR.to_string())
+                }
+                Some(expanded) => res.extend(self.splice(expanded?, env, depth + 1)?),
+                None => res.push(form),
+            }
+        }
+        Ok(res)
+    }
+
+    // eval_expansion evaluates the forms a cond-expand or include expands to in order,
+    // producing the value of the last, or nil if there are none
+    // in a lambda body or at the top level the forms are spliced in, so they may be definitions,
+    // anywhere else they are evaluated like a lambda body without definitions
+    pub(super) fn eval_expansion(
+        &mut self,
+        node: Ptr<Value>,
+        env: Ptr<Environment>,
+        top_level: bool,
+    ) {
+        let forms = match self.splice(vec![node], env, 0) {
+            Ok(forms) => forms,
+            Err(message) => {
+                self.error = Some(Error {
+                    message,
+                    location: None,
+                });
+                return;
+            }
+        };
+        if forms.is_empty() {
+            self.results.push(Value::Nil.gc(&mut self.alloc));
+            return;
+        }
+
+        let eval = move |interp: &mut Interpreter, form| {
+            if top_level {
+                interp.eval_form(form, env)
+            } else {
+                interp.eval_node(form, env)
+            }
+        };
+        // each later form discards the value of the one before it
+        for &form in forms[1..].iter().rev() {
+            self.next_steps
+                .push(Step::new(StepKind::Eval(form), move |interp| {
+                    interp.results.pop();
+                    eval(interp, form);
+                }));
+        }
+        eval(self, forms[0]);
+    }
+
+    // defined_name returns the name bound by a define or define-constant form,
+    // given in either the plain or the procedure shorthand syntax
+    fn defined_name(&self, node: Ptr<Value>) -> Option<String> {
+        let nodes = list_items(&self.alloc, node)?;
+        let name = match self.alloc.get_val(*nodes.get(1)?) {
+            Value::Cons(name, _) => self.alloc.get_val(*name),
+            target => target,
+        };
+        match name {
+            Value::Symbol(name) => Some(name.clone()),
+            _ => None,
+        }
+    }
+
+    // expand_procedure_shorthand rewrites (define (name param ...) body ...)
+    // as (define name (lambda (param ...) body ...)), returning the new nodes
+    fn expand_procedure_shorthand(&mut self, nodes: &[Ptr<Value>]) -> Option<Vec<Ptr<Value>>> {
+        if nodes.len() < 3 {
+            return None;
+        }
+        let (name, params) = match self.alloc.get_val(nodes[1]) {
+            Value::Cons(name, params) => (*name, *params),
+            _ => return None,
+        };
+        let lambda = Value::Symbol("lambda".to_string()).gc(&mut self.alloc);
+        let mut lambda_nodes = vec![lambda, params];
+        lambda_nodes.extend_from_slice(&nodes[2..]);
+        let lambda = Value::from_vec(&lambda_nodes, &mut self.alloc);
+        Some(vec![nodes[0], name, lambda])
+    }
+
+    // eval_definition evaluates a definition, given its keyword and the nodes of the form
+    fn eval_definition(&mut self, keyword: &str, nodes: &[Ptr<Value>], env: Ptr<Environment>) {
+        if keyword == "define-enumeration" {
+            return self.define_enumeration(nodes, env);
+        }
+
+        let expanded = self.expand_procedure_shorthand(nodes);
+        let nodes = expanded.as_deref().unwrap_or(nodes);
+
+        let constant = keyword == "define-constant";
+        let form = if constant {
+            &syntax::DEFINE_CONSTANT
+        } else {
+            &syntax::DEFINE
+        };
+        if let Err(message) = form.validate(&self.alloc, &nodes[1..]) {
+            self.error = Some(Error {
+                message,
+                location: None,
+            });
+            return;
+        }
+
+        let name = match self.alloc.get_val(nodes[1]) {
+            Value::Symbol(s) => s.clone(),
+            _ => unreachable!(),
+        };
+        if let Err(message) = self.check_frozen(&name, env) {
+            self.error = Some(Error {
+                message,
+                location: None,
+            });
+            return;
+        }
+
+        if !self.options.language.allows_redefinition() && self.alloc.binds(env, &name) {
+            self.error = Some(Error {
+                message: format!(
+                    "{} is already defined, the {} language does not allow redefinition",
+                    name, self.options.language
+                ),
+                location: None,
+            });
+            return;
+        }
+
+        if self.options.warn_shadowing {
+            let warning = self.definition_warning(&name, env);
+            self.warn_shadowing(nodes[1], warning);
+        }
+        // a trivial value is bound directly, without a define step
+        if let Some(value) = self.simple_value(nodes[2], env) {
+            self.bind_definition(name, value, env, constant);
+            return;
+        }
+
+        let kind = StepKind::Define(name.clone());
+        self.next_steps.push(Step::new(kind, move |interp| {
+            let value = interp.results.pop().unwrap();
+            interp.bind_definition(name, value, env, constant);
+        }));
+        self.eval_node(nodes[2], env);
+    }
+
+    // bind_definition binds name to the value of a define form, producing its result
+    fn bind_definition(
+        &mut self,
+        name: String,
+        value: Ptr<Value>,
+        env: Ptr<Environment>,
+        constant: bool,
+    ) {
+        // anonymous functions take the name they are first defined as
+        if let Value::Function(f) = self.alloc.get_val_mut(value) {
+            if f.name.is_none() {
+                f.name = Some(name.clone());
+            }
+        }
+        let res = if constant {
+            self.alloc.set_constant_value(env, name, value)
+        } else {
+            self.alloc.set_bound_value(env, name, value)
+        };
+        match res {
+            Ok(()) => self.results.push(Value::Nil.gc(&mut self.alloc)),
+            Err(message) => {
+                self.error = Some(Error {
+                    message,
+                    location: None,
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::interpreter::test::run;
+
+    #[test]
+    fn allows_internal_definitions() {
+        assert_eq!(
+            run("((lambda (x) (define y (+ x 1)) (define z (+ y 1)) (+ x y z)) 1)"),
+            Ok("6".to_string())
+        );
+        // internal definitions are local to each call
+        assert_eq!(
+            run("(define y 10) ((lambda () (define y 1) y)) y"),
+            Ok("10".to_string())
+        );
+        assert_eq!(run("((lambda (x) 1 2 x) 3)"), Ok("3".to_string()));
+    }
+
+    #[test]
+    fn rejects_definitions_elsewhere() {
+        assert_eq!(
+            run("(if #t (define x 1) 2)"),
+            Err(format!("define: {}", super::super::DEFINE_CONTEXT_ERROR))
+        );
+        assert_eq!(
+            run("((lambda () 1 (define x 2) x))"),
+            Err(format!("define: {}", super::DEFINITION_ORDER_ERROR))
+        );
+        assert_eq!(
+            run("((lambda () (define x 2)))"),
+            Err(super::MISSING_BODY_EXPRESSION_ERROR.to_string())
+        );
+        assert!(run("(cons (define-constant x 1) 2)").is_err());
+    }
+
+    mod letrec {
+        use crate::interpreter::test::run;
+
+        #[test]
+        fn allows_mutually_recursive_definitions() {
+            let source = "
+                (define (parity n)
+                  (define (even? n) (if (= n 0) #t (odd? (- n 1))))
+                  (define (odd? n) (if (= n 0) #f (even? (- n 1))))
+                  (if (even? n) 'even 'odd))
+                (cons (parity 10) (parity 7))";
+            assert_eq!(run(source), Ok("(even . odd)".to_string()));
+        }
+
+        #[test]
+        fn evaluates_definitions_in_order() {
+            assert_eq!(
+                run("((lambda () (define a 1) (define b (+ a 1)) (define (f) c) (define c 3) (+ b (f))))"),
+                Ok("5".to_string())
+            );
+        }
+
+        #[test]
+        fn rejects_reading_uninitialized_bindings() {
+            let err = Err(format!(
+                "{}: x",
+                crate::interpreter::UNINITIALIZED_BINDING_ERROR
+            ));
+            // the outer x is hidden by the internal definition for the whole body
+            assert_eq!(
+                run("(define x 10) ((lambda () (define y x) (define x 1) y))"),
+                err
+            );
+            assert_eq!(
+                run("((lambda () (define (f) x) (define y (f)) (define x 1) y))"),
+                err
+            );
+        }
+
+        #[test]
+        fn names_shorthand_procedures() {
+            assert_eq!(
+                run("(define (square x) (* x x)) (square 4)"),
+                Ok("16".to_string())
+            );
+            assert_eq!(
+                run("(define (square x) (* x x)) square"),
+                Ok("#<procedure square (x)>".to_string())
+            );
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use crate::interpreter::{Interpreter, Options};
+use std::path::PathBuf;
+
+const EXAMPLE_PREFIX: &str = ";;>";
+const EXPECTED_PREFIX: &str = ";;=";
+
+// Example is an expression from a ";;>" comment and the result from the ";;=" comment after it
+#[derive(Debug, PartialEq)]
+struct Example {
+    line: usize,
+    source: String,
+    expected: String,
+}
+
+// extract_examples collects the examples in source
+// consecutive ";;>" lines form one example, so expressions can span several lines
+fn extract_examples(source: &str) -> Vec<Example> {
+    let mut examples = Vec::new();
+    let mut pending: Option<(usize, String)> = None;
+
+    for (i, line) in source.lines().enumerate() {
+        let line = line.trim_start();
+        if let Some(rest) = line.strip_prefix(EXAMPLE_PREFIX) {
+            match &mut pending {
+                Some((_, example)) => {
+                    example.push('\n');
+                    example.push_str(rest);
+                }
+                None => pending = Some((i + 1, rest.to_string())),
+            }
+        } else if let Some(rest) = line.strip_prefix(EXPECTED_PREFIX) {
+            if let Some((line, source)) = pending.take() {
+                examples.push(Example {
+                    line,
+                    source,
+                    expected: rest.trim().to_string(),
+                });
+            }
+        } else {
+            pending = None;
+        }
+    }
+
+    examples
+}
+
+// check_source loads source and runs its examples, returning a description of each failure
+fn check_source(source: &str) -> Result<(usize, Vec<String>), String> {
+    let mut interp = Interpreter::new(Options::default());
+    let env = interp.new_global_env();
+
+    for node in super::read_source(source)? {
+        interp.eval_ast(node, env);
+        interp.run().map_err(|err| err.to_string())?;
+    }
+
+    let examples = extract_examples(source);
+    let mut failures = Vec::new();
+    for example in &examples {
+        let mut actual = Err("no expression".to_string());
+        match super::read_source(&example.source) {
+            Ok(nodes) => {
+                for node in nodes {
+                    interp.eval_ast(node, env);
+                    actual = interp
+                        .run()
+                        .map(|val| interp.alloc.get_val(val).to_string(&interp.alloc))
+                        .map_err(|err| err.to_string());
+                }
+            }
+            Err(err) => actual = Err(err),
+        }
+
+        match actual {
+            Ok(actual) if actual == example.expected => {}
+            Ok(actual) => failures.push(format!(
+                "line {}: expected {}, got {}",
+                example.line, example.expected, actual
+            )),
+            Err(err) => failures.push(format!("line {}: {}", example.line, err)),
+        }
+    }
+
+    Ok((examples.len(), failures))
+}
+
+// test_docs runs the examples in each file, returning the exit status for the process
+pub fn test_docs(paths: &[PathBuf]) -> i32 {
+    let mut total = 0;
+    let mut failed = 0;
+
+    for path in paths {
+        let source = match std::fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(err) => {
+                eprintln!("{}: unable to read: {}", path.display(), err);
+                failed += 1;
+                continue;
+            }
+        };
+
+        match check_source(&source) {
+            Ok((count, failures)) => {
+                total += count;
+                failed += failures.len();
+                for failure in failures {
+                    eprintln!("{}: {}", path.display(), failure);
+                }
+            }
+            Err(err) => {
+                eprintln!("{}: unable to load: {}", path.display(), err);
+                failed += 1;
+            }
+        }
+    }
+
+    println!("{} examples, {} failures", total, failed);
+    if failed == 0 {
+        0
+    } else {
+        1
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn extracts_examples() {
+        let source = "
+            ;; squares its argument
+            ;;> (square
+            ;;>   3)
+            ;;= 9
+            (define square (lambda (x) (* x x)))";
+        assert_eq!(
+            extract_examples(source),
+            vec![Example {
+                line: 3,
+                source: " (square\n   3)".to_string(),
+                expected: "9".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn reports_failing_examples() {
+        let source = "
+            ;;> (square 3)
+            ;;= 9
+            ;;> (square 2)
+            ;;= 5
+            (define square (lambda (x) (* x x)))";
+        assert_eq!(
+            check_source(source),
+            Ok((2, vec!["line 4: expected 5, got 4".to_string()]))
+        );
+    }
+
+    #[test]
+    fn metacircular_example_passes() {
+        let source = include_str!("../../examples/metacircular.scm");
+        let (count, failures) = check_source(source).unwrap();
+        assert_eq!(failures, Vec::<String>::new());
+        assert_eq!(count, 5);
+    }
+}
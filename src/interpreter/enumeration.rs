@@ -0,0 +1,193 @@
+use crate::interpreter::allocator::{Environment, Ptr};
+use crate::interpreter::syntax;
+use crate::interpreter::value::{hash, Arity, Function, NativeFunction, Set, Value};
+use crate::interpreter::{Error, Interpreter};
+
+// the parameter of the generated predicate and constructor
+const PARAM: &str = "obj";
+
+// is_member is the body of an enumeration predicate, given the set of members and an object
+fn is_member(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let hash = hash(&interp.alloc, args[1]);
+    let res = match interp.alloc.get_val(args[0]) {
+        Value::Set(set) => set.contains(&interp.alloc, hash, args[1]),
+        _ => unreachable!(),
+    };
+    ret!(interp, Value::Bool(res))
+}
+
+// check_member is the body of an enumeration constructor, given the enumeration's name,
+// the set of members and an object, which it returns if it is a member
+fn check_member(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let hash = hash(&interp.alloc, args[2]);
+    let (name, member) = match (interp.alloc.get_val(args[0]), interp.alloc.get_val(args[1])) {
+        (Value::String(name), Value::Set(set)) => {
+            (name, set.contains(&interp.alloc, hash, args[2]))
+        }
+        _ => unreachable!(),
+    };
+    if !member {
+        bail!(
+            interp,
+            "{}: {} is not a member of the enumeration",
+            name,
+            interp.alloc.get_val(args[2]).to_string(&interp.alloc)
+        );
+    }
+    interp.results.push(args[2]);
+}
+
+impl Interpreter {
+    // define_enumeration evaluates (define-enumeration type (member ...) predicate)
+    // each member name is bound as a constant to its own symbol,
+    // (predicate obj) tests whether obj is a member
+    // and (type obj) returns obj if it is a member and fails otherwise
+    pub(super) fn define_enumeration(&mut self, nodes: &[Ptr<Value>], env: Ptr<Environment>) {
+        if let Err(message) = syntax::DEFINE_ENUMERATION.validate(&self.alloc, &nodes[1..]) {
+            self.error = Some(Error {
+                message,
+                location: None,
+            });
+            return;
+        }
+
+        let symbol = |interp: &Self, node| match interp.alloc.get_val(node) {
+            Value::Symbol(s) => s.clone(),
+            _ => unreachable!(),
+        };
+        let type_name = symbol(self, nodes[1]);
+        let predicate_name = symbol(self, nodes[3]);
+
+        let mut members = Set::new();
+        let mut member_list = nodes[2];
+        while let Value::Cons(hd, tl) = self.alloc.get_val(member_list) {
+            let (member, rest) = (*hd, *tl);
+            let hash = hash(&self.alloc, member);
+            if members.contains(&self.alloc, hash, member) {
+                self.error = Some(Error {
+                    message: format!(
+                        "define-enumeration: {} is listed more than once",
+                        symbol(self, member)
+                    ),
+                    location: None,
+                });
+                return;
+            }
+            members.insert(hash, member);
+            member_list = rest;
+        }
+
+        let mut bindings = Vec::new();
+        for &member in &members.items {
+            bindings.push((symbol(self, member), member, true));
+        }
+        let members = Value::Set(members).gc(&mut self.alloc);
+        let param = Value::Symbol(PARAM.to_string()).gc(&mut self.alloc);
+
+        // the generated functions refer to their natives and the member set directly,
+        // which evaluate to themselves, so later definitions cannot change their behavior
+        let predicate = NativeFunction {
+            name: predicate_name.clone(),
+            func: is_member,
+            arity: Arity::exactly(2),
+        };
+        let predicate = Value::NativeFunction(predicate).gc(&mut self.alloc);
+        let call = Value::from_vec(&[predicate, members, param], &mut self.alloc);
+        let body = Value::from_vec(&[call], &mut self.alloc);
+        let predicate = Function {
+            name: Some(predicate_name.clone()),
+            args: vec![PARAM.to_string()],
+            env,
+            body,
+        };
+        bindings.push((
+            predicate_name,
+            Value::Function(predicate).gc(&mut self.alloc),
+            false,
+        ));
+
+        let constructor = NativeFunction {
+            name: type_name.clone(),
+            func: check_member,
+            arity: Arity::exactly(3),
+        };
+        let constructor = Value::NativeFunction(constructor).gc(&mut self.alloc);
+        let name = Value::String(type_name.clone()).gc(&mut self.alloc);
+        let call = Value::from_vec(&[constructor, name, members, param], &mut self.alloc);
+        let body = Value::from_vec(&[call], &mut self.alloc);
+        let constructor = Function {
+            name: Some(type_name.clone()),
+            args: vec![PARAM.to_string()],
+            env,
+            body,
+        };
+        bindings.push((
+            type_name,
+            Value::Function(constructor).gc(&mut self.alloc),
+            false,
+        ));
+
+        for (name, value, constant) in bindings {
+            let res = self.check_frozen(&name, env).and_then(|()| {
+                if constant {
+                    self.alloc.set_constant_value(env, name, value)
+                } else {
+                    self.alloc.set_bound_value(env, name, value)
+                }
+            });
+            if let Err(message) = res {
+                self.error = Some(Error {
+                    message,
+                    location: None,
+                });
+                return;
+            }
+        }
+        self.results.push(Value::Nil.gc(&mut self.alloc));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::interpreter::test::run;
+
+    #[test]
+    fn defines_enumerations() {
+        let defn = "(define-enumeration color (red green blue) color?) ";
+        assert_eq!(run(&format!("{}red", defn)), Ok("red".to_string()));
+        assert_eq!(
+            run(&format!("{}(color? 'green)", defn)),
+            Ok("#t".to_string())
+        );
+        assert_eq!(
+            run(&format!("{}(color? 'purple)", defn)),
+            Ok("#f".to_string())
+        );
+        assert_eq!(
+            run(&format!("{}(color blue)", defn)),
+            Ok("blue".to_string())
+        );
+        assert_eq!(
+            run(&format!("{}(color 'purple)", defn)),
+            Err("color: purple is not a member of the enumeration".to_string())
+        );
+        assert_eq!(
+            run(&format!("{}(define red 1)", defn)),
+            Err("cannot redefine constant binding: red".to_string())
+        );
+        // the generated functions do not depend on redefinable bindings
+        assert_eq!(
+            run(&format!("{}(define set-member? 1) (color? 'red)", defn)),
+            Ok("#t".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_enumerations() {
+        assert_eq!(
+            run("(define-enumeration color (red red) color?)"),
+            Err("define-enumeration: red is listed more than once".to_string())
+        );
+        assert!(run("(define-enumeration color (red 1) color?)").is_err());
+    }
+}
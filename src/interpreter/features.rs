@@ -0,0 +1,172 @@
+use crate::interpreter::allocator::{Environment, Ptr};
+use crate::interpreter::syntax;
+use crate::interpreter::value::{list_items, Value};
+use crate::interpreter::Interpreter;
+
+const COND_EXPAND: &str = "cond-expand";
+
+// default_features are the feature identifiers recognized by cond-expand,
+// including the operating system, its family and the processor architecture
+pub(super) fn default_features() -> Vec<String> {
+    [
+        "r7rs",
+        "ratios",
+        "full-unicode",
+        "scheme-interp-rs",
+        std::env::consts::OS,
+        std::env::consts::FAMILY,
+        std::env::consts::ARCH,
+    ]
+    .iter()
+    .map(|feature| feature.to_string())
+    .collect()
+}
+
+// cond-expand is expanded when it is evaluated, before any of its clauses are:
+// it is replaced by the body of the first clause whose feature requirement is met,
+// see Interpreter::eval_expansion
+impl Interpreter {
+    // is_cond_expand checks whether node is a use of cond-expand in env
+    pub(super) fn is_cond_expand(&self, node: Ptr<Value>, env: Ptr<Environment>) -> bool {
+        self.uses_keyword(node, env, COND_EXPAND)
+    }
+
+    // expand_cond_expand returns the body of the first clause of a cond-expand whose requirement
+    // is met, which is empty if there is none
+    pub(super) fn expand_cond_expand(&self, node: Ptr<Value>) -> Result<Vec<Ptr<Value>>, String> {
+        let nodes = list_items(&self.alloc, node).unwrap_or_default();
+        syntax::COND_EXPAND.validate(&self.alloc, &nodes[1..])?;
+
+        let clauses = &nodes[1..];
+        for (i, &clause) in clauses.iter().enumerate() {
+            let items = match list_items(&self.alloc, clause) {
+                Some(items) if !items.is_empty() => items,
+                _ => {
+                    return Err(format!(
+                        "{}: clause must be a non-empty list, got {}",
+                        COND_EXPAND,
+                        self.alloc.get_val(clause).to_string(&self.alloc)
+                    ))
+                }
+            };
+            let is_else = matches!(self.alloc.get_val(items[0]), Value::Symbol(s) if s == "else");
+            if is_else && i != clauses.len() - 1 {
+                return Err(format!("{}: else must be the last clause", COND_EXPAND));
+            }
+            if is_else || self.requirement_met(items[0])? {
+                return Ok(items[1..].to_vec());
+            }
+        }
+        Ok(Vec::new())
+    }
+
+    // requirement_met checks a feature requirement: a feature identifier,
+    // or (and requirement ...), (or requirement ...), (not requirement) or (library name)
+    fn requirement_met(&self, requirement: Ptr<Value>) -> Result<bool, String> {
+        if let Value::Symbol(feature) = self.alloc.get_val(requirement) {
+            return Ok(self.features.contains(feature));
+        }
+
+        let items = list_items(&self.alloc, requirement).unwrap_or_default();
+        let operator = match items.first().map(|&op| self.alloc.get_val(op)) {
+            Some(Value::Symbol(s)) => s.as_str(),
+            _ => "",
+        };
+        match (operator, items.len()) {
+            ("and", _) => {
+                for &item in &items[1..] {
+                    if !self.requirement_met(item)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            ("or", _) => {
+                for &item in &items[1..] {
+                    if self.requirement_met(item)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+            ("not", 2) => Ok(!self.requirement_met(items[1])?),
+            // there is no library system, so no library can be imported
+            ("library", 2) => Ok(false),
+            _ => Err(format!(
+                "{}: invalid feature requirement {}",
+                COND_EXPAND,
+                self.alloc.get_val(requirement).to_string(&self.alloc)
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::interpreter::test::run;
+    use crate::interpreter::{Interpreter, Options};
+
+    #[test]
+    fn expands_matching_clauses() {
+        assert_eq!(
+            run("(cond-expand ((and r7rs (not no-such-feature)) 1 2) (else 3))"),
+            Ok("2".to_string())
+        );
+        assert_eq!(
+            run("(cond-expand ((or no-such-feature (library (scheme base))) 1) (else 3))"),
+            Ok("3".to_string())
+        );
+        assert_eq!(
+            run("(cond-expand (no-such-feature 1))"),
+            Ok("()".to_string())
+        );
+        // clauses which are not chosen are never evaluated
+        assert_eq!(
+            run("(+ 1 (cond-expand (scheme-interp-rs 2) (else (car '()))))"),
+            Ok("3".to_string())
+        );
+    }
+
+    #[test]
+    fn splices_definitions() {
+        assert_eq!(
+            run("(cond-expand (r7rs (define x 1) (define y 2))) (+ x y)"),
+            Ok("3".to_string())
+        );
+        assert_eq!(
+            run("((lambda () (cond-expand (else (define x 1))) (define y 2) (+ x y)))"),
+            Ok("3".to_string())
+        );
+        assert!(run("(+ 1 (cond-expand (r7rs (define x 1) 2)))").is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_clauses() {
+        assert_eq!(
+            run("(cond-expand)"),
+            Err(
+                "cond-expand: expected at least 1 subexpression, got 0; missing clauses"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            run("(cond-expand (else 1) (r7rs 2))"),
+            Err("cond-expand: else must be the last clause".to_string())
+        );
+        assert_eq!(
+            run("(cond-expand ((nand r7rs) 1))"),
+            Err("cond-expand: invalid feature requirement (nand r7rs)".to_string())
+        );
+    }
+
+    #[test]
+    fn embedders_configure_features() {
+        let mut interp = Interpreter::new(Options::default());
+        interp.add_feature("sandboxed");
+        interp.remove_feature("r7rs");
+        let res = interp.eval("(cond-expand ((and sandboxed (not r7rs)) 1) (else 2))");
+        assert_eq!(interp.to_string(&res.unwrap()), "1");
+        let res = interp.eval("(car (features))");
+        assert_eq!(interp.to_string(&res.unwrap()), "ratios");
+    }
+}
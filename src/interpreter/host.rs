@@ -0,0 +1,145 @@
+use crate::interpreter::allocator::{Environment, Ptr};
+use crate::interpreter::convert::IntoScheme;
+use crate::interpreter::value::Value;
+use crate::interpreter::{read_source, Error, Interpreter};
+use std::rc::Rc;
+
+// ValueHandle is a reference to a value held by the host
+// the value stays alive across garbage collections for as long as the handle exists
+#[derive(Clone)]
+pub struct ValueHandle {
+    ptr: Ptr<Value>,
+    _root: Rc<()>,
+}
+
+impl ValueHandle {
+    pub fn ptr(&self) -> Ptr<Value> {
+        self.ptr
+    }
+}
+
+impl Interpreter {
+    // global_env returns the environment the host API works in, creating it on first use
+    pub fn global_env(&mut self) -> Ptr<Environment> {
+        match self.globals {
+            Some(env) => env,
+            None => {
+                let env = self.new_global_env();
+                self.globals = Some(env);
+                env
+            }
+        }
+    }
+
+    // handle roots ptr, returning a handle to it for the host
+    pub fn handle(&mut self, ptr: Ptr<Value>) -> ValueHandle {
+        ValueHandle {
+            ptr,
+            _root: self.alloc.root(ptr),
+        }
+    }
+
+    // get returns the value a handle refers to
+    pub fn get(&self, handle: &ValueHandle) -> &Value {
+        self.alloc.get_val(handle.ptr)
+    }
+
+    // to_string prints the value a handle refers to as the REPL would
+    pub fn to_string(&self, handle: &ValueHandle) -> String {
+        self.get(handle).to_string(&self.alloc)
+    }
+
+    // define_global binds name in the global environment, failing if it's a constant
+    pub fn define_global(&mut self, name: &str, val: impl IntoScheme) -> Result<(), Error> {
+        let env = self.global_env();
+        let ptr = val.into_scheme(self);
+        self.alloc
+            .set_bound_value(env, name.to_string(), ptr)
+            .map_err(|message| Error {
+                message,
+                location: None,
+            })
+    }
+
+    // lookup_global returns the value bound to name in the global environment
+    pub fn lookup_global(&mut self, name: &str) -> Option<ValueHandle> {
+        let env = self.global_env();
+        let ptr = self.alloc.get_bound_ptr(env, name)?;
+        Some(self.handle(ptr))
+    }
+
+    // call_global applies the procedure bound to name in the global environment to args
+    pub fn call_global(&mut self, name: &str, args: &[&ValueHandle]) -> Result<ValueHandle, Error> {
+        let env = self.global_env();
+        let func = match self.alloc.get_bound_ptr(env, name) {
+            Some(func) => func,
+            None => {
+                return Err(Error {
+                    message: format!("unbound symbol: {}", name),
+                    location: None,
+                })
+            }
+        };
+        let args = args.iter().map(|arg| arg.ptr).collect::<Vec<_>>();
+        let res = self.call(func, &args, env)?;
+        Ok(self.handle(res))
+    }
+
+    // add_feature makes cond-expand recognize the feature identifier feature
+    pub fn add_feature(&mut self, feature: &str) {
+        if !self.features.iter().any(|f| f == feature) {
+            self.features.push(feature.to_string());
+        }
+    }
+
+    // remove_feature stops cond-expand recognizing the feature identifier feature
+    pub fn remove_feature(&mut self, feature: &str) {
+        self.features.retain(|f| f != feature);
+    }
+
+    // eval evaluates each form in source in the global environment,
+    // returning the value of the last one
+    pub fn eval(&mut self, source: &str) -> Result<ValueHandle, Error> {
+        let env = self.global_env();
+        let mut res = Value::Nil.gc(&mut self.alloc);
+        for node in read_source(source).map_err(|message| Error {
+            message,
+            location: None,
+        })? {
+            self.eval_ast(node, env);
+            res = self.run()?;
+        }
+        Ok(self.handle(res))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::interpreter::{Interpreter, Options, Value};
+
+    #[test]
+    fn defines_and_looks_up_globals() {
+        let mut interp = Interpreter::new(Options::default());
+        interp.define_global("limit", Value::Integer(10)).unwrap();
+        let res = interp.eval("(define twice (lambda (x) (* x 2))) (twice limit)");
+        assert_eq!(interp.to_string(&res.unwrap()), "20");
+
+        let limit = interp.lookup_global("limit").unwrap();
+        let res = interp.call_global("twice", &[&limit]).unwrap();
+        assert_eq!(interp.to_string(&res), "20");
+        assert!(interp.lookup_global("missing").is_none());
+        assert_eq!(
+            interp.call_global("missing", &[]).err().unwrap().message,
+            "unbound symbol: missing"
+        );
+    }
+
+    #[test]
+    fn handles_survive_collection() {
+        let mut interp = Interpreter::new(Options::default());
+        let list = interp.eval("(cons 1 (cons 2 nil))").unwrap();
+        interp.eval("(gc-run)").unwrap();
+        interp.eval("(cons 3 (cons 4 nil))").unwrap();
+        assert_eq!(interp.to_string(&list), "(1 2)");
+    }
+}
@@ -0,0 +1,158 @@
+use crate::interpreter::allocator::{Environment, Ptr};
+use crate::interpreter::syntax;
+use crate::interpreter::value::{list_items, Value};
+use crate::interpreter::{read_source, Interpreter};
+use crate::parse::{Node, AST};
+use std::path::Path;
+
+const INCLUDE: &str = "include";
+
+// resolve_includes rewrites the relative file names of include forms in node read from a file
+// in dir to be relative to dir instead, so that they are found however late they are evaluated
+// quoted data is left alone
+pub(super) fn resolve_includes(node: &mut Node, dir: &Path) {
+    let items = match &mut node.ast {
+        AST::List(items) => items,
+        _ => return,
+    };
+    match items.first().map(|item| &item.ast) {
+        Some(AST::Symbol(s)) if s == "quote" || s == "quasiquote" => return,
+        Some(AST::Symbol(s)) if s == INCLUDE => {
+            for item in &mut items[1..] {
+                if let AST::String(name) = &mut item.ast {
+                    if Path::new(name).is_relative() {
+                        *name = dir.join(&name).to_string_lossy().into_owned();
+                    }
+                }
+            }
+            return;
+        }
+        _ => {}
+    }
+    for item in items {
+        resolve_includes(item, dir);
+    }
+}
+
+// include reads the forms of each file it names, in order, which are evaluated in its place,
+// see Interpreter::eval_expansion
+// relative file names in a file are relative to the file's directory,
+// and elsewhere to the working directory
+impl Interpreter {
+    // is_include checks whether node is a use of include in env
+    pub(super) fn is_include(&self, node: Ptr<Value>, env: Ptr<Environment>) -> bool {
+        self.uses_keyword(node, env, INCLUDE)
+    }
+
+    // read_include reads the forms of the files named by an include form
+    pub(super) fn read_include(&mut self, node: Ptr<Value>) -> Result<Vec<Ptr<Value>>, String> {
+        let nodes = list_items(&self.alloc, node).unwrap_or_default();
+        syntax::INCLUDE.validate(&self.alloc, &nodes[1..])?;
+
+        let mut forms = Vec::new();
+        for &name in &nodes[1..] {
+            let path = match self.alloc.get_val(name) {
+                Value::String(name) => Path::new(name).to_path_buf(),
+                val => {
+                    return Err(format!(
+                        "{}: file name must be a string, got {}",
+                        INCLUDE,
+                        val.to_string(&self.alloc)
+                    ))
+                }
+            };
+            let source = std::fs::read_to_string(&path).map_err(|err| {
+                format!("{}: unable to read {}: {}", INCLUDE, path.display(), err)
+            })?;
+            let nodes = read_source(&source)
+                .map_err(|message| format!("{}: {}: {}", INCLUDE, path.display(), message))?;
+
+            let dir = path.parent().unwrap_or_else(|| Path::new(""));
+            for mut node in nodes {
+                resolve_includes(&mut node, dir);
+                forms.push(Value::from_ast(node, &mut self.alloc));
+            }
+        }
+        Ok(forms)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::interpreter::test::run;
+    use std::fs;
+    use std::path::PathBuf;
+
+    // scratch_dir creates an empty directory for the files of a test
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("scheme-include-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn splices_included_forms() {
+        let dir = scratch_dir("splice");
+        fs::write(dir.join("main.scm"), "(include \"lib/square.scm\")").unwrap();
+        // lib/square.scm includes lib/double.scm relative to itself
+        fs::write(
+            dir.join("lib/square.scm"),
+            "(define (square x) (* x x)) (include \"double.scm\")",
+        )
+        .unwrap();
+        fs::write(dir.join("lib/double.scm"), "(define (double x) (+ x x))").unwrap();
+        let main = dir.join("main.scm");
+
+        assert_eq!(
+            run(&format!("(include {:?}) (double (square 3))", main)),
+            Ok("18".to_string())
+        );
+        // included definitions are local to a lambda body
+        assert_eq!(
+            run(&format!(
+                "(define (f) (include {:?}) (square 2)) (cons (f) (procedure? square))",
+                main
+            )),
+            Err("unbound symbol: square".to_string())
+        );
+        assert_eq!(
+            run(&format!("(+ 1 (include {:?} {:?}))", main, main)),
+            Err(format!("define: {}", super::super::DEFINE_CONTEXT_ERROR))
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn reports_include_errors() {
+        let dir = scratch_dir("errors");
+        let looping = dir.join("loop.scm");
+        fs::write(&looping, "(include \"loop.scm\")").unwrap();
+        let broken = dir.join("broken.scm");
+        fs::write(&broken, "(define x").unwrap();
+
+        assert_eq!(
+            run(&format!("(include {:?})", looping)),
+            Err(
+                "cond-expand and include forms are nested too deeply, a file may be including itself"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            run(&format!("(include {:?})", broken)),
+            Err(format!(
+                "include: {}: Syntax error at line 0, col 0: missing matching ')'",
+                broken.display()
+            ))
+        );
+        assert!(run("(include \"no/such/file.scm\")")
+            .unwrap_err()
+            .starts_with("include: unable to read no/such/file.scm"));
+        assert_eq!(
+            run("(include 1)"),
+            Err("include: file name must be a string, got 1".to_string())
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
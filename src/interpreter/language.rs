@@ -0,0 +1,151 @@
+use std::fmt;
+use std::str::FromStr;
+
+// the builtins that mutate data, unavailable in the beginner language
+const MUTATORS: &[&str] = &[
+    "set-add!",
+    "set-object-property!",
+    "hash-table-set!",
+    "hash-table-delete!",
+    "hash-table-update!",
+    "hash-table-update!/default",
+    "vector-set!",
+    "bitvector-set!",
+    "enqueue!",
+    "dequeue!",
+    "guardian-register!",
+    "guardian-pop!",
+    "heap-push!",
+    "heap-pop!",
+    "array-set!",
+    "array-map!",
+    "s32vector-set!",
+    "f64vector-set!",
+    "sb-add!",
+    "repl-prompt!",
+    "repl-banner!",
+];
+
+// Language is a language level, restricting the language for teaching
+// full currently matches standard, it is where future non-standard extensions are enabled
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Language {
+    // no continuations, mutation or redefinition
+    Beginner,
+    #[default]
+    Standard,
+    Full,
+}
+
+impl Language {
+    // restriction explains why the builtin name is unavailable in the language, if it is
+    pub(super) fn restriction(self, name: &str) -> Option<&'static str> {
+        match self {
+            Language::Beginner if name == "call/cc" => {
+                Some("continuations are not part of the beginner language")
+            }
+            Language::Beginner if MUTATORS.contains(&name) => {
+                Some("the beginner language does not allow mutation, build a new value instead")
+            }
+            _ => None,
+        }
+    }
+
+    // allows_redefinition reports whether a name may be defined again in the same scope
+    pub(super) fn allows_redefinition(self) -> bool {
+        self != Language::Beginner
+    }
+}
+
+impl FromStr for Language {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "beginner" => Ok(Language::Beginner),
+            "standard" => Ok(Language::Standard),
+            "full" => Ok(Language::Full),
+            _ => Err(format!(
+                "unknown language '{}', expected beginner, standard or full",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Language::Beginner => "beginner",
+            Language::Standard => "standard",
+            Language::Full => "full",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::MUTATORS;
+    use crate::interpreter::{Interpreter, Language, Options};
+
+    #[test]
+    fn restricts_the_beginner_language() {
+        let mut interp = Interpreter::new(Options {
+            language: Language::Beginner,
+            ..Options::default()
+        });
+        let message = |interp: &mut Interpreter, source| interp.eval(source).err().unwrap().message;
+
+        assert_eq!(
+            message(&mut interp, "(vector-set! (vector 1) 0 2)"),
+            "vector-set! is not available: the beginner language does not allow mutation, \
+             build a new value instead"
+        );
+        assert_eq!(
+            message(&mut interp, "(call/cc (lambda (k) 1))"),
+            "call/cc is not available: continuations are not part of the beginner language"
+        );
+        interp.eval("(define x 1)").unwrap();
+        assert_eq!(
+            message(&mut interp, "(define x 2)"),
+            "x is already defined, the beginner language does not allow redefinition"
+        );
+        assert_eq!(message(&mut interp, "y"), "unbound symbol: y");
+
+        let mut interp = Interpreter::new(Options::default());
+        interp
+            .eval("(define x 1) (define x (vector-ref (vector 2) 0))")
+            .unwrap();
+    }
+
+    #[test]
+    fn restricts_every_mutator() {
+        let mut interp = Interpreter::new(Options::default());
+        let env = interp.new_global_env();
+        let mut names = interp.alloc.binding_names(env);
+        names.retain(|name| name.ends_with('!'));
+        names.push("hash-table-update!/default".to_string());
+        assert!(names.len() >= MUTATORS.len());
+
+        let mut beginner = Interpreter::new(Options {
+            language: Language::Beginner,
+            ..Options::default()
+        });
+        let env = beginner.new_global_env();
+        for name in names {
+            assert!(
+                Language::Beginner.restriction(&name).is_some(),
+                "{} is allowed in the beginner language",
+                name
+            );
+            assert!(beginner.alloc.get_bound_ptr(env, &name).is_none());
+        }
+    }
+
+    #[test]
+    fn parses_language_names() {
+        assert_eq!("full".parse::<Language>(), Ok(Language::Full));
+        assert!("expert".parse::<Language>().is_err());
+    }
+}
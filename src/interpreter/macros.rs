@@ -0,0 +1,43 @@
+// Helpers for writing native functions.
+// They are used inside a native, where returning early from the native
+// after setting interp.error or pushing a result ends the call.
+
+// bail sets a runtime error with a formatted message and returns from the native
+macro_rules! bail {
+    ($interp:expr, $($fmt:tt)+) => {{
+        $interp.error = Some($crate::interpreter::Error {
+            message: format!($($fmt)+),
+            location: None,
+        });
+        return;
+    }};
+}
+
+// expect_int evaluates to the integer at ptr, bailing with the formatted message otherwise
+macro_rules! expect_int {
+    ($interp:expr, $ptr:expr, $($fmt:tt)+) => {
+        match $interp.alloc.get_val($ptr) {
+            $crate::interpreter::value::Value::Integer(i) => *i,
+            _ => bail!($interp, $($fmt)+),
+        }
+    };
+}
+
+// expect_num evaluates to the Number at ptr, bailing with the formatted message otherwise
+macro_rules! expect_num {
+    ($interp:expr, $ptr:expr, $($fmt:tt)+) => {
+        match $crate::interpreter::number::Number::from_value(&$interp.alloc, $ptr) {
+            Some(num) => num,
+            None => bail!($interp, $($fmt)+),
+        }
+    };
+}
+
+// ret allocates val, pushes it as the result of the native and returns
+macro_rules! ret {
+    ($interp:expr, $val:expr) => {{
+        let ptr = $crate::interpreter::value::Value::gc($val, &mut $interp.alloc);
+        $interp.results.push(ptr);
+        return;
+    }};
+}
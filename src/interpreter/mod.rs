@@ -1,61 +1,448 @@
+#[macro_use]
+mod macros;
+
 mod allocator;
+mod convert;
+mod date;
+mod definition;
+pub mod doctest;
+mod enumeration;
+mod features;
+mod host;
+mod include;
+mod language;
+mod number;
+mod plugin;
+mod quasiquote;
 pub mod repl;
+mod rng;
+pub mod script;
 mod stdlib;
+mod syntax;
 mod value;
 
+pub use self::allocator::{Environment, GcCause, GcEvent, HeapStats, Metrics, Ptr};
+pub use self::convert::{FromScheme, IntoScheme};
+pub use self::host::ValueHandle;
+pub use self::language::Language;
+pub use self::plugin::{Plugin, StdlibRegistry};
 use self::value::*;
-use crate::interpreter::allocator::{Allocator, Environment, Ptr};
-use crate::parse::AST;
+pub use self::value::{Arity, NativeFn, Value};
+use crate::interpreter::allocator::{Allocator, GcLog};
+use crate::interpreter::repl::ReplConfig;
+use crate::interpreter::rng::Rng;
+use crate::parse::{Location, Node};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::panic;
 
 #[derive(Debug)]
 pub struct Error {
     pub message: String,
+    // the expression being evaluated when the error occurred, if it was read from source
+    pub location: Option<Location>,
 }
 
-impl ToString for Error {
-    fn to_string(&self) -> String {
-        format!("Runtime error: {}", self.message)
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.location {
+            Some(location) => write!(
+                f,
+                "Runtime error at line {}, col {}: {}",
+                location.line, location.column, self.message
+            ),
+            None => write!(f, "Runtime error: {}", self.message),
+        }
     }
 }
 
+// Output is a line written by a native, or a warning, while output is being captured
+pub(super) enum Output {
+    Line(String),
+    Warning(String),
+}
+
 const UNBOUND_SYMBOL_ERROR: &str = "unbound symbol";
+const UNINITIALIZED_BINDING_ERROR: &str = "internal definition used before its value was evaluated";
 const EVAL_EMPTY_LIST_ERROR: &str = "cannot evaluate empty list";
 const EVAL_BAD_LIST_ERROR: &str = "attempt to evaluate malformed list";
 const WRONG_NUMBER_ARGS_ERROR: &str = "wrong number of arguments";
-const INVALID_IF_ERROR: &str = "invalid structure for if expression";
-const INVALID_LAMBDA_ERROR: &str = "invalid structure for lambda expression";
-const INVALID_DEFINE_ERROR: &str = "invalid structure for define expression";
-const INVALID_QUOTE_ERROR: &str = "invalid structure for quote expression";
+const CONTINUATION_DEPTH_ERROR: &str =
+    "continuation resumed outside of the native call it was captured in";
+const CONTINUATION_REINVOKED_ERROR: &str = "one-shot continuation invoked more than once";
+const NATIVE_PANIC_ERROR: &str = "native function panicked";
+const DEFINE_CONTEXT_ERROR: &str = "definitions are only allowed at the top level or at the \
+     start of a lambda body; move this definition to the start of the enclosing body, \
+     or pass the value in as a lambda parameter";
+const ALLOCATION_LIMIT_ERROR: &str = "allocation limit exceeded";
+const PINNED_HEAP_ERROR: &str = "heap exhausted: evaluation outgrew the pinned heap";
+const FROZEN_STDLIB_ERROR: &str =
+    "the standard library is frozen by the freeze_stdlib option (--freeze-stdlib)";
 
 trait StepTrait: FnOnce(&mut Interpreter) {
-    fn clone_box(&self) -> Step;
+    fn clone_box(&self) -> Box<dyn StepTrait>;
 }
 
 impl<T: 'static + FnOnce(&mut Interpreter) + Clone> StepTrait for T {
-    fn clone_box(&self) -> Step {
+    fn clone_box(&self) -> Box<dyn StepTrait> {
         Box::new(self.clone())
     }
 }
 
-type Step = Box<dyn StepTrait>;
+// StepKind describes what a pending step does, for inspecting continuations
+#[derive(Clone)]
+enum StepKind {
+    // evaluate an expression
+    Eval(Ptr<Value>),
+    // evaluate an expression within a quasiquote
+    Quasiquote(Ptr<Value>),
+    // apply a function to the values on the results stack
+    Apply,
+    // choose an if branch from the condition on the results stack
+    Branch,
+    // bind the value on the results stack
+    Define(String),
+    // build a list from quasiquoted values
+    BuildList,
+    // pop the frame of a call that has returned
+    Return,
+}
+
+impl StepKind {
+    fn to_string(&self, alloc: &Allocator) -> String {
+        match self {
+            StepKind::Eval(node) => format!("eval {}", alloc.get_val(*node).to_string(alloc)),
+            StepKind::Quasiquote(node) => {
+                format!("quasiquote {}", alloc.get_val(*node).to_string(alloc))
+            }
+            StepKind::Apply => "apply".to_string(),
+            StepKind::Branch => "branch".to_string(),
+            StepKind::Define(name) => format!("define {}", name),
+            StepKind::BuildList => "build list".to_string(),
+            StepKind::Return => "return".to_string(),
+        }
+    }
+}
+
+// Frame is a call in progress, kept for backtraces
+// call is the expression the call was made from, or None if it was applied by a native
+#[derive(Clone, Copy)]
+struct Frame {
+    func: Ptr<Value>,
+    call: Option<Ptr<Value>>,
+}
+
+struct Step {
+    kind: StepKind,
+    func: Box<dyn StepTrait>,
+}
+
+impl Step {
+    fn new(kind: StepKind, func: impl 'static + FnOnce(&mut Interpreter) + Clone) -> Self {
+        Step {
+            kind,
+            func: Box::new(func),
+        }
+    }
+}
+
+impl Clone for Step {
+    fn clone(&self) -> Self {
+        Step {
+            kind: self.kind.clone(),
+            func: self.func.clone_box(),
+        }
+    }
+}
+
+// Options configures optional interpreter behaviour
+#[derive(Default)]
+pub struct Options {
+    // continuations are invalidated after their first invocation,
+    // releasing their captured state instead of retaining it indefinitely
+    pub one_shot_continuations: bool,
+    // the standard library bindings cannot be redefined
+    pub lock_stdlib: bool,
+    // like lock_stdlib, and references to the standard library are cached where they are
+    // evaluated, since the bindings they resolve to can never change
+    pub freeze_stdlib: bool,
+    // print a warning when a definition or parameter shadows an existing binding
+    pub warn_shadowing: bool,
+    // seeds random so runs are reproducible, seeded from the clock if None
+    pub seed: Option<u64>,
+    // the language level, which can restrict the language for teaching
+    pub language: Language,
+    // fail a top-level evaluation once it allocates more than this many values,
+    // to catch runaway programs early
+    // exceeding the limit is fatal to the evaluation: there is no condition system to catch it
+    // with, so it aborts any natives calling back into Scheme and reaches the caller of eval
+    pub max_allocations_per_eval: Option<u64>,
+}
 
 pub struct Interpreter {
+    options: Options,
     alloc: Allocator,
     next_steps: Vec<Step>,
     results: Vec<Ptr<Value>>,
     saved_results: Vec<Vec<Ptr<Value>>>,
     error: Option<Error>,
+    repl: ReplConfig,
+    exit_code: Option<i32>,
+    // the active calls, innermost last, when backtraces are enabled
+    frames: Option<Vec<Frame>>,
+    // names bound by the standard library
+    builtins: HashSet<String>,
+    // number of nested step loops started by Interpreter::call
+    call_depth: usize,
+    plugins: Vec<Box<dyn Plugin>>,
+    // the environment used by the host API
+    globals: Option<Ptr<Environment>>,
+    rng: Rng,
+    // the number of symbols made by gensym
+    gensym_count: u64,
+    // values allocated before the current top-level evaluation began
+    eval_start_allocations: u64,
+    // the location of the expression most recently evaluated or applied
+    location: Option<Location>,
+    // the feature identifiers recognized by cond-expand
+    features: Vec<String>,
+    // output and warnings kept for the caller, such as a REPL session which shows and records
+    // them with its results, instead of being written to stdout and stderr
+    captured_output: Option<Vec<Output>>,
+    // the nodes binding names which have been warned about shadowing other bindings
+    shadowing_warned: HashSet<Ptr<Value>>,
+    // the builtin each symbol node resolved to, with a frozen standard library
+    builtin_cache: HashMap<Ptr<Value>, Ptr<Value>>,
 }
 
 impl Interpreter {
-    pub fn new() -> Self {
+    // new creates an interpreter, which logs collections to stderr if SCHEME_INTERP_GC_LOG is set
+    pub fn new(options: Options) -> Self {
+        let mut alloc = Allocator::new();
+        if GcLog::enabled() {
+            alloc.set_metrics(Some(Box::new(GcLog)));
+        }
+
         Interpreter {
-            alloc: Allocator::new(),
+            rng: Rng::new(options.seed),
+            gensym_count: 0,
+            options,
+            alloc,
             next_steps: Vec::new(),
             results: Vec::new(),
             saved_results: Vec::new(),
             error: None,
+            repl: ReplConfig::new(),
+            exit_code: None,
+            frames: None,
+            builtins: HashSet::new(),
+            call_depth: 0,
+            plugins: Vec::new(),
+            globals: None,
+            eval_start_allocations: 0,
+            location: None,
+            features: features::default_features(),
+            captured_output: None,
+            shadowing_warned: HashSet::new(),
+            builtin_cache: HashMap::new(),
+        }
+    }
+
+    // set_metrics installs a hook receiving allocator telemetry, replacing any previous one,
+    // including the SCHEME_INTERP_GC_LOG logger
+    pub fn set_metrics(&mut self, metrics: Box<dyn Metrics>) {
+        self.alloc.set_metrics(Some(metrics));
+    }
+
+    pub fn heap_stats(&self) -> HeapStats {
+        self.alloc.profile()
+    }
+
+    // reserve_heap makes room for allocating the given numbers of values and environments
+    // without pausing to grow the heap mid-evaluation
+    pub fn reserve_heap(&mut self, values: usize, environments: usize) {
+        self.alloc.reserve(values, environments);
+    }
+
+    // pin_heap fixes the heap at its current capacity, failing evaluations which outgrow it,
+    // or unfixes it
+    pub fn pin_heap(&mut self, pinned: bool) {
+        self.alloc.pin(pinned);
+    }
+
+    // add_plugin registers a pack of builtins, which are bound in every global environment
+    // created afterwards, replacing any standard library bindings of the same names
+    pub fn add_plugin(&mut self, plugin: Box<dyn Plugin>) {
+        self.plugins.push(plugin);
+    }
+
+    // value returns the value ptr refers to, for inspecting arguments in plugin natives
+    pub fn value(&self, ptr: Ptr<Value>) -> &Value {
+        self.alloc.get_val(ptr)
+    }
+
+    // return_value makes val the result of the running plugin native
+    pub fn return_value(&mut self, val: impl IntoScheme) {
+        let ptr = val.into_scheme(self);
+        self.results.push(ptr);
+    }
+
+    // raise makes the running plugin native fail with a runtime error
+    pub fn raise(&mut self, message: String) {
+        self.error = Some(Error {
+            message,
+            location: None,
+        });
+    }
+
+    // new_global_env builds the standard library environment programs are evaluated in
+    fn new_global_env(&mut self) -> Ptr<Environment> {
+        let env = stdlib::build(&mut self.alloc, &self.plugins, self.options.language);
+        self.builtins = self.alloc.binding_names(env).into_iter().collect();
+        if self.options.lock_stdlib || self.options.freeze_stdlib {
+            self.alloc.make_constant(env);
         }
+        env
+    }
+
+    // check_frozen fails if binding name in env would redefine a frozen builtin
+    fn check_frozen(&self, name: &str, env: Ptr<Environment>) -> Result<(), String> {
+        if self.options.freeze_stdlib
+            && self.alloc.parent(env).is_none()
+            && self.builtins.contains(name)
+        {
+            return Err(format!("cannot redefine {}: {}", name, FROZEN_STDLIB_ERROR));
+        }
+        Ok(())
+    }
+
+    // shadowing_warning describes the binding hidden when a definition or parameter
+    // binds name in a scope nested inside outer, if there is one
+    fn shadowing_warning(&self, what: &str, name: &str, outer: Ptr<Environment>) -> Option<String> {
+        self.alloc.get_bound_ptr(outer, name)?;
+        let shadowed = if self.builtins.contains(name) {
+            "a builtin"
+        } else {
+            "an outer binding"
+        };
+        Some(format!("{} '{}' shadows {}", what, name, shadowed))
+    }
+
+    fn definition_warning(&self, name: &str, env: Ptr<Environment>) -> Option<String> {
+        match self.alloc.parent(env) {
+            Some(parent) => self.shadowing_warning("definition of", name, parent),
+            None if self.builtins.contains(name) => {
+                Some(format!("definition of '{}' replaces a builtin", name))
+            }
+            None => None,
+        }
+    }
+
+    // warn_shadowing warns about the name bound by node, with the location it was read from
+    // the warning is given the first time node is evaluated, not every time
+    fn warn_shadowing(&mut self, node: Ptr<Value>, warning: Option<String>) {
+        let warning = match warning {
+            Some(warning) if self.shadowing_warned.insert(node) => warning,
+            _ => return,
+        };
+        match self.alloc.location(node) {
+            Some(location) => self.warn(format!(
+                "Warning at line {}, col {}: {}",
+                location.line, location.column, warning
+            )),
+            None => self.warn(format!("Warning: {}", warning)),
+        }
+    }
+
+    // print writes a line of output, to stdout unless output is being captured
+    pub(super) fn print(&mut self, text: String) {
+        match &mut self.captured_output {
+            Some(captured) => captured.push(Output::Line(text)),
+            None => println!("{}", text),
+        }
+    }
+
+    // warn writes a warning, to stderr unless output is being captured
+    pub(super) fn warn(&mut self, text: String) {
+        match &mut self.captured_output {
+            Some(captured) => captured.push(Output::Warning(text)),
+            None => eprintln!("{}", text),
+        }
+    }
+
+    // capture_output starts or stops keeping output for take_output
+    pub(super) fn capture_output(&mut self, capture: bool) {
+        self.captured_output = capture.then(Vec::new);
+    }
+
+    // take_output returns the output captured since it was last taken
+    pub(super) fn take_output(&mut self) -> Vec<Output> {
+        self.captured_output
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    // enable_backtrace starts keeping the stack of active calls
+    fn enable_backtrace(&mut self) {
+        self.frames = Some(Vec::new());
+    }
+
+    // backtrace formats the calls that were active when the last evaluation failed,
+    // innermost first
+    fn backtrace(&self) -> Vec<String> {
+        let frames = match &self.frames {
+            Some(frames) => frames,
+            None => return Vec::new(),
+        };
+        frames
+            .iter()
+            .rev()
+            .map(|frame| match frame.call {
+                Some(call) => {
+                    let call_str = self.alloc.get_val(call).to_string(&self.alloc);
+                    match self.alloc.location(call) {
+                        Some(location) => format!(
+                            "{} at line {}, col {}",
+                            call_str, location.line, location.column
+                        ),
+                        None => call_str,
+                    }
+                }
+                None => self.alloc.get_val(frame.func).to_string(&self.alloc),
+            })
+            .collect()
+    }
+
+    // push_frame records a call to func as active until its Return step runs
+    // a call in tail position replaces the frame of its caller, which has nothing left to do,
+    // so loops written as tail calls keep a bounded stack
+    fn push_frame(&mut self, func: Ptr<Value>, call: Option<Ptr<Value>>) {
+        let frames = match &mut self.frames {
+            Some(frames) => frames,
+            None => return,
+        };
+        let frame = Frame { func, call };
+        let is_tail_call = matches!(
+            self.next_steps.last(),
+            Some(Step {
+                kind: StepKind::Return,
+                ..
+            })
+        );
+        if is_tail_call {
+            if let Some(top) = frames.last_mut() {
+                *top = frame;
+                return;
+            }
+        }
+
+        frames.push(frame);
+        self.next_steps.push(Step::new(StepKind::Return, |interp| {
+            if let Some(frames) = &mut interp.frames {
+                frames.pop();
+            }
+        }));
     }
 
     fn clear_run_state(&mut self) {
@@ -84,92 +471,279 @@ impl Interpreter {
         }
     }
 
-    fn handle_func_call(&mut self, nodes: Vec<Ptr<Value>>, env: Ptr<Environment>) {
+    // handle_func_call evaluates the function and argument expressions in nodes, then applies
+    // the function to the arguments
+    // the expressions are guaranteed to be evaluated strictly left to right, function first:
+    // steps are popped from the end of next_steps, so they are pushed in reverse
+    fn handle_func_call(
+        &mut self,
+        node: Ptr<Value>,
+        nodes: Vec<Ptr<Value>>,
+        env: Ptr<Environment>,
+    ) {
+        self.saved_results.push(std::mem::take(&mut self.results));
+        self.push_apply_step(env, Some(node));
+
+        for node in nodes.into_iter().rev() {
+            self.next_steps
+                .push(Step::new(StepKind::Eval(node), move |interp| {
+                    interp.eval_node(node, env);
+                }))
+        }
+    }
+
+    // apply schedules a call of func with already evaluated args
+    fn apply(&mut self, func: Ptr<Value>, args: &[Ptr<Value>], env: Ptr<Environment>) {
+        let mut vals = Vec::with_capacity(args.len() + 1);
+        vals.push(func);
+        vals.extend_from_slice(args);
         self.saved_results
-            .push(std::mem::replace(&mut self.results, Vec::new()));
-
-        self.next_steps.push(Box::new(move |interp| {
-            let mut vals =
-                std::mem::replace(&mut interp.results, interp.saved_results.pop().unwrap())
-                    .into_iter();
-            let func = vals.next().unwrap();
-            let func_val = interp.alloc.get_val(func);
-            match func_val {
-                Value::Function(Function { args, env, body }) => {
-                    if args.len() != vals.len() {
-                        interp.error = Some(Error {
-                            message: format!(
-                                "{}: expected {}, received {}",
-                                WRONG_NUMBER_ARGS_ERROR,
-                                args.len(),
-                                vals.len()
-                            ),
-                        });
-                        return;
-                    }
+            .push(std::mem::replace(&mut self.results, vals));
+        self.push_apply_step(env, None);
+    }
+
+    // call synchronously applies func to args in env and returns the result
+    // it allows natives, including plugin natives, to call back into Scheme: the pending
+    // evaluation is set aside while a nested step loop runs the call, then restored unchanged,
+    // so a native may make any number of calls, and the procedures it calls may in turn call
+    // natives which call back again
+    // continuations can only be resumed at the nesting depth they were captured at,
+    // so escaping out of (or back into) the call is reported as an error
+    // on error the native should pass the message on with raise, which fails its own call
+    // pointers are not roots: the arguments and results a native holds stay valid across calls
+    // unless the called code collects garbage, e.g. with gc-run, so a native which keeps
+    // values the program can't reach should hold them through handles
+    pub fn call(
+        &mut self,
+        func: Ptr<Value>,
+        args: &[Ptr<Value>],
+        env: Ptr<Environment>,
+    ) -> Result<Ptr<Value>, Error> {
+        let next_steps = std::mem::take(&mut self.next_steps);
+        let results = std::mem::take(&mut self.results);
+        let saved_results = std::mem::take(&mut self.saved_results);
+
+        self.call_depth += 1;
+        self.apply(func, args, env);
+        let res = self.run();
+        self.call_depth -= 1;
+
+        self.next_steps = next_steps;
+        self.results = results;
+        self.saved_results = saved_results;
+        res
+    }
+
+    // call_native runs a native function, turning a panic into a runtime error so that
+    // buggy extensions don't take down the REPL or host
+    // a panicking native may have pushed steps or results before failing, so those are dropped
+    // this relies on panics unwinding: with panic=abort the process still exits
+    fn call_native(&mut self, func: NativeFn, env: Ptr<Environment>, args: &[Ptr<Value>]) {
+        let steps = self.next_steps.len();
+        let results = self.results.len();
+        let saved_results = self.saved_results.len();
 
-                    let body = *body;
-                    let new_bindings = args.iter().map(String::clone).zip(vals).collect();
-                    let bound_env = Environment::new_child_with_bindings(*env, new_bindings);
-                    let bound_env_ptr = interp.alloc.new_env(bound_env);
+        let res = panic::catch_unwind(panic::AssertUnwindSafe(|| func(self, env, args)));
+        if let Err(payload) = res {
+            let message = match payload.downcast_ref::<&str>() {
+                Some(message) => message.to_string(),
+                None => match payload.downcast_ref::<String>() {
+                    Some(message) => message.clone(),
+                    None => "unknown panic".to_string(),
+                },
+            };
 
-                    interp.eval_node(body, bound_env_ptr)
+            self.next_steps.truncate(steps);
+            self.results.truncate(results);
+            self.saved_results.truncate(saved_results);
+            self.error = Some(Error {
+                message: format!("{}: {}", NATIVE_PANIC_ERROR, message),
+                location: None,
+            });
+        }
+    }
+
+    // push_apply_step schedules the application of the values on the results stack,
+    // the first being the function, restoring the previous results stack
+    // call is the expression the call was made from, if any
+    fn push_apply_step(&mut self, env: Ptr<Environment>, call: Option<Ptr<Value>>) {
+        self.next_steps
+            .push(Step::new(StepKind::Apply, move |interp| {
+                if let Some(location) = call.and_then(|call| interp.alloc.location(call)) {
+                    interp.location = Some(location);
                 }
-                Value::NativeFunction(f) => {
-                    // test
-                    f(interp, env, vals.as_slice())
+                let mut vals =
+                    std::mem::replace(&mut interp.results, interp.saved_results.pop().unwrap())
+                        .into_iter();
+                let func = vals.next().unwrap();
+                if !matches!(interp.alloc.get_val(func), Value::Continuation(_)) {
+                    interp.push_frame(func, call);
                 }
-                Value::Continuation(c) => {
-                    if vals.len() != 1 {
-                        interp.error = Some(Error {
-                            message: "continuation must be called with 1 argument".to_string(),
-                        });
-                        return;
+                let func_val = interp.alloc.get_val(func);
+                match func_val {
+                    Value::Function(Function {
+                        args, env, body, ..
+                    }) => {
+                        if args.len() != vals.len() {
+                            interp.error = Some(Error {
+                                message: format!(
+                                    "{} to {}: expected {}, received {}",
+                                    WRONG_NUMBER_ARGS_ERROR,
+                                    func_val.to_string(&interp.alloc),
+                                    args.len(),
+                                    vals.len()
+                                ),
+                                location: None,
+                            });
+                            return;
+                        }
+
+                        let body = *body;
+                        let new_bindings = args.iter().map(String::clone).zip(vals).collect();
+                        let bound_env = Environment::new_child_with_bindings(*env, new_bindings);
+                        let bound_env_ptr = interp.alloc.new_env(bound_env);
+
+                        interp.eval_body(body, bound_env_ptr)
                     }
+                    Value::NativeFunction(NativeFunction { func, arity, .. }) => {
+                        if !arity.accepts(vals.len()) {
+                            interp.error = Some(Error {
+                                message: format!(
+                                    "{} to {}: expected {}, received {}",
+                                    WRONG_NUMBER_ARGS_ERROR,
+                                    func_val.to_string(&interp.alloc),
+                                    arity,
+                                    vals.len()
+                                ),
+                                location: None,
+                            });
+                            return;
+                        }
 
-                    interp.next_steps = clone_steps(&c.next_steps);
-                    interp.results = c.results.clone();
-                    interp.saved_results = c.saved_results.clone();
-                    interp.results.push(vals.next().unwrap());
-                }
-                _ => {
-                    interp.error = Some(Error {
-                        message: format!(
-                            "attempt to call a non-function value: {}",
-                            func_val.to_string(&interp.alloc)
-                        ),
-                    });
-                }
-            };
-        }));
+                        let func = *func;
+                        interp.call_native(func, env, vals.as_slice())
+                    }
+                    Value::Continuation(c) => {
+                        if vals.len() != 1 {
+                            interp.error = Some(Error {
+                                message: "continuation must be called with 1 argument".to_string(),
+                                location: None,
+                            });
+                            return;
+                        }
 
-        for node in nodes.into_iter().rev() {
-            self.next_steps.push(Box::new(move |interp| {
-                interp.eval_node(node, env);
-            }))
-        }
+                        if c.depth != interp.call_depth {
+                            interp.error = Some(Error {
+                                message: CONTINUATION_DEPTH_ERROR.to_string(),
+                                location: None,
+                            });
+                            return;
+                        }
+
+                        if !c.one_shot {
+                            interp.next_steps = clone_steps(&c.next_steps);
+                            interp.results = c.results.clone();
+                            interp.saved_results = c.saved_results.clone();
+                            if let Some(frames) = &mut interp.frames {
+                                frames.clone_from(&c.frames);
+                            }
+                        } else if c.invoked {
+                            interp.error = Some(Error {
+                                message: CONTINUATION_REINVOKED_ERROR.to_string(),
+                                location: None,
+                            });
+                            return;
+                        } else if let Value::Continuation(c) = interp.alloc.get_val_mut(func) {
+                            // hand the captured state over rather than copying it
+                            c.invoked = true;
+                            interp.next_steps = std::mem::take(&mut c.next_steps);
+                            interp.results = std::mem::take(&mut c.results);
+                            interp.saved_results = std::mem::take(&mut c.saved_results);
+                            let frames = std::mem::take(&mut c.frames);
+                            if interp.frames.is_some() {
+                                interp.frames = Some(frames);
+                            }
+                        }
+                        interp.results.push(vals.next().unwrap());
+                    }
+                    _ => {
+                        interp.error = Some(Error {
+                            message: format!(
+                                "attempt to call a non-function value: {}",
+                                func_val.to_string(&interp.alloc)
+                            ),
+                            location: None,
+                        });
+                    }
+                };
+            }));
     }
 
     fn eval_node(&mut self, node: Ptr<Value>, env: Ptr<Environment>) {
+        if let Some(location) = self.alloc.location(node) {
+            self.location = Some(location);
+        }
+        if let Some(&builtin) = self.builtin_cache.get(&node) {
+            self.results.push(builtin);
+            return;
+        }
         match self.alloc.get_val(node) {
             Value::Integer(_) => self.results.push(node),
+            Value::Float(_) => self.results.push(node),
+            Value::Rational(_, _) => self.results.push(node),
             Value::Bool(_) => self.results.push(node),
             Value::NativeFunction(_) => self.results.push(node),
             Value::Function(_) => self.results.push(node),
             Value::Continuation(_) => self.results.push(node),
+            Value::Set(_) => self.results.push(node),
+            Value::Comparator(_) => self.results.push(node),
+            Value::HashTable(_) => self.results.push(node),
+            Value::Vector(_) => self.results.push(node),
+            Value::Bitvector(_) => self.results.push(node),
+            Value::Queue(_) => self.results.push(node),
+            Value::StringBuilder(_) => self.results.push(node),
+            Value::Guardian(_) => self.results.push(node),
+            Value::Heap(_) => self.results.push(node),
+            Value::Array(_) => self.results.push(node),
+            Value::S32Vector(_) => self.results.push(node),
+            Value::F64Vector(_) => self.results.push(node),
+            Value::Date(_) => self.results.push(node),
+            Value::Bytevector(_) => self.results.push(node),
+            Value::String(_) => self.results.push(node),
+            Value::Char(_) => self.results.push(node),
+            Value::Keyword(_) => self.results.push(node),
 
             Value::Symbol(s) => match self.alloc.get_bound_ptr(env, s) {
                 None => {
+                    let message = match self.options.language.restriction(s) {
+                        Some(reason) => format!("{} is not available: {}", s, reason),
+                        None if self.alloc.is_uninitialized(env, s) => {
+                            format!("{}: {}", UNINITIALIZED_BINDING_ERROR, s)
+                        }
+                        None => format!("{}: {}", UNBOUND_SYMBOL_ERROR, s),
+                    };
                     self.error = Some(Error {
-                        message: format!("{}: {}", UNBOUND_SYMBOL_ERROR, s),
+                        message,
+                        location: None,
                     })
                 }
-                Some(p) => self.results.push(p),
+                Some(p) => {
+                    // scopes are lexical, so a reference not shadowed by a local binding
+                    // the first time it is evaluated never is
+                    if self.options.freeze_stdlib
+                        && self.builtins.contains(s)
+                        && !self.alloc.binds_locally(env, s)
+                    {
+                        self.builtin_cache.insert(node, p);
+                    }
+                    self.results.push(p)
+                }
             },
 
             Value::Nil => {
                 self.error = Some(Error {
                     message: EVAL_EMPTY_LIST_ERROR.to_string(),
+                    location: None,
                 })
             }
 
@@ -179,6 +753,7 @@ impl Interpreter {
                     None => {
                         self.error = Some(Error {
                             message: EVAL_BAD_LIST_ERROR.to_string(),
+                            location: None,
                         });
                         return;
                     }
@@ -187,17 +762,23 @@ impl Interpreter {
                 if nodes.is_empty() {
                     self.error = Some(Error {
                         message: EVAL_EMPTY_LIST_ERROR.to_string(),
+                        location: None,
                     });
                     return;
                 }
 
-                // handle special forms
-                if let Value::Symbol(first_sym) = self.alloc.get_val(nodes[0]) {
+                // handle special forms, unless the keyword is shadowed by a local binding
+                let keyword = match self.alloc.get_val(nodes[0]) {
+                    Value::Symbol(s) if !self.alloc.binds_locally(env, s) => Some(s),
+                    _ => None,
+                };
+                if let Some(first_sym) = keyword {
                     match first_sym.as_str() {
                         "if" => {
-                            if nodes.len() != 4 {
+                            if let Err(message) = syntax::IF.validate(&self.alloc, &nodes[1..]) {
                                 self.error = Some(Error {
-                                    message: INVALID_IF_ERROR.to_string(),
+                                    message,
+                                    location: None,
                                 });
                                 return;
                             }
@@ -205,61 +786,69 @@ impl Interpreter {
                             let else_clause = nodes[3];
                             let then_clause = nodes[2];
 
-                            self.next_steps.push(Box::new(move |interp| {
-                                let res = interp.results.pop().unwrap();
-
-                                if let Value::Bool(false) = interp.alloc.get_val(res) {
-                                    interp.eval_node(else_clause, env)
-                                } else {
-                                    interp.eval_node(then_clause, env)
+                            // a trivial condition is checked directly, without a branch step
+                            if let Some(res) = self.simple_value(nodes[1], env) {
+                                match self.alloc.get_val(res) {
+                                    Value::Bool(false) => self.eval_node(else_clause, env),
+                                    _ => self.eval_node(then_clause, env),
                                 }
-                            }));
+                                return;
+                            }
+
+                            self.next_steps
+                                .push(Step::new(StepKind::Branch, move |interp| {
+                                    let res = interp.results.pop().unwrap();
+
+                                    if let Value::Bool(false) = interp.alloc.get_val(res) {
+                                        interp.eval_node(else_clause, env)
+                                    } else {
+                                        interp.eval_node(then_clause, env)
+                                    }
+                                }));
                             self.eval_node(nodes[1], env);
                             return;
                         }
                         "lambda" => {
-                            if nodes.len() != 3 {
+                            if let Err(message) = syntax::LAMBDA.validate(&self.alloc, &nodes[1..])
+                            {
                                 self.error = Some(Error {
-                                    message: INVALID_LAMBDA_ERROR.to_string(),
+                                    message,
+                                    location: None,
                                 });
                                 return;
                             }
 
-                            let mut args_names: Vec<String>;
-                            if let Value::Cons(al_hd, al_tl) = self.alloc.get_val(nodes[1]) {
-                                let arg_list = match self.cons_list_to_vector(*al_hd, *al_tl) {
-                                    Some(al) => al,
-                                    None => {
-                                        self.error = Some(Error {
-                                            message: INVALID_LAMBDA_ERROR.to_string(),
-                                        });
-                                        return;
-                                    }
-                                };
+                            // the body is the list of forms after the parameter list
+                            let body_forms = match self.alloc.get_val(node) {
+                                Value::Cons(_, tl) => match self.alloc.get_val(*tl) {
+                                    Value::Cons(_, body_forms) => *body_forms,
+                                    _ => unreachable!(),
+                                },
+                                _ => unreachable!(),
+                            };
 
-                                args_names = Vec::with_capacity(arg_list.len());
-                                for arg in arg_list {
-                                    if let Value::Symbol(arg) = self.alloc.get_val(arg) {
-                                        args_names.push(arg.clone());
-                                    } else {
-                                        self.error = Some(Error {
-                                            message: INVALID_LAMBDA_ERROR.to_string(),
-                                        });
-                                        return;
+                            let mut args_names = Vec::new();
+                            let mut arg_list = nodes[1];
+                            while let Value::Cons(hd, tl) = self.alloc.get_val(arg_list) {
+                                let (arg_node, tl) = (*hd, *tl);
+                                if let Value::Symbol(arg) = self.alloc.get_val(arg_node) {
+                                    let arg = arg.clone();
+                                    if self.options.warn_shadowing {
+                                        let warning =
+                                            self.shadowing_warning("parameter", &arg, env);
+                                        self.warn_shadowing(arg_node, warning);
                                     }
+                                    args_names.push(arg);
                                 }
-                            } else {
-                                self.error = Some(Error {
-                                    message: INVALID_LAMBDA_ERROR.to_string(),
-                                });
-                                return;
+                                arg_list = tl;
                             }
 
                             self.results.push(
                                 Value::Function(Function {
+                                    name: None,
                                     args: args_names,
                                     env,
-                                    body: nodes[2],
+                                    body: body_forms,
                                 })
                                 .gc(&mut self.alloc),
                             );
@@ -267,9 +856,10 @@ impl Interpreter {
                             return;
                         }
                         "quote" => {
-                            if nodes.len() != 2 {
+                            if let Err(message) = syntax::QUOTE.validate(&self.alloc, &nodes[1..]) {
                                 self.error = Some(Error {
-                                    message: INVALID_QUOTE_ERROR.to_string(),
+                                    message,
+                                    location: None,
                                 });
                                 return;
                             }
@@ -277,62 +867,128 @@ impl Interpreter {
                             self.results.push(nodes[1]);
                             return;
                         }
-                        "define" => {
-                            if nodes.len() != 3 {
+                        "quasiquote" => {
+                            if let Err(message) =
+                                syntax::QUASIQUOTE.validate(&self.alloc, &nodes[1..])
+                            {
                                 self.error = Some(Error {
-                                    message: INVALID_DEFINE_ERROR.to_string(),
+                                    message,
+                                    location: None,
                                 });
                                 return;
                             }
 
-                            let mut iter = nodes.into_iter();
-                            iter.next(); // drop define
-
-                            let name = match self.alloc.get_val(iter.next().unwrap()) {
-                                Value::Symbol(s) => s.clone(),
-                                _ => {
-                                    self.error = Some(Error {
-                                        message: INVALID_DEFINE_ERROR.to_string(),
-                                    });
-                                    return;
-                                }
-                            };
-
-                            self.next_steps.push(Box::new(move |interp| {
-                                assert_eq!(interp.results.len(), 1);
-                                interp.alloc.set_bound_value(
-                                    env,
-                                    name,
-                                    interp.results.pop().unwrap(),
-                                );
-                                interp.results.push(Value::Nil.gc(&mut interp.alloc));
-                            }));
-                            self.eval_node(iter.next().unwrap(), env);
+                            self.eval_quasiquote(nodes[1], env, 1);
+                            return;
+                        }
+                        "cond-expand" | "include" => {
+                            self.eval_expansion(node, env, false);
+                            return;
+                        }
+                        "define" | "define-constant" | "define-enumeration" => {
+                            self.error = Some(Error {
+                                message: format!("{}: {}", first_sym, DEFINE_CONTEXT_ERROR),
+                                location: None,
+                            });
                             return;
                         }
                         _ => {}
                     }
                 }
 
-                self.handle_func_call(nodes, env)
+                self.handle_func_call(node, nodes, env)
             }
         }
     }
 
-    fn eval_ast(&mut self, node: AST, env: Ptr<Environment>) {
+    // uses_keyword checks whether node is a list starting with keyword, not shadowed in env
+    fn uses_keyword(&self, node: Ptr<Value>, env: Ptr<Environment>, keyword: &str) -> bool {
+        match self.alloc.get_val(node) {
+            Value::Cons(hd, _) => matches!(
+                self.alloc.get_val(*hd),
+                Value::Symbol(s) if s == keyword && !self.alloc.binds_locally(env, s)
+            ),
+            _ => false,
+        }
+    }
+
+    // simple_value returns the value of node if it can be found without evaluation steps:
+    // if it is a self-evaluating atom or a bound symbol
+    fn simple_value(&self, node: Ptr<Value>, env: Ptr<Environment>) -> Option<Ptr<Value>> {
+        match self.alloc.get_val(node) {
+            Value::Cons(_, _) | Value::Nil => None,
+            Value::Symbol(s) => self.alloc.get_bound_ptr(env, s),
+            _ => Some(node),
+        }
+    }
+
+    fn eval_ast(&mut self, node: Node, env: Ptr<Environment>) {
+        // an evaluation that outgrew the pinned heap leaves its garbage behind,
+        // which is collected so that later evaluations can run
+        if self.alloc.exceeds_pin() {
+            self.collect_garbage(env, GcCause::PinnedHeap);
+        }
+        self.eval_start_allocations = self.alloc.values_allocated();
+        self.location = None;
+        if let Some(frames) = &mut self.frames {
+            frames.clear();
+        }
         let node_as_val = Value::from_ast(node, &mut self.alloc);
-        self.eval_node(node_as_val, env);
+        self.eval_form(node_as_val, env);
+    }
+
+    // collect_garbage frees the values unreachable from env
+    fn collect_garbage(&mut self, env: Ptr<Environment>, cause: GcCause) {
+        // the active calls are formatted if the evaluation fails, so they must survive
+        let frames = self.frames.clone().unwrap_or_default();
+        let _roots = frames
+            .iter()
+            .flat_map(|frame| std::iter::once(frame.func).chain(frame.call))
+            .map(|val| self.alloc.root(val))
+            .collect::<Vec<_>>();
+        self.alloc.gc(env, cause);
+        // collected nodes may be reused, so they must not keep their cached builtins
+        self.builtin_cache.clear();
+    }
+
+    // check_allocation_limit fails the evaluation if it has allocated more values than allowed
+    fn check_allocation_limit(&mut self) {
+        let limit = match self.options.max_allocations_per_eval {
+            Some(limit) => limit,
+            None => return,
+        };
+        if self.error.is_none()
+            && self.alloc.values_allocated() - self.eval_start_allocations > limit
+        {
+            self.error = Some(Error {
+                message: format!(
+                    "{}: evaluation allocated more than {} values",
+                    ALLOCATION_LIMIT_ERROR, limit
+                ),
+                location: None,
+            });
+        }
     }
 
     fn run(&mut self) -> Result<Ptr<Value>, Error> {
         if self.error.is_some() {
-            return Err(std::mem::replace(&mut self.error, None).unwrap());
+            let mut err = self.error.take().unwrap();
+            err.location = err.location.or(self.location);
+            return Err(err);
         }
 
         while let Some(step) = self.next_steps.pop() {
-            step(self);
+            (step.func)(self);
+            self.check_allocation_limit();
+            if self.error.is_none() && self.alloc.exceeds_pin() {
+                self.error = Some(Error {
+                    message: PINNED_HEAP_ERROR.to_string(),
+                    location: None,
+                });
+            }
             if self.error.is_some() {
-                let err = std::mem::replace(&mut self.error, None).unwrap();
+                let mut err = self.error.take().unwrap();
+                err.location = err.location.or(self.location);
                 self.clear_run_state();
                 return Err(err);
             }
@@ -343,136 +999,366 @@ impl Interpreter {
     }
 }
 
-// #[cfg(test)]
-// mod test {
-//     use super::*;
-//     use std::cell::Cell;
-//
-//     #[test]
-//     fn runs_simple_example() {
-//         let node = AST::List(vec![
-//             AST::Symbol("+".to_string()),
-//             AST::Integer(1),
-//             AST::Integer(2),
-//         ]);
-//
-//         let env = stdlib::build();
-//         let cont_called = Rc::new(Cell::new(false));
-//         let ccb = cont_called.clone();
-//
-//         eval_node(
-//             Value::from(node).rc(),
-//             env,
-//             Box::new(move |res| {
-//                 let res = res.unwrap();
-//                 match res.borrow() {
-//                     Value::Integer(3) => {}
-//                     _ => panic!("expected 3"),
-//                 };
-//                 ccb.set(true);
-//             }),
-//         );
-//
-//         assert!(cont_called.get());
-//     }
-//
-//     #[test]
-//     fn handles_if() {
-//         // (if #t (if #f 1 2) 3) -> 2
-//         let node = AST::List(vec![
-//             AST::Symbol("if".to_string()),
-//             AST::Bool(true),
-//             AST::List(vec![
-//                 AST::Symbol("if".to_string()),
-//                 AST::Bool(false),
-//                 AST::Integer(1),
-//                 AST::Integer(2),
-//             ]),
-//             AST::Integer(3),
-//         ]);
-//
-//         let env = stdlib::build();
-//         let cont_called = Rc::new(Cell::new(false));
-//         let ccb = cont_called.clone();
-//
-//         eval_node(
-//             Value::from(node).rc(),
-//             env,
-//             Box::new(move |res| {
-//                 let res = res.unwrap();
-//                 match res.borrow() {
-//                     Value::Integer(2) => {}
-//                     _ => panic!("expected 2"),
-//                 };
-//                 ccb.set(true);
-//             }),
-//         );
-//
-//         assert!(cont_called.get());
-//     }
-//
-//     #[test]
-//     fn handles_lambda() {
-//         // ((lambda (x) (+ x 1)) 2) -> 3
-//         let node = AST::List(vec![
-//             AST::List(vec![
-//                 AST::Symbol("lambda".to_string()),
-//                 AST::List(vec![AST::Symbol("x".to_string())]),
-//                 AST::List(vec![
-//                     AST::Symbol("+".to_string()),
-//                     AST::Symbol("x".to_string()),
-//                     AST::Integer(1),
-//                 ]),
-//             ]),
-//             AST::Integer(2),
-//         ]);
-//
-//         let env = stdlib::build();
-//         let cont_called = Rc::new(Cell::new(false));
-//         let ccb = cont_called.clone();
-//         eval_node(
-//             Value::from(node).rc(),
-//             env,
-//             Box::new(move |res| {
-//                 let res = res.unwrap();
-//                 match res.borrow() {
-//                     Value::Integer(3) => {}
-//                     _ => panic!("expected 2"),
-//                 };
-//                 ccb.set(true);
-//             }),
-//         );
-//         assert!(cont_called.get())
-//     }
-//
-//     #[test]
-//     fn runs_program() {
-//         // (define x 1) x
-//         let program = vec![
-//             AST::List(vec![
-//                 AST::Symbol("define".to_string()),
-//                 AST::Symbol("x".to_string()),
-//                 AST::Integer(1),
-//             ]),
-//             AST::Symbol("x".to_string()),
-//         ];
-//
-//         let cont_called = Rc::new(Cell::new(false));
-//         let ccb = cont_called.clone();
-//
-//         eval_nodes_toplevel(
-//             program,
-//             stdlib::build(),
-//             Box::new(move |res| {
-//                 let res = res.unwrap();
-//                 if let Value::Integer(i) = res.as_ref() {
-//                     assert_eq!(*i, 1);
-//                 } else {
-//                     panic!("expected integer result");
-//                 }
-//                 ccb.set(true);
-//             }),
-//         );
-//         assert!(cont_called.get());
-//     }
-// }
+// read_source tokenizes and parses source, returning the displayed syntax error on failure
+fn read_source(source: &str) -> Result<Vec<Node>, String> {
+    let tokens = crate::lex::tokenize(source).map_err(|err| err.to_string())?;
+    crate::parse::parse(&tokens).map_err(|err| err.to_string())
+}
+
+// is_incomplete checks whether source ends inside an unfinished form or token
+// errors are left for read_source to report
+fn is_incomplete(source: &str) -> bool {
+    match crate::lex::tokenize(source) {
+        Ok(tokens) => matches!(
+            crate::parse::parse_partial(&tokens),
+            Ok(crate::parse::Partial::Incomplete(_))
+        ),
+        Err(err) => err.is_incomplete(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // run evaluates every form in source in a fresh interpreter
+    // returning the printed value of the last form
+    pub(super) fn run(source: &str) -> Result<String, String> {
+        evaluator()(source)
+    }
+
+    // evaluator returns a function like run which keeps one interpreter across calls
+    pub(super) fn evaluator() -> impl FnMut(&str) -> Result<String, String> {
+        let mut interp = Interpreter::new(Options::default());
+        let env = interp.new_global_env();
+
+        move |source| {
+            let mut res = Err("no forms to evaluate".to_string());
+            for node in read_source(source)? {
+                interp.eval_ast(node, env);
+                let val = interp.run().map_err(|err| err.message)?;
+                res = Ok(interp.alloc.get_val(val).to_string(&interp.alloc));
+            }
+            res
+        }
+    }
+
+    #[test]
+    fn runs_simple_example() {
+        assert_eq!(run("(+ 1 2)"), Ok("3".to_string()));
+    }
+
+    #[test]
+    fn evaluates_bytevector_literals() {
+        assert_eq!(run("#u8(0 255 16)"), Ok("#u8(0 255 16)".to_string()));
+        assert_eq!(run("(equal? #u8(1 2) #u8(1 2))"), Ok("#t".to_string()));
+        assert_eq!(run("(equal? #u8(1 2) #u8(1))"), Ok("#f".to_string()));
+    }
+
+    #[test]
+    fn reads_and_prints_datum_labels() {
+        assert_eq!(run("'#0=(a #0#)"), Ok("#0=(a #0#)".to_string()));
+        assert_eq!(
+            run("(define l '(#0=(x) #0#)) (eq? (car l) (car (cdr l)))"),
+            Ok("#t".to_string())
+        );
+        // shared structure without a cycle is printed in full
+        assert_eq!(run("'(#0=(x) #0#)"), Ok("((x) (x))".to_string()));
+        assert_eq!(
+            run("(define v (vector 1 2)) (vector-set! v 1 v) v"),
+            Ok("#0=#(1 #0#)".to_string())
+        );
+    }
+
+    #[test]
+    fn prints_lists() {
+        assert_eq!(run("'(1 (2 3) ())"), Ok("(1 (2 3) ())".to_string()));
+        assert_eq!(run("(cons 1 '())"), Ok("(1)".to_string()));
+        // only an improper tail is dotted
+        assert_eq!(run("(cons 1 (cons 2 3))"), Ok("(1 2 . 3)".to_string()));
+        assert_eq!(
+            run("'(1 (2 . 3) . #(4))"),
+            Ok("(1 (2 . 3) . #(4))".to_string())
+        );
+        assert_eq!(run("'#0=(a b . #0#)"), Ok("#0=(a b . #0#)".to_string()));
+        assert_eq!(
+            run("'(a . #0=(b . #0#))"),
+            Ok("(a . #0=(b . #0#))".to_string())
+        );
+        // cycles are found without recursing along the list
+        let long = run(
+            "(define (build n acc) (if (= n 0) acc (build (- n 1) (cons n acc)))) \
+             (build 200000 '())",
+        );
+        assert!(long.unwrap().ends_with(" 199999 200000)"));
+    }
+
+    #[test]
+    fn locates_runtime_errors() {
+        let mut interp = Interpreter::new(Options::default());
+        let env = interp.new_global_env();
+        let mut locate = |source| {
+            let mut res = None;
+            for node in read_source(source).unwrap() {
+                interp.eval_ast(node, env);
+                if let Err(err) = interp.run() {
+                    res = err
+                        .location
+                        .map(|location| (location.line, location.column));
+                }
+            }
+            res
+        };
+
+        assert_eq!(locate("(+ 1\n  x)"), Some((1, 2)));
+        assert_eq!(locate("y"), Some((0, 0)));
+        // failures inside a function are located at the call that failed
+        assert_eq!(locate("(define (f) (car 1))\n(f)"), Some((0, 12)));
+    }
+
+    #[test]
+    fn handles_if() {
+        assert_eq!(run("(if #t (if #f 1 2) 3)"), Ok("2".to_string()));
+    }
+
+    #[test]
+    fn handles_lambda() {
+        assert_eq!(run("((lambda (x) (+ x 1)) 2)"), Ok("3".to_string()));
+    }
+
+    #[test]
+    fn runs_program() {
+        assert_eq!(run("(define x 1) x"), Ok("1".to_string()));
+    }
+
+    #[test]
+    fn evaluates_arguments_left_to_right() {
+        assert_eq!(
+            run("(define v (vector 0)) ((lambda (a b c) (vector-ref v 0)) (vector-set! v 0 1) (vector-set! v 0 2) (vector-set! v 0 3))"),
+            Ok("3".to_string())
+        );
+        assert_eq!(
+            run("(define v (vector 0)) (cons (vector-set! v 0 1) (vector-set! v 0 2)) (vector-ref v 0)"),
+            Ok("2".to_string())
+        );
+    }
+
+    #[test]
+    fn evaluates_function_before_arguments() {
+        assert_eq!(
+            run("(define v (vector 0)) ((last (vector-set! v 0 1) (lambda (a) (vector-ref v 0))) (vector-set! v 0 2))"),
+            Ok("2".to_string())
+        );
+    }
+
+    #[test]
+    fn reinvokes_continuations() {
+        let source = "
+            (define k (call/cc (lambda (c) c)))
+            (define j k)
+            (j 1)
+            (j 2)
+            k";
+        assert_eq!(run(source), Ok("2".to_string()));
+    }
+
+    #[test]
+    fn invalidates_one_shot_continuations() {
+        let mut interp = Interpreter::new(Options {
+            one_shot_continuations: true,
+            ..Options::default()
+        });
+        let env = interp.new_global_env();
+        let source = "
+            (define k (call/cc (lambda (c) c)))
+            (define j k)
+            (continuation-one-shot? j)
+            (j 1)
+            (j 2)";
+
+        let results = read_source(source)
+            .unwrap()
+            .into_iter()
+            .map(|node| {
+                interp.eval_ast(node, env);
+                interp
+                    .run()
+                    .map(|val| interp.alloc.get_val(val).to_string(&interp.alloc))
+                    .map_err(|err| err.message)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(results[2], Ok("#t".to_string()));
+        assert_eq!(results[3], Ok("()".to_string()));
+        assert_eq!(results[4], Err(CONTINUATION_REINVOKED_ERROR.to_string()));
+    }
+
+    #[test]
+    fn rejects_redefining_constants() {
+        assert_eq!(
+            run("(define-constant x 1) (define x 2)"),
+            Err("cannot redefine constant binding: x".to_string())
+        );
+        assert_eq!(
+            run("(define-constant x 1) ((lambda (y) (define x y) x) 2) x"),
+            Ok("1".to_string())
+        );
+    }
+
+    #[test]
+    fn caches_frozen_builtins() {
+        let mut interp = Interpreter::new(Options {
+            freeze_stdlib: true,
+            ..Options::default()
+        });
+        let mut eval = |source: &str| {
+            interp
+                .eval(source)
+                .map(|res| interp.to_string(&res))
+                .map_err(|err| err.message)
+        };
+        assert_eq!(
+            eval("(define + -)"),
+            Err(format!("cannot redefine +: {}", FROZEN_STDLIB_ERROR))
+        );
+        assert!(eval("(define-enumeration color (red car) color?)")
+            .unwrap_err()
+            .starts_with("cannot redefine car"));
+        // local bindings may still shadow builtins, and are never cached
+        assert_eq!(
+            eval("(define (f car) (+ car 1)) (define (g x) (car x))"),
+            Ok("()".to_string())
+        );
+        assert_eq!(eval("(cons (f 1) (f 2))"), Ok("(2 . 3)".to_string()));
+        assert_eq!(eval("(g '(1 2)) (gc-run) (g '(3 4))"), Ok("3".to_string()));
+        assert!(!interp.builtin_cache.is_empty());
+    }
+
+    #[test]
+    fn describes_shadowed_bindings() {
+        let mut interp = Interpreter::new(Options::default());
+        let env = interp.new_global_env();
+        let local = interp.alloc.new_env(Environment::new_child_with_bindings(
+            env,
+            Default::default(),
+        ));
+
+        assert_eq!(
+            interp.definition_warning("+", env),
+            Some("definition of '+' replaces a builtin".to_string())
+        );
+        assert_eq!(interp.definition_warning("x", env), None);
+        assert_eq!(
+            interp.definition_warning("cons", local),
+            Some("definition of 'cons' shadows a builtin".to_string())
+        );
+        assert_eq!(interp.shadowing_warning("parameter", "y", local), None);
+    }
+
+    #[test]
+    fn warns_about_shadowing_once_with_locations() {
+        let mut interp = Interpreter::new(Options {
+            warn_shadowing: true,
+            ..Options::default()
+        });
+        interp.capture_output(true);
+        let source = "(define (count-down list n)
+  (define car n)
+  (if (= n 0) n (count-down list (- n 1))))
+(count-down 1 5)";
+        interp.eval(source).unwrap();
+        let warnings = interp
+            .take_output()
+            .into_iter()
+            .map(|output| match output {
+                Output::Warning(text) => text,
+                Output::Line(text) => panic!("unexpected output {}", text),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            warnings,
+            [
+                "Warning at line 0, col 20: parameter 'list' shadows a builtin",
+                "Warning at line 1, col 10: definition of 'car' shadows a builtin",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_unbound_symbol() {
+        assert!(run("(+ x 1)").is_err());
+    }
+
+    #[test]
+    fn evaluates_trivial_forms_without_steps() {
+        let mut interp = Interpreter::new(Options::default());
+        let env = interp.new_global_env();
+        for node in read_source("(define x 1) (if x 'yes 'no) (if #f 1 x)").unwrap() {
+            interp.eval_ast(node, env);
+            assert!(interp.next_steps.is_empty());
+            assert!(interp.run().is_ok());
+        }
+        assert_eq!(run("(define x #f) (if x 'yes 'no)"), Ok("no".to_string()));
+        assert!(run("(if y 1 2)").is_err());
+    }
+
+    #[test]
+    fn limits_allocations_per_eval() {
+        let mut interp = Interpreter::new(Options {
+            max_allocations_per_eval: Some(1000),
+            ..Options::default()
+        });
+        interp
+            .eval("(define build (lambda (n acc) (if (= n 0) acc (build (- n 1) (cons n acc)))))")
+            .unwrap();
+        assert!(interp.eval("(build 10 '())").is_ok());
+
+        let err = interp.eval("(build 100000 '())").err().unwrap();
+        assert_eq!(
+            err.message,
+            "allocation limit exceeded: evaluation allocated more than 1000 values"
+        );
+        // the count starts again for each top-level evaluation
+        assert!(interp.eval("(build 10 '())").is_ok());
+
+        // natives calling back into Scheme don't stop the error
+        let err = interp
+            .eval("(sort '(3 1 2) (lambda (a b) (build 100000 '())))")
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.message,
+            "allocation limit exceeded: evaluation allocated more than 1000 values"
+        );
+        assert!(interp.eval("(build 10 '())").is_ok());
+    }
+
+    #[test]
+    fn reserves_and_pins_heap() {
+        let mut interp = Interpreter::new(Options::default());
+        interp
+            .eval("(define build (lambda (n acc) (if (= n 0) acc (build (- n 1) (cons n acc)))))")
+            .unwrap();
+        let before = interp.heap_stats();
+        interp.reserve_heap(10000, 10000);
+        let stats = interp.heap_stats();
+        assert!(stats.values_heap_capacity >= before.values_heap_size + 10000);
+        assert!(stats.environments_heap_capacity >= before.environments_heap_size + 10000);
+
+        interp.pin_heap(true);
+        assert!(interp.eval("(build 100 '())").is_ok());
+        let stats = interp.heap_stats();
+        assert!(stats.values_heap_capacity >= before.values_heap_size + 10000);
+        assert!(stats.values_heap_size <= stats.values_heap_capacity);
+
+        let err = interp.eval("(build 1000000 '())").err().unwrap();
+        assert_eq!(err.message, PINNED_HEAP_ERROR);
+        // the heap stays pinned, and later evaluations which fit in it still run
+        assert!(interp.eval("(build 10 '())").is_ok());
+        assert!(interp.eval("(build 100 '())").is_ok());
+        let err = interp.eval("(build 1000000 '())").err().unwrap();
+        assert_eq!(err.message, PINNED_HEAP_ERROR);
+        assert!(interp.eval("(build 10 '())").is_ok());
+        interp.pin_heap(false);
+        assert!(interp.eval("(build 100000 '())").is_ok());
+    }
+}
@@ -0,0 +1,259 @@
+use crate::interpreter::allocator::{Allocator, Ptr};
+use crate::interpreter::value::Value;
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+
+// Number is a numeric value, used by natives to work across the numeric types
+// integers and rationals are exact: operations on them give exact results, falling back
+// to a float only if the result doesn't fit in 64 bits
+// an operation involving a float gives a float
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) enum Number {
+    Integer(i64),
+    // numerator and denominator in lowest terms, the denominator greater than 1
+    Rational(i64, i64),
+    Float(f64),
+}
+
+fn gcd(mut a: i128, mut b: i128) -> i128 {
+    while b != 0 {
+        let r = a % b;
+        a = b;
+        b = r;
+    }
+    a.abs()
+}
+
+impl Number {
+    // rational returns the exact number num/den, which must have a non-zero denominator
+    pub(super) fn rational(num: i128, den: i128) -> Self {
+        let divisor = gcd(num, den) * den.signum();
+        let (num, den) = (num / divisor, den / divisor);
+        match (i64::try_from(num), i64::try_from(den)) {
+            (Ok(num), Ok(1)) => Number::Integer(num),
+            (Ok(num), Ok(den)) => Number::Rational(num, den),
+            _ => Number::Float(num as f64 / den as f64),
+        }
+    }
+
+    // from_value returns the number at ptr, or None if it is not a number
+    pub(super) fn from_value(alloc: &Allocator, ptr: Ptr<Value>) -> Option<Self> {
+        match alloc.get_val(ptr) {
+            Value::Integer(i) => Some(Number::Integer(*i)),
+            Value::Rational(num, den) => Some(Number::Rational(*num, *den)),
+            Value::Float(f) => Some(Number::Float(*f)),
+            _ => None,
+        }
+    }
+
+    pub(super) fn into_value(self) -> Value {
+        match self {
+            Number::Integer(i) => Value::Integer(i),
+            Number::Rational(num, den) => Value::Rational(num, den),
+            Number::Float(f) => Value::Float(f),
+        }
+    }
+
+    pub(super) fn to_f64(self) -> f64 {
+        match self {
+            Number::Integer(i) => i as f64,
+            Number::Rational(num, den) => num as f64 / den as f64,
+            Number::Float(f) => f,
+        }
+    }
+
+    // exact_parts returns the numerator and denominator of an exact number
+    fn exact_parts(self) -> Option<(i128, i128)> {
+        match self {
+            Number::Integer(i) => Some((i.into(), 1)),
+            Number::Rational(num, den) => Some((num.into(), den.into())),
+            Number::Float(_) => None,
+        }
+    }
+
+    pub(super) fn add(self, other: Self) -> Self {
+        match (self, other) {
+            (Number::Integer(a), Number::Integer(b)) => a
+                .checked_add(b)
+                .map_or_else(|| Number::Float(a as f64 + b as f64), Number::Integer),
+            _ => match (self.exact_parts(), other.exact_parts()) {
+                (Some((a, b)), Some((c, d))) => Number::rational(a * d + c * b, b * d),
+                _ => Number::Float(self.to_f64() + other.to_f64()),
+            },
+        }
+    }
+
+    pub(super) fn sub(self, other: Self) -> Self {
+        match (self, other) {
+            (Number::Integer(a), Number::Integer(b)) => a
+                .checked_sub(b)
+                .map_or_else(|| Number::Float(a as f64 - b as f64), Number::Integer),
+            _ => match (self.exact_parts(), other.exact_parts()) {
+                (Some((a, b)), Some((c, d))) => Number::rational(a * d - c * b, b * d),
+                _ => Number::Float(self.to_f64() - other.to_f64()),
+            },
+        }
+    }
+
+    pub(super) fn mul(self, other: Self) -> Self {
+        match (self, other) {
+            (Number::Integer(a), Number::Integer(b)) => a
+                .checked_mul(b)
+                .map_or_else(|| Number::Float(a as f64 * b as f64), Number::Integer),
+            _ => match (self.exact_parts(), other.exact_parts()) {
+                (Some((a, b)), Some((c, d))) => Number::rational(a * c, b * d),
+                _ => Number::Float(self.to_f64() * other.to_f64()),
+            },
+        }
+    }
+
+    // div divides exactly when both numbers are exact, returning None on exact division by zero
+    pub(super) fn div(self, other: Self) -> Option<Self> {
+        match (self.exact_parts(), other.exact_parts()) {
+            (Some(_), Some((0, _))) => None,
+            (Some((a, b)), Some((c, d))) => Some(Number::rational(a * d, b * c)),
+            _ => Some(Number::Float(self.to_f64() / other.to_f64())),
+        }
+    }
+
+    // compare orders numbers by value, or returns None if either is NaN
+    pub(super) fn compare(self, other: Self) -> Option<Ordering> {
+        match (self.exact_parts(), other.exact_parts()) {
+            (Some((a, b)), Some((c, d))) => Some((a * d).cmp(&(c * b))),
+            _ => self.to_f64().partial_cmp(&other.to_f64()),
+        }
+    }
+}
+
+// format_float writes f so it reads back as a float, using R7RS notation for infinities and NaN
+// it uses the fewest digits that read back as exactly f
+pub(super) fn format_float(f: f64) -> String {
+    if f.is_nan() {
+        "+nan.0".to_string()
+    } else if f.is_infinite() {
+        (if f > 0.0 { "+inf.0" } else { "-inf.0" }).to_string()
+    } else {
+        // Debug formatting is shortest round-trip and always includes a point or exponent,
+        // unlike Display
+        format!("{:?}", f)
+    }
+}
+
+// Notation is a way of writing a float with a given number of digits after the point
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) enum Notation {
+    // 1234.50
+    Fixed,
+    // 1.23e3
+    Scientific,
+}
+
+impl Notation {
+    pub(super) fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "fixed" => Some(Notation::Fixed),
+            "scientific" => Some(Notation::Scientific),
+            _ => None,
+        }
+    }
+}
+
+// format_float_in writes f in notation, rounding to digits after the point
+// infinities and NaN are written as by format_float
+pub(super) fn format_float_in(f: f64, notation: Notation, digits: usize) -> String {
+    if !f.is_finite() {
+        return format_float(f);
+    }
+    match notation {
+        Notation::Fixed => format!("{:.*}", digits, f),
+        Notation::Scientific => format!("{:.*e}", digits, f),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn promotes_mixed_arithmetic() {
+        let (two, half) = (Number::Integer(2), Number::Float(0.5));
+        assert_eq!(two.add(Number::Integer(3)), Number::Integer(5));
+        assert_eq!(two.add(half), Number::Float(2.5));
+        assert_eq!(half.sub(two), Number::Float(-1.5));
+        assert_eq!(two.mul(half), Number::Float(1.0));
+        assert_eq!(two.compare(half), Some(Ordering::Greater));
+        assert_eq!(two.compare(Number::Float(f64::NAN)), None);
+
+        // integer results that don't fit in 64 bits become floats
+        let (max, min) = (Number::Integer(i64::MAX), Number::Integer(i64::MIN));
+        assert_eq!(
+            max.add(Number::Integer(1)),
+            Number::Float(i64::MAX as f64 + 1.0)
+        );
+        assert_eq!(
+            min.sub(Number::Integer(1)),
+            Number::Float(i64::MIN as f64 - 1.0)
+        );
+        assert_eq!(max.mul(two), Number::Float(i64::MAX as f64 * 2.0));
+        assert_eq!(
+            min.mul(Number::Integer(-1)),
+            Number::Float(-(i64::MIN as f64))
+        );
+        assert_eq!(max.add(Number::Integer(-1)), Number::Integer(i64::MAX - 1));
+        assert_eq!(min.sub(Number::Integer(-1)), Number::Integer(i64::MIN + 1));
+    }
+
+    #[test]
+    fn keeps_rationals_exact() {
+        let third = Number::rational(1, 3);
+        assert_eq!(third, Number::Rational(1, 3));
+        assert_eq!(Number::rational(4, -6), Number::Rational(-2, 3));
+        assert_eq!(third.add(third).add(third), Number::Integer(1));
+        assert_eq!(Number::Integer(1).sub(third), Number::Rational(2, 3));
+        assert_eq!(third.mul(Number::Integer(6)), Number::Integer(2));
+        assert_eq!(
+            third.add(Number::Float(0.5)),
+            Number::Float(1.0 / 3.0 + 0.5)
+        );
+        assert_eq!(
+            Number::Integer(3).div(Number::Integer(6)),
+            Some(Number::Rational(1, 2))
+        );
+        assert_eq!(third.div(Number::Integer(0)), None);
+        assert_eq!(
+            Number::Float(1.0).div(Number::Integer(0)),
+            Some(Number::Float(f64::INFINITY))
+        );
+        assert_eq!(third.compare(Number::Rational(1, 2)), Some(Ordering::Less));
+        assert_eq!(
+            Number::rational(i64::MAX as i128 * 2, 3),
+            Number::Float(i64::MAX as f64 * 2.0 / 3.0)
+        );
+    }
+
+    #[test]
+    fn formats_floats() {
+        assert_eq!(format_float(1.0), "1.0");
+        assert_eq!(format_float(-0.25), "-0.25");
+        assert_eq!(format_float(1e100), "1e100");
+        assert_eq!(format_float(f64::INFINITY), "+inf.0");
+        assert_eq!(format_float(f64::NAN), "+nan.0");
+        assert_eq!(format_float(0.1 + 0.2), "0.30000000000000004");
+        for f in [0.1, 1.0 / 3.0, 5e-324, f64::MAX, -123456.789e-20] {
+            assert_eq!(format_float(f).parse::<f64>(), Ok(f));
+        }
+    }
+
+    #[test]
+    fn formats_floats_in_notations() {
+        assert_eq!(format_float_in(1234.5, Notation::Fixed, 2), "1234.50");
+        assert_eq!(format_float_in(-0.125, Notation::Fixed, 1), "-0.1");
+        assert_eq!(format_float_in(2.0 / 3.0, Notation::Fixed, 0), "1");
+        assert_eq!(format_float_in(1234.5, Notation::Scientific, 2), "1.23e3");
+        assert_eq!(format_float_in(0.00018, Notation::Scientific, 0), "2e-4");
+        assert_eq!(
+            format_float_in(f64::NEG_INFINITY, Notation::Fixed, 2),
+            "-inf.0"
+        );
+    }
+}
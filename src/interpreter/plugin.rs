@@ -0,0 +1,115 @@
+use crate::interpreter::allocator::{Allocator, Ptr};
+use crate::interpreter::value::{Arity, NativeFn, NativeFunction, Value};
+use std::collections::HashMap;
+
+// Plugin is implemented by packs of builtins which hosts add with Interpreter::add_plugin,
+// so third parties can ship bindings without changes to the standard library
+pub trait Plugin {
+    fn register(&self, registry: &mut StdlibRegistry);
+}
+
+// StdlibRegistry collects the bindings a plugin provides while a global environment is built
+pub struct StdlibRegistry<'a> {
+    alloc: &'a mut Allocator,
+    bindings: &'a mut HashMap<String, Ptr<Value>>,
+}
+
+impl<'a> StdlibRegistry<'a> {
+    pub(super) fn new(
+        alloc: &'a mut Allocator,
+        bindings: &'a mut HashMap<String, Ptr<Value>>,
+    ) -> Self {
+        StdlibRegistry { alloc, bindings }
+    }
+
+    // native binds name to a native function, which is only called with an accepted
+    // number of arguments
+    // the function finishes by calling either Interpreter::return_value or Interpreter::raise
+    pub fn native(&mut self, name: &str, func: NativeFn, arity: Arity) {
+        let val = Value::NativeFunction(NativeFunction {
+            name: name.to_string(),
+            func,
+            arity,
+        })
+        .gc(self.alloc);
+        self.bindings.insert(name.to_string(), val);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::interpreter::{read_source, Environment, Interpreter, Options};
+
+    struct Doubler;
+
+    fn explode(_interp: &mut Interpreter, _env: Ptr<Environment>, _args: &[Ptr<Value>]) {
+        panic!("boom {}", 1)
+    }
+
+    fn double(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+        match interp.value(args[0]) {
+            Value::Integer(i) => {
+                let doubled = i * 2;
+                interp.return_value(Value::Integer(doubled))
+            }
+            _ => interp.raise("double: expected an integer".to_string()),
+        }
+    }
+
+    impl Plugin for Doubler {
+        fn register(&self, registry: &mut StdlibRegistry) {
+            registry.native("double", double, Arity::exactly(1));
+            registry.native("explode", explode, Arity::exactly(0));
+        }
+    }
+
+    fn run_with_plugin(source: &str) -> Result<String, String> {
+        let mut interp = Interpreter::new(Options::default());
+        interp.add_plugin(Box::new(Doubler));
+        let env = interp.new_global_env();
+
+        let mut res = Err("no forms to evaluate".to_string());
+        for node in read_source(source)? {
+            interp.eval_ast(node, env);
+            let val = interp.run().map_err(|err| err.message)?;
+            res = Ok(interp.alloc.get_val(val).to_string(&interp.alloc));
+        }
+        res
+    }
+
+    #[test]
+    fn binds_plugin_natives() {
+        assert_eq!(run_with_plugin("(double (+ 1 2))"), Ok("6".to_string()));
+        assert_eq!(
+            run_with_plugin("(double #t)"),
+            Err("double: expected an integer".to_string())
+        );
+        assert_eq!(
+            run_with_plugin("(double 1 2)"),
+            Err(
+                "wrong number of arguments to #<builtin double>: expected 1, received 2"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn survives_panicking_natives() {
+        assert_eq!(
+            run_with_plugin("(+ 1 (explode))"),
+            Err("native function panicked: boom 1".to_string())
+        );
+        // a panic under a native that called back into Scheme is reported the same way
+        assert_eq!(
+            run_with_plugin("(map (lambda (x) (explode)) (quote (1)))"),
+            Err("native function panicked: boom 1".to_string())
+        );
+
+        let mut interp = Interpreter::new(Options::default());
+        interp.add_plugin(Box::new(Doubler));
+        assert!(interp.eval("(double (explode))").is_err());
+        let res = interp.eval("(double 2)").unwrap();
+        assert_eq!(interp.to_string(&res), "4");
+    }
+}
@@ -0,0 +1,258 @@
+use crate::interpreter::allocator::{Environment, Ptr};
+use crate::interpreter::value::Value;
+use crate::interpreter::{Error, Interpreter, Step, StepKind};
+
+const SPLICE_CONTEXT_ERROR: &str = "unquote-splicing: not in list context";
+const SPLICE_LIST_ERROR: &str = "unquote-splicing: expected a list";
+
+#[derive(Clone, Copy, PartialEq)]
+enum QuasiForm {
+    Quasiquote,
+    Unquote,
+    UnquoteSplicing,
+}
+
+impl Interpreter {
+    // quasi_form matches (quasiquote x), (unquote x) and (unquote-splicing x), returning x
+    fn quasi_form(&self, node: Ptr<Value>) -> Option<(QuasiForm, Ptr<Value>)> {
+        let (hd, tl) = match self.alloc.get_val(node) {
+            Value::Cons(hd, tl) => (*hd, *tl),
+            _ => return None,
+        };
+        let form = match self.alloc.get_val(hd) {
+            Value::Symbol(s) if s == "quasiquote" => QuasiForm::Quasiquote,
+            Value::Symbol(s) if s == "unquote" => QuasiForm::Unquote,
+            Value::Symbol(s) if s == "unquote-splicing" => QuasiForm::UnquoteSplicing,
+            _ => return None,
+        };
+        match self.alloc.get_val(tl) {
+            Value::Cons(arg, rest) if matches!(self.alloc.get_val(*rest), Value::Nil) => {
+                Some((form, *arg))
+            }
+            _ => None,
+        }
+    }
+
+    // is_static returns whether template contains nothing to unquote at depth
+    fn is_static(&self, template: Ptr<Value>, depth: usize) -> bool {
+        let mut node = template;
+        loop {
+            match self.quasi_form(node) {
+                Some((QuasiForm::Quasiquote, arg)) => return self.is_static(arg, depth + 1),
+                Some((_, arg)) => return depth > 1 && self.is_static(arg, depth - 1),
+                None => {}
+            }
+            match self.alloc.get_val(node) {
+                Value::Cons(hd, tl) => {
+                    if !self.is_static(*hd, depth) {
+                        return false;
+                    }
+                    node = *tl;
+                }
+                _ => return true,
+            }
+        }
+    }
+
+    // eval_quasiquote pushes the result of expanding template at the given nesting depth
+    // static parts of the template are shared rather than copied, and a new list cell is
+    // only allocated up to the last unquoted element of each list
+    pub(super) fn eval_quasiquote(
+        &mut self,
+        template: Ptr<Value>,
+        env: Ptr<Environment>,
+        depth: usize,
+    ) {
+        if self.is_static(template, depth) {
+            self.results.push(template);
+            return;
+        }
+
+        let form = self.quasi_form(template).map(|(form, _)| form);
+        let depth = match (form, depth) {
+            (Some(QuasiForm::Unquote), 1) => {
+                let (_, arg) = self.quasi_form(template).unwrap();
+                return self.eval_node(arg, env);
+            }
+            (Some(QuasiForm::UnquoteSplicing), 1) => {
+                self.error = Some(Error {
+                    message: SPLICE_CONTEXT_ERROR.to_string(),
+                    location: None,
+                });
+                return;
+            }
+            (Some(QuasiForm::Quasiquote), _) => depth + 1,
+            (Some(_), _) => depth - 1,
+            (None, _) => depth,
+        };
+
+        // split the template into its elements, stopping at a dotted unquote tail
+        let mut cells = Vec::new();
+        let mut elements = Vec::new();
+        let mut tail = template;
+        while let Value::Cons(hd, tl) = self.alloc.get_val(tail) {
+            if tail != template && self.quasi_form(tail).is_some() {
+                break;
+            }
+            cells.push(tail);
+            elements.push(*hd);
+            tail = *tl;
+        }
+
+        let dynamic_tail = !self.is_static(tail, depth);
+        let count = if dynamic_tail {
+            elements.len()
+        } else {
+            // elements after the last dynamic one are shared with the template
+            elements
+                .iter()
+                .rposition(|&element| !self.is_static(element, depth))
+                .map_or(0, |i| i + 1)
+        };
+        let suffix = cells.get(count).copied().unwrap_or(tail);
+
+        let splices = elements[..count]
+            .iter()
+            .map(|&element| match self.quasi_form(element) {
+                Some((QuasiForm::UnquoteSplicing, arg)) if depth == 1 => Some(arg),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        self.saved_results.push(std::mem::take(&mut self.results));
+        let splice_flags = splices.iter().map(Option::is_some).collect::<Vec<_>>();
+        self.next_steps
+            .push(Step::new(StepKind::BuildList, move |interp| {
+                let mut values =
+                    std::mem::replace(&mut interp.results, interp.saved_results.pop().unwrap());
+                let mut res = if dynamic_tail {
+                    values.pop().unwrap()
+                } else {
+                    suffix
+                };
+
+                for (value, splice) in values.into_iter().zip(splice_flags.iter()).rev() {
+                    if !*splice {
+                        res = Value::Cons(value, res).gc(&mut interp.alloc);
+                    } else if let Value::Nil = interp.alloc.get_val(res) {
+                        // like append, the final spliced list is shared rather than copied
+                        res = value;
+                    } else {
+                        res = match interp.append_copy(value, res) {
+                            Some(res) => res,
+                            None => {
+                                interp.error = Some(Error {
+                                    message: format!(
+                                        "{}, got {}",
+                                        SPLICE_LIST_ERROR,
+                                        interp.alloc.get_val(value).to_string(&interp.alloc)
+                                    ),
+                                    location: None,
+                                });
+                                return;
+                            }
+                        };
+                    }
+                }
+                interp.results.push(res);
+            }));
+
+        if dynamic_tail {
+            self.next_steps
+                .push(Step::new(StepKind::Quasiquote(tail), move |interp| {
+                    interp.eval_quasiquote(tail, env, depth)
+                }));
+        }
+        for (&element, splice) in elements[..count].iter().zip(splices).rev() {
+            let kind = match splice {
+                Some(arg) => StepKind::Eval(arg),
+                None => StepKind::Quasiquote(element),
+            };
+            self.next_steps
+                .push(Step::new(kind, move |interp| match splice {
+                    Some(arg) => interp.eval_node(arg, env),
+                    None => interp.eval_quasiquote(element, env, depth),
+                }));
+        }
+    }
+
+    // append_copy returns a copy of the proper list list with tail as its final cdr
+    fn append_copy(&mut self, list: Ptr<Value>, tail: Ptr<Value>) -> Option<Ptr<Value>> {
+        let mut items = Vec::new();
+        let mut node = list;
+        while let Value::Cons(hd, tl) = self.alloc.get_val(node) {
+            items.push(*hd);
+            node = *tl;
+        }
+        if !matches!(self.alloc.get_val(node), Value::Nil) {
+            return None;
+        }
+
+        let mut res = tail;
+        for item in items.into_iter().rev() {
+            res = Value::Cons(item, res).gc(&mut self.alloc);
+        }
+        Some(res)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::interpreter::test::run;
+
+    #[test]
+    fn expands_templates() {
+        assert_eq!(
+            run("(define x 2) (quasiquote (1 (unquote x) (unquote (+ x 1))))"),
+            Ok("(1 2 3)".to_string())
+        );
+        assert_eq!(
+            run("(define xs (quote (2 3))) (quasiquote (1 (unquote-splicing xs) 4))"),
+            Ok("(1 2 3 4)".to_string())
+        );
+        assert_eq!(
+            run("(define x 2) (quasiquote (1 unquote x))"),
+            Ok("(1 . 2)".to_string())
+        );
+        assert_eq!(
+            run("(quasiquote (1 (quasiquote (unquote (unquote (+ 1 1))))))"),
+            Ok("(1 (quasiquote (unquote 2)))".to_string())
+        );
+    }
+
+    #[test]
+    fn expands_reader_shorthand() {
+        assert_eq!(
+            run("(define xs '(2 3)) `(1 ,@xs ,(+ 2 2) 'q)"),
+            Ok("(1 2 3 4 (quote q))".to_string())
+        );
+    }
+
+    #[test]
+    fn shares_static_structure() {
+        assert_eq!(
+            run("(define f (lambda (x) (quasiquote (a (b c))))) (eq? (f 1) (f 2))"),
+            Ok("#t".to_string())
+        );
+        assert_eq!(
+            run("(define t (quote (a b))) (eq? (quasiquote (unquote t)) t)"),
+            Ok("#t".to_string())
+        );
+        assert_eq!(
+            run("(define xs (quote (1 2))) (eq? (quasiquote ((unquote-splicing xs))) xs)"),
+            Ok("#t".to_string())
+        );
+    }
+
+    #[test]
+    fn reports_bad_splices() {
+        assert_eq!(
+            run("(quasiquote (unquote-splicing (quote (1))))"),
+            Err("unquote-splicing: not in list context".to_string())
+        );
+        assert_eq!(
+            run("(quasiquote ((unquote-splicing 1) 2))"),
+            Err("unquote-splicing: expected a list, got 1".to_string())
+        );
+    }
+}
@@ -1,40 +1,585 @@
-use crate::interpreter::Interpreter;
+use crate::interpreter::allocator::{BindingChange, Environment, Ptr};
+use crate::interpreter::{Interpreter, Options, Output};
+#[cfg(all(feature = "rustyline", not(feature = "minimal-repl")))]
 use rustyline::Editor;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 
-pub fn repl() {
-    let mut interp = Interpreter::new();
-    let env = super::stdlib::build(&mut interp.alloc);
-    let mut editor = Editor::<()>::new();
+const DEFAULT_PROMPT: &str = "> ";
+// shown while reading the rest of an unfinished form
+const CONTINUATION_PROMPT: &str = "... ";
+const DEFAULT_INIT_FILE: &str = ".scheme-interp.scm";
 
-    loop {
-        let line = match editor.readline("> ") {
-            Ok(line) => line,
-            Err(_) => return,
-        };
-        editor.add_history_entry(&line);
+pub(super) struct ReplConfig {
+    pub(super) prompt: String,
+    pub(super) banner: Option<String>,
+}
 
-        let tokens = match crate::lex::tokenize(&line) {
-            Ok(tokens) => tokens,
-            Err(err) => {
-                eprintln!("{}", err.to_string());
-                continue;
+impl ReplConfig {
+    pub(super) fn new() -> Self {
+        ReplConfig {
+            prompt: DEFAULT_PROMPT.to_string(),
+            banner: Some(format!("scheme-interp-rs {}", env!("CARGO_PKG_VERSION"))),
+        }
+    }
+}
+
+fn default_init_file() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+    Some(PathBuf::from(home).join(DEFAULT_INIT_FILE))
+}
+
+pub struct ReplOptions {
+    // loaded instead of the default init file
+    pub init_file: Option<PathBuf>,
+    // receives a verbatim record of the session
+    pub transcript: Option<PathBuf>,
+}
+
+// LineSource is the terminal a REPL session runs on: it supplies input lines
+// and displays results and errors
+pub trait LineSource {
+    // read_line shows prompt and returns the next line, or None at the end of the input
+    fn read_line(&mut self, prompt: &str) -> Option<String>;
+    fn write_output(&mut self, text: &str);
+    fn write_error(&mut self, text: &str);
+}
+
+// EditorSource reads lines with rustyline, giving line editing and history
+#[cfg(all(feature = "rustyline", not(feature = "minimal-repl")))]
+struct EditorSource {
+    editor: Editor<()>,
+}
+
+#[cfg(all(feature = "rustyline", not(feature = "minimal-repl")))]
+impl LineSource for EditorSource {
+    fn read_line(&mut self, prompt: &str) -> Option<String> {
+        let line = self.editor.readline(prompt).ok()?;
+        self.editor.add_history_entry(&line);
+        Some(line)
+    }
+
+    fn write_output(&mut self, text: &str) {
+        println!("{}", text);
+    }
+
+    fn write_error(&mut self, text: &str) {
+        eprintln!("{}", text);
+    }
+}
+
+// StreamSource runs a session over a plain reader and writer, such as stdio or a socket
+// errors are written to the same writer as results
+pub struct StreamSource<R: BufRead, W: Write> {
+    reader: R,
+    writer: W,
+}
+
+impl<R: BufRead, W: Write> StreamSource<R, W> {
+    pub fn new(reader: R, writer: W) -> Self {
+        StreamSource { reader, writer }
+    }
+}
+
+// terminal returns the source for interactive sessions
+#[cfg(all(feature = "rustyline", not(feature = "minimal-repl")))]
+fn terminal() -> impl LineSource {
+    EditorSource {
+        editor: Editor::<()>::new(),
+    }
+}
+
+// without rustyline, interactive sessions are a plain line loop over stdio
+#[cfg(not(all(feature = "rustyline", not(feature = "minimal-repl"))))]
+fn terminal() -> impl LineSource {
+    StreamSource::new(io::stdin().lock(), io::stdout())
+}
+
+impl<R: BufRead, W: Write> LineSource for StreamSource<R, W> {
+    fn read_line(&mut self, prompt: &str) -> Option<String> {
+        write!(self.writer, "{}", prompt).ok()?;
+        self.writer.flush().ok()?;
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(n) if n > 0 => Some(line),
+            _ => None,
+        }
+    }
+
+    fn write_output(&mut self, text: &str) {
+        // a disconnected client shows up as the end of the input on the next read
+        let _ = writeln!(self.writer, "{}", text);
+    }
+
+    fn write_error(&mut self, text: &str) {
+        self.write_output(text);
+    }
+}
+
+struct Session<'a> {
+    interp: &'a mut Interpreter,
+    env: Ptr<Environment>,
+    io: &'a mut dyn LineSource,
+    transcript: Option<File>,
+    // environment version before the last evaluated input
+    checkpoint: u64,
+    // the status passed to exit, which ends the session
+    exit_code: Option<i32>,
+    // follow each result with its type, evaluation time and allocations
+    show_meta: bool,
+}
+
+impl<'a> Session<'a> {
+    fn new(interp: &'a mut Interpreter, io: &'a mut dyn LineSource) -> Self {
+        // what natives print goes through the session, so it's part of the transcript
+        interp.capture_output(true);
+        let env = interp.global_env();
+        let checkpoint = interp.alloc.version(env);
+        Session {
+            interp,
+            env,
+            io,
+            transcript: None,
+            checkpoint,
+            exit_code: None,
+            show_meta: false,
+        }
+    }
+
+    fn record(&mut self, text: &str) {
+        if let Some(transcript) = &mut self.transcript {
+            if let Err(err) = writeln!(transcript, "{}", text) {
+                eprintln!("Unable to write transcript, recording stopped: {}", err);
+                self.transcript = None;
             }
-        };
+        }
+    }
+
+    fn output(&mut self, text: &str) {
+        self.io.write_output(text);
+        self.record(text);
+    }
 
-        let nodes = match crate::parse::parse(&tokens) {
+    fn error(&mut self, text: &str) {
+        self.io.write_error(text);
+        self.record(text);
+    }
+
+    // eval_source evaluates every form in source, reporting errors
+    // the value of each form is output if echo is set
+    fn eval_source(&mut self, source: &str, echo: bool) {
+        self.checkpoint = self.interp.alloc.version(self.env);
+        let nodes = match super::read_source(source) {
             Ok(nodes) => nodes,
             Err(err) => {
-                eprintln!("{}", err.to_string());
-                continue;
+                self.error(&err);
+                return;
             }
         };
 
         for node in nodes {
-            interp.eval_ast(node, env);
-            match interp.run() {
-                Err(err) => eprintln!("Error: {}", err.to_string()),
-                Ok(val) => println!("{}", interp.alloc.get_val(val).to_string(&interp.alloc)),
+            let start = Instant::now();
+            let before = self.allocations();
+            self.interp.eval_ast(node, self.env);
+            let res = self.interp.run();
+            self.show_captured_output();
+            match res {
+                Err(_) if self.interp.exit_code.is_some() => {
+                    self.exit_code = self.interp.exit_code.take();
+                    return;
+                }
+                Err(err) => self.error(&format!("Error: {}", err)),
+                Ok(val) if echo => {
+                    let val = self.interp.alloc.get_val(val);
+                    let mut text = val.to_string(&self.interp.alloc);
+                    if self.show_meta {
+                        text = format!(
+                            "{} ; {}, {:.1}ms, {} allocs",
+                            text,
+                            val.type_name(),
+                            start.elapsed().as_secs_f64() * 1000.0,
+                            self.allocations() - before
+                        );
+                    }
+                    self.output(&text)
+                }
+                Ok(_) => {}
+            }
+        }
+    }
+
+    // show_captured_output outputs what natives printed, and warnings as errors
+    fn show_captured_output(&mut self) {
+        for output in self.interp.take_output() {
+            match output {
+                Output::Line(text) => self.output(&text),
+                Output::Warning(text) => self.error(&text),
             }
         }
     }
+
+    // allocations counts the values and environments allocated so far
+    fn allocations(&self) -> u64 {
+        let stats = self.interp.heap_stats();
+        stats.values_allocated + stats.environments_allocated
+    }
+
+    // command runs a REPL command, given without its leading colon
+    fn command(&mut self, command: &str) {
+        match command.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["changed"] => self.show_changes(),
+            ["set", option, value] => self.set_option(option, value),
+            _ => self.error(&format!("Unknown command: :{}", command)),
+        }
+    }
+
+    // set_option turns a session option on or off
+    fn set_option(&mut self, option: &str, value: &str) {
+        let enabled = match value {
+            "on" => true,
+            "off" => false,
+            _ => return self.error(&format!("Expected on or off, got {}", value)),
+        };
+        match option {
+            "show-meta" => self.show_meta = enabled,
+            _ => self.error(&format!("Unknown option: {}", option)),
+        }
+    }
+
+    // show_changes outputs the top-level bindings added or modified by the last evaluated input
+    fn show_changes(&mut self) {
+        let alloc = &self.interp.alloc;
+        let changes = alloc.changes_since(self.env, self.checkpoint);
+
+        if changes.is_empty() {
+            self.output("No changes");
+            return;
+        }
+        for change in changes {
+            let kind = match change {
+                BindingChange::Added(_) => "added",
+                BindingChange::Modified(_) => "modified",
+            };
+            let alloc = &self.interp.alloc;
+            let value = match alloc.get_bound_ptr(self.env, change.name()) {
+                Some(ptr) => alloc.get_val(ptr).to_string(alloc),
+                None => continue,
+            };
+            self.output(&format!("{} {} = {}", kind, change.name(), value));
+        }
+    }
+
+    fn load_init_file(&mut self, path: &Path) {
+        match std::fs::read_to_string(path) {
+            Ok(source) => self.eval_source(&source, false),
+            Err(err) => self.error(&format!("Unable to load {}: {}", path.display(), err)),
+        }
+    }
+
+    // run reads and evaluates lines until the input ends or exit is called
+    // lines are gathered until the forms they hold are complete
+    fn run(&mut self) {
+        if let Some(banner) = self.interp.repl.banner.clone() {
+            self.output(&banner);
+        }
+
+        let mut source = String::new();
+        while self.exit_code.is_none() {
+            let prompt = if source.is_empty() {
+                self.interp.repl.prompt.clone()
+            } else {
+                CONTINUATION_PROMPT.to_string()
+            };
+            let line = match self.io.read_line(&prompt) {
+                Some(line) => line,
+                None => {
+                    // report the unfinished form
+                    if !source.is_empty() {
+                        self.eval_source(&source, true);
+                    }
+                    return;
+                }
+            };
+            let line = line.trim_end_matches(&['\r', '\n'][..]);
+            self.record(&format!("{}{}", prompt, line));
+
+            if source.is_empty() {
+                if let Some(command) = line.trim().strip_prefix(':') {
+                    self.command(command);
+                    continue;
+                }
+            }
+            source.push_str(line);
+            source.push('\n');
+            if !super::is_incomplete(&source) {
+                self.eval_source(&source, true);
+                source.clear();
+            }
+        }
+    }
+}
+
+impl Drop for Session<'_> {
+    fn drop(&mut self) {
+        self.interp.capture_output(false);
+    }
+}
+
+// load_init_file loads the init file from options if provided
+// or the default init file from the home directory if it exists
+fn load_init_file(session: &mut Session, repl_options: &ReplOptions) {
+    match &repl_options.init_file {
+        Some(path) => session.load_init_file(path),
+        None => {
+            if let Some(path) = default_init_file().filter(|path| path.is_file()) {
+                session.load_init_file(&path)
+            }
+        }
+    }
+}
+
+// repl runs an interactive session, first loading the init file
+pub fn repl(repl_options: &ReplOptions, options: Options) {
+    let transcript = match &repl_options.transcript {
+        Some(path) => match File::create(path) {
+            Ok(file) => Some(file),
+            Err(err) => {
+                eprintln!("Unable to create transcript {}: {}", path.display(), err);
+                return;
+            }
+        },
+        None => None,
+    };
+
+    let mut interp = Interpreter::new(options);
+    let mut io = terminal();
+    let mut session = Session::new(&mut interp, &mut io);
+    session.transcript = transcript;
+
+    load_init_file(&mut session, repl_options);
+    if session.exit_code.is_none() {
+        session.run();
+    }
+    if let Some(code) = session.exit_code {
+        std::process::exit(code)
+    }
+}
+
+// serve runs REPL sessions over connections to listener, one client at a time
+// every session shares the interpreter's global environment, so definitions persist
+// between connections, and exit ends only the current connection
+pub fn serve(interp: &mut Interpreter, listener: &TcpListener) -> io::Result<()> {
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let reader = BufReader::new(stream.try_clone()?);
+        let mut io = StreamSource::new(reader, stream);
+        Session::new(interp, &mut io).run();
+    }
+    Ok(())
+}
+
+// listen loads the init file, then serves REPL sessions on addr
+pub fn listen(addr: &str, repl_options: &ReplOptions, options: Options) -> io::Result<()> {
+    let mut interp = Interpreter::new(options);
+    let mut stdio = StreamSource::new(io::stdin().lock(), io::stdout());
+    load_init_file(&mut Session::new(&mut interp, &mut stdio), repl_options);
+
+    let listener = TcpListener::bind(addr)?;
+    eprintln!("Listening on {}", listener.local_addr()?);
+    serve(&mut interp, &listener)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn reads_forms_across_lines() {
+        let mut interp = Interpreter::new(Options::default());
+        interp.eval("(repl-banner! #f)").unwrap();
+
+        let mut output = Vec::new();
+        let input = Cursor::new("(define (f x)\n  ; comment\n  (* x 2))\n(f\n\"a\nb\")\n(f 2) (\n");
+        Session::new(&mut interp, &mut StreamSource::new(input, &mut output)).run();
+        // a form still unfinished at the end of the input is reported with its line
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "> ... ... ()\n> ... ... Error: Runtime error at line 2, col 2: \
+             all arguments to '*' must be numbers\n\
+             > ... Syntax error at line 0, col 6: missing matching ')'\n"
+        );
+    }
+
+    #[test]
+    fn reads_datum_comments_across_lines() {
+        let mut interp = Interpreter::new(Options::default());
+        interp.eval("(repl-banner! #f)").unwrap();
+
+        let mut output = Vec::new();
+        let input =
+            Cursor::new("(+ 1\n   #;(* 2\n        3)\n   4)\n#;\n(car '())\n5\n#;#;1\n2 6\n");
+        Session::new(&mut interp, &mut StreamSource::new(input, &mut output)).run();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "> ... ... ... 5\n> ... > 5\n> ... 6\n> "
+        );
+    }
+
+    #[test]
+    fn shows_result_metadata() {
+        let mut interp = Interpreter::new(Options::default());
+        interp.eval("(repl-banner! #f)").unwrap();
+
+        let mut output = Vec::new();
+        let input = Cursor::new(
+            ":set show-meta on\n(+ 1 2)\n(list 1 2)\n:set show-meta off\n'a\n:set show-meta 1\n",
+        );
+        Session::new(&mut interp, &mut StreamSource::new(input, &mut output)).run();
+        let output = String::from_utf8(output).unwrap();
+        let lines = output.split("> ").collect::<Vec<_>>();
+        // timings vary, so only the value and type are checked exactly
+        let meta = |line: &str| {
+            let (value, meta) = line.split_once(" ; ").unwrap();
+            let parts = meta.trim_end().split(", ").collect::<Vec<_>>();
+            assert!(parts[1].ends_with("ms"));
+            let allocs = parts[2].strip_suffix(" allocs").unwrap();
+            assert!(allocs.parse::<u64>().unwrap() > 0);
+            (value.to_string(), parts[0].to_string())
+        };
+        assert_eq!(meta(lines[2]), ("3".to_string(), "integer".to_string()));
+        assert_eq!(meta(lines[3]).1, "pair");
+        assert_eq!(&lines[5..], &["a\n", "Expected on or off, got 1\n", ""]);
+    }
+
+    #[test]
+    fn records_native_output_in_transcript() {
+        let mut interp = Interpreter::new(Options {
+            warn_shadowing: true,
+            ..Options::default()
+        });
+        interp.eval("(repl-banner! #f)").unwrap();
+        let path = std::env::temp_dir().join(format!("scheme-transcript-{}", std::process::id()));
+
+        let mut output = Vec::new();
+        let input = Cursor::new(
+            "(gc-profile)
+(define car 1)
+",
+        );
+        let mut io = StreamSource::new(input, &mut output);
+        let mut session = Session::new(&mut interp, &mut io);
+        session.transcript = Some(File::create(&path).unwrap());
+        session.run();
+        drop(session);
+
+        let transcript = std::fs::read_to_string(&path).unwrap();
+        let lines = transcript.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "> (gc-profile)");
+        assert!(lines[1].starts_with("values: size: "));
+        assert!(lines[2].starts_with("environments: size: "));
+        assert_eq!(
+            &lines[3..],
+            &[
+                "()",
+                "> (define car 1)",
+                "Warning at line 0, col 8: definition of 'car' replaces a builtin",
+                "()"
+            ]
+        );
+        // the session's own output has the same lines, without the input
+        assert!(String::from_utf8(output).unwrap().ends_with(
+            "()\n> Warning at line 0, col 8: definition of 'car' replaces a builtin\n()\n> "
+        ));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn shows_changes_made_by_the_last_input() {
+        let mut interp = Interpreter::new(Options::default());
+        interp.eval("(repl-banner! #f)").unwrap();
+
+        let mut output = Vec::new();
+        let input = Cursor::new(
+            "(define x 1)\n(define w 0)\n(define y 2) (define x 3)\n:changed\n:changed\n'a\n:changed\n",
+        );
+        Session::new(&mut interp, &mut StreamSource::new(input, &mut output)).run();
+        // only the last of the inputs evaluated since the previous :changed counts
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "> ()\n> ()\n> ()\n()\n> modified x = 3\nadded y = 2\n\
+             > modified x = 3\nadded y = 2\n> a\n> No changes\n> "
+        );
+    }
+
+    #[test]
+    fn loads_init_file_before_the_banner_and_prompt() {
+        let path = std::env::temp_dir().join(format!("scheme-init-{}.scm", std::process::id()));
+        std::fs::write(
+            &path,
+            "(repl-prompt! \"scm> \")\n(repl-banner! \"hello\")\n(car '())\n(define greeting 'hi)\n",
+        )
+        .unwrap();
+        let repl_options = ReplOptions {
+            init_file: Some(path.clone()),
+            transcript: None,
+        };
+
+        let mut interp = Interpreter::new(Options::default());
+        let mut output = Vec::new();
+        let mut io = StreamSource::new(Cursor::new("greeting\n"), &mut output);
+        let mut session = Session::new(&mut interp, &mut io);
+        load_init_file(&mut session, &repl_options);
+        session.run();
+        drop(session);
+        // errors in the init file are reported without stopping it, and its values aren't echoed
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Error: Runtime error at line 2, col 0: the first argument to 'car' must be a pair\n\
+             hello\nscm> hi\nscm> "
+        );
+
+        // a missing init file is reported, then the default banner and prompt are shown
+        std::fs::remove_file(&path).unwrap();
+        let mut interp = Interpreter::new(Options::default());
+        let mut output = Vec::new();
+        let mut io = StreamSource::new(Cursor::new(""), &mut output);
+        let mut session = Session::new(&mut interp, &mut io);
+        load_init_file(&mut session, &repl_options);
+        session.run();
+        drop(session);
+        let output = String::from_utf8(output).unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+        assert!(lines[0].starts_with(&format!("Unable to load {}: ", path.display())));
+        assert_eq!(
+            &lines[1..],
+            &[
+                format!("scheme-interp-rs {}", env!("CARGO_PKG_VERSION")),
+                DEFAULT_PROMPT.to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn runs_sessions_over_streams() {
+        let mut interp = Interpreter::new(Options::default());
+        interp.eval("(repl-banner! #f)").unwrap();
+
+        let mut output = Vec::new();
+        let input = Cursor::new("(define x 2)\n(+ x 1)\n:bogus\n(exit)\n(+ x 2)\n");
+        Session::new(&mut interp, &mut StreamSource::new(input, &mut output)).run();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "> ()\n> 3\n> Unknown command: :bogus\n> "
+        );
+
+        // a later session sees the earlier definitions
+        let mut output = Vec::new();
+        let input = Cursor::new("x\n");
+        Session::new(&mut interp, &mut StreamSource::new(input, &mut output)).run();
+        assert_eq!(String::from_utf8(output).unwrap(), "> 2\n> ");
+    }
 }
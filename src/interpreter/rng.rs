@@ -0,0 +1,63 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Rng is a small xorshift64* generator, the source of all randomness in the interpreter
+// so that seeding it makes programs reproducible
+pub(super) struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    // new seeds the generator, from the clock and process id if seed is None
+    pub(super) fn new(seed: Option<u64>) -> Self {
+        let seed = seed.unwrap_or_else(|| {
+            let nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+            nanos ^ (u64::from(std::process::id()) << 32)
+        });
+        // scramble the seed with splitmix64, as xorshift needs a non-zero state
+        // and similar seeds should give unrelated sequences
+        let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        Rng {
+            state: if z == 0 { 1 } else { z },
+        }
+    }
+
+    pub(super) fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    // below returns a uniformly distributed integer in [0, n), n must be positive
+    pub(super) fn below(&mut self, n: u64) -> u64 {
+        // reject the top partial range to avoid modulo bias
+        let zone = u64::MAX - u64::MAX % n;
+        loop {
+            let x = self.next_u64();
+            if x < zone {
+                return x % n;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn seeded_sequences_repeat() {
+        let mut a = Rng::new(Some(42));
+        let mut b = Rng::new(Some(42));
+        let mut c = Rng::new(Some(43));
+        let a = (0..4).map(|_| a.below(1000)).collect::<Vec<_>>();
+        assert_eq!(a, (0..4).map(|_| b.below(1000)).collect::<Vec<_>>());
+        assert_ne!(a, (0..4).map(|_| c.below(1000)).collect::<Vec<_>>());
+        assert!(a.iter().all(|&x| x < 1000));
+    }
+}
@@ -0,0 +1,407 @@
+use crate::interpreter::allocator::{Environment, Ptr};
+use crate::interpreter::include::resolve_includes;
+use crate::interpreter::stdlib::exit_status;
+use crate::interpreter::value::Value;
+use crate::interpreter::{Error, Interpreter, Options};
+use crate::parse::Node;
+use std::path::Path;
+use std::str::FromStr;
+
+// exit statuses follow the BSD sysexits conventions
+const EXIT_SUCCESS: i32 = 0;
+const EXIT_SYNTAX_ERROR: i32 = 65;
+const EXIT_NO_INPUT: i32 = 66;
+pub const DEFAULT_ERROR_EXIT_CODE: i32 = 70;
+
+// ErrorFormat selects how errors are written to stderr
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorFormat {
+    Human,
+    // one JSON object per line, for editors and CI
+    Json,
+}
+
+impl FromStr for ErrorFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(ErrorFormat::Human),
+            "json" => Ok(ErrorFormat::Json),
+            _ => Err(format!(
+                "unknown error format '{}', expected human or json",
+                s
+            )),
+        }
+    }
+}
+
+pub struct ScriptOptions {
+    // report the calls that were active when an error occurred
+    pub backtrace: bool,
+    pub error_exit_code: i32,
+    pub error_format: ErrorFormat,
+}
+
+// Report is an error to be written in the chosen ErrorFormat
+struct Report<'a> {
+    // one of "syntax", "runtime" or "io"
+    kind: &'static str,
+    message: String,
+    file: Option<&'a Path>,
+    // the position of the error, if known
+    position: Option<(u64, u64)>,
+    backtrace: Vec<String>,
+}
+
+impl Report<'_> {
+    fn emit(&self, format: ErrorFormat) {
+        match format {
+            ErrorFormat::Human => {
+                eprintln!("{}", self.human());
+                if !self.backtrace.is_empty() {
+                    eprintln!("Active calls, innermost first:");
+                    for call in &self.backtrace {
+                        eprintln!("  {}", call);
+                    }
+                }
+            }
+            ErrorFormat::Json => eprintln!("{}", self.to_json()),
+        }
+    }
+
+    // human formats the report as the interpreter has always written errors
+    fn human(&self) -> String {
+        let file = self.file.map(|path| path.display().to_string());
+        match (self.kind, self.position) {
+            ("syntax", Some((line, column))) => format!(
+                "Syntax error at line {}, col {}: {}",
+                line, column, self.message
+            ),
+            ("io", _) => format!(
+                "Unable to load {}: {}",
+                file.unwrap_or_default(),
+                self.message
+            ),
+            ("runtime", Some((line, column))) => format!(
+                "Error: Runtime error at line {}, col {}: {}",
+                line, column, self.message
+            ),
+            _ => format!("Error: Runtime error: {}", self.message),
+        }
+    }
+
+    fn to_json(&self) -> String {
+        let file = match self.file {
+            Some(path) => json_string(&path.display().to_string()),
+            None => "null".to_string(),
+        };
+        let (line, column) = match self.position {
+            Some((line, column)) => (line.to_string(), column.to_string()),
+            None => ("null".to_string(), "null".to_string()),
+        };
+        let backtrace = self
+            .backtrace
+            .iter()
+            .map(|call| json_string(call))
+            .collect::<Vec<_>>()
+            .join(",");
+        format!(
+            "{{\"kind\":{},\"message\":{},\"file\":{},\"line\":{},\"column\":{},\"backtrace\":[{}]}}",
+            json_string(self.kind),
+            json_string(&self.message),
+            file,
+            line,
+            column,
+            backtrace
+        )
+    }
+}
+
+// json_string quotes s as a JSON string
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for chr in s.chars() {
+        match chr {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            chr if chr.is_control() => out.push_str(&format!("\\u{:04x}", chr as u32)),
+            chr => out.push(chr),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// read tokenizes and parses source, reporting a syntax error on failure
+fn read<'a>(source: &str, file: Option<&'a Path>) -> Result<Vec<Node>, Report<'a>> {
+    let syntax_error = |message: &str, line: u64, column: u64| Report {
+        kind: "syntax",
+        message: message.to_string(),
+        file,
+        position: Some((line, column)),
+        backtrace: Vec::new(),
+    };
+
+    let tokens = crate::lex::tokenize(source)
+        .map_err(|err| syntax_error(err.message, err.line, err.column))?;
+    crate::parse::parse(&tokens).map_err(|err| syntax_error(err.message, err.line, err.column))
+}
+
+// run_source evaluates every form in source, stopping at the first error
+// it returns the exit status the process should terminate with
+pub fn run_source(source: &str, options: &ScriptOptions, interp_options: Options) -> i32 {
+    run(source, None, &[], options, interp_options)
+}
+
+// call_main calls the procedure main defined by a script, if there is one,
+// with the script's arguments as a list of strings
+// its result is the exit status: an integer from 0 to 255 as given, #f for failure
+// and otherwise success
+fn call_main(
+    interp: &mut Interpreter,
+    env: Ptr<Environment>,
+    args: &[String],
+) -> Result<i32, Error> {
+    let main = match interp.alloc.get_bound_ptr(env, "main") {
+        Some(main) => main,
+        None => return Ok(EXIT_SUCCESS),
+    };
+    match interp.alloc.get_val(main) {
+        Value::Function(_) | Value::NativeFunction(_) => {}
+        _ => return Ok(EXIT_SUCCESS),
+    }
+
+    let args = args
+        .iter()
+        .map(|arg| Value::String(arg.clone()).gc(&mut interp.alloc))
+        .collect::<Vec<_>>();
+    let args = Value::from_vec(&args, &mut interp.alloc);
+    let res = interp.call(main, &[args], env)?;
+    match interp.alloc.get_val(res) {
+        Value::Integer(code) => exit_status(*code).map_err(|message| Error {
+            message: format!("main: {}", message),
+            location: None,
+        }),
+        Value::Bool(false) => Ok(1),
+        _ => Ok(EXIT_SUCCESS),
+    }
+}
+
+fn run(
+    source: &str,
+    file: Option<&Path>,
+    args: &[String],
+    options: &ScriptOptions,
+    interp_options: Options,
+) -> i32 {
+    let mut interp = Interpreter::new(interp_options);
+    let env = interp.new_global_env();
+    if options.backtrace {
+        interp.enable_backtrace();
+    }
+
+    let nodes = match read(source, file) {
+        Ok(nodes) => nodes,
+        Err(report) => {
+            report.emit(options.error_format);
+            return EXIT_SYNTAX_ERROR;
+        }
+    };
+
+    let fail = |interp: &mut Interpreter, err: Error| {
+        if let Some(code) = interp.exit_code.take() {
+            return code;
+        }
+
+        let report = Report {
+            kind: "runtime",
+            message: err.message,
+            file,
+            position: err
+                .location
+                .map(|location| (location.line, location.column)),
+            backtrace: interp.backtrace(),
+        };
+        report.emit(options.error_format);
+        options.error_exit_code
+    };
+
+    for mut node in nodes {
+        if let Some(dir) = file.and_then(Path::parent) {
+            resolve_includes(&mut node, dir);
+        }
+        interp.eval_ast(node, env);
+        if let Err(err) = interp.run() {
+            return fail(&mut interp, err);
+        }
+    }
+
+    // scripts run from a file may define a main procedure to be called once they are loaded
+    if file.is_none() {
+        return EXIT_SUCCESS;
+    }
+    match call_main(&mut interp, env, args) {
+        Ok(code) => code,
+        Err(err) => fail(&mut interp, err),
+    }
+}
+
+// run_file evaluates the script at path, then calls its main procedure with args if it defines one
+pub fn run_file(
+    path: &Path,
+    args: &[String],
+    options: &ScriptOptions,
+    interp_options: Options,
+) -> i32 {
+    match std::fs::read_to_string(path) {
+        Ok(source) => run(&source, Some(path), args, options, interp_options),
+        Err(err) => {
+            let report = Report {
+                kind: "io",
+                message: err.to_string(),
+                file: Some(path),
+                position: None,
+                backtrace: Vec::new(),
+            };
+            report.emit(options.error_format);
+            EXIT_NO_INPUT
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn formats_json_reports() {
+        let report = read("(a \"b", Some(Path::new("dir/x.scm"))).err().unwrap();
+        assert_eq!(
+            report.to_json(),
+            "{\"kind\":\"syntax\",\"message\":\"unterminated string literal\",\
+             \"file\":\"dir/x.scm\",\"line\":0,\"column\":3,\"backtrace\":[]}"
+        );
+
+        let report = Report {
+            kind: "runtime",
+            message: "unbound symbol: \"q\"\n".to_string(),
+            file: None,
+            position: None,
+            backtrace: vec!["(f 1)".to_string()],
+        };
+        assert_eq!(
+            report.to_json(),
+            "{\"kind\":\"runtime\",\"message\":\"unbound symbol: \\\"q\\\"\\n\",\"file\":null,\
+             \"line\":null,\"column\":null,\"backtrace\":[\"(f 1)\"]}"
+        );
+    }
+
+    #[test]
+    fn calls_main_with_arguments() {
+        let options = ScriptOptions {
+            backtrace: false,
+            error_exit_code: DEFAULT_ERROR_EXIT_CODE,
+            error_format: ErrorFormat::Human,
+        };
+        let run_script = |source: &str, args: &[&str]| {
+            let args = args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+            let file = Some(Path::new("main.scm"));
+            run(source, file, &args, &options, Options::default())
+        };
+
+        let source = "(define (main args) (if (equal? args '(\"a\" \"b\")) 3 4))";
+        assert_eq!(run_script(source, &["a", "b"]), 3);
+        assert_eq!(run_script(source, &[]), 4);
+        assert_eq!(run_script("(define (main args) #f)", &[]), 1);
+        assert_eq!(run_script("(define (main args) (exit 5))", &[]), 5);
+        assert_eq!(
+            run_script("(define (main) 0)", &[]),
+            DEFAULT_ERROR_EXIT_CODE
+        );
+        assert_eq!(run_script("(define main 2)", &[]), EXIT_SUCCESS);
+        assert_eq!(run_script("(define (main args) 255)", &[]), 255);
+        // statuses the operating system would truncate are errors, not success
+        assert_eq!(
+            run_script("(define (main args) 256)", &[]),
+            DEFAULT_ERROR_EXIT_CODE
+        );
+        assert_eq!(
+            run_script("(define (main args) (exit 256))", &[]),
+            DEFAULT_ERROR_EXIT_CODE
+        );
+        assert_eq!(
+            run_script("(define (main args) -1)", &[]),
+            DEFAULT_ERROR_EXIT_CODE
+        );
+        // main is only called when running a file
+        let source = "(define (main args) 3)";
+        assert_eq!(run(source, None, &[], &options, Options::default()), 0);
+    }
+
+    #[test]
+    fn exits_with_status_of_exit() {
+        let options = ScriptOptions {
+            backtrace: false,
+            error_exit_code: DEFAULT_ERROR_EXIT_CODE,
+            error_format: ErrorFormat::Human,
+        };
+        let run_script = |source: &str| run_source(source, &options, Options::default());
+
+        assert_eq!(run_script("(exit)"), EXIT_SUCCESS);
+        assert_eq!(run_script("(exit 3) (exit 4)"), 3);
+        assert_eq!(run_script("(exit #f)"), 1);
+        assert_eq!(run_script("(exit 255)"), 255);
+        assert_eq!(run_script("(exit 256)"), DEFAULT_ERROR_EXIT_CODE);
+        assert_eq!(run_script("(exit -1)"), DEFAULT_ERROR_EXIT_CODE);
+    }
+
+    #[test]
+    fn backtrace_lists_active_calls() {
+        let backtrace = |source: &str| {
+            let mut interp = Interpreter::new(Options::default());
+            let env = interp.new_global_env();
+            interp.enable_backtrace();
+            for node in read(source, None).ok().unwrap() {
+                interp.eval_ast(node, env);
+                if interp.run().is_err() {
+                    return interp.backtrace();
+                }
+            }
+            panic!("no error in {}", source);
+        };
+
+        // calls that have returned are not listed, nor are calls waiting on their arguments
+        let source = "(define (h) (list 1))
+                      (define (g x) (+ 1 (car x)))
+                      (define (f x) (h) (+ 1 (g x)))
+                      (f 5)";
+        assert_eq!(
+            backtrace(source),
+            vec![
+                "(car x) at line 1, col 41",
+                "(g x) at line 2, col 45",
+                "(f 5) at line 3, col 22",
+            ]
+        );
+
+        // a tail call replaces its caller, so loops don't grow the stack
+        let source = "(define (count n) (if (= n 0) (car n) (count (- n 1))))
+                      (count 100000)";
+        assert_eq!(backtrace(source), vec!["(car n) at line 0, col 30"]);
+
+        // escaping with a continuation leaves the calls it escaped from
+        let source = "(define (fail k) (k 1))
+                      (define (g) (+ 1 (call/cc fail)))
+                      (define (f) (+ 1 (g) (car 1)))
+                      (f)";
+        assert_eq!(
+            backtrace(source),
+            vec!["(car 1) at line 2, col 43", "(f) at line 3, col 22"]
+        );
+    }
+}
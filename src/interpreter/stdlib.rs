@@ -1,200 +1,3489 @@
 use super::{value::*, Error};
-use crate::interpreter::allocator::{Allocator, Environment, Ptr};
+use crate::interpreter::allocator::{Allocator, Environment, GcCause, Ptr};
+use crate::interpreter::date::Date;
+use crate::interpreter::language::Language;
+use crate::interpreter::number::{format_float_in, Notation, Number};
+use crate::interpreter::plugin::{Plugin, StdlibRegistry};
+use crate::interpreter::syntax;
 use crate::interpreter::Interpreter;
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 fn plus(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
-    let mut sum = 0;
+    let mut sum = Number::Integer(0);
+    for arg in args {
+        sum = sum.add(expect_num!(
+            interp,
+            *arg,
+            "all arguments to '+' must be numbers"
+        ));
+    }
+    ret!(interp, sum.into_value())
+}
 
+fn times(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let mut prod = Number::Integer(1);
     for arg in args {
-        match interp.alloc.get_val(*arg) {
-            Value::Integer(i) => sum += *i,
-            _ => {
-                interp.error = Some(Error {
-                    message: "all arguments to '+' must be integers".to_string(),
-                });
-                return;
-            }
-        }
+        prod = prod.mul(expect_num!(
+            interp,
+            *arg,
+            "all arguments to '*' must be numbers"
+        ));
     }
+    ret!(interp, prod.into_value())
+}
 
-    interp
-        .results
-        .push(Value::Integer(sum).gc(&mut interp.alloc));
+fn ieq(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let i1 = expect_int!(
+        interp,
+        args[0],
+        "the first argument to 'i=' must be an integer"
+    );
+    let i2 = expect_int!(
+        interp,
+        args[1],
+        "the second argument to 'i=' must be an integer"
+    );
+    ret!(interp, Value::Bool(i1 == i2))
 }
 
-fn times(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
-    let mut prod = 1;
+fn ilt(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let i1 = expect_int!(
+        interp,
+        args[0],
+        "the first argument to 'i<' must be an integer"
+    );
+    let i2 = expect_int!(
+        interp,
+        args[1],
+        "the second argument to 'i<' must be an integer"
+    );
+    ret!(interp, Value::Bool(i1 < i2))
+}
 
+// divide divides the first argument by the rest, or returns the reciprocal of a single argument
+fn divide(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let mut nums = Vec::with_capacity(args.len());
     for arg in args {
-        match interp.alloc.get_val(*arg) {
-            Value::Integer(i) => prod *= *i,
-            _ => {
-                interp.error = Some(Error {
-                    message: "all arguments to '*' must be integers".to_string(),
-                });
-                return;
-            }
-        }
+        nums.push(expect_num!(
+            interp,
+            *arg,
+            "all arguments to '/' must be numbers"
+        ));
     }
-
-    interp
-        .results
-        .push(Value::Integer(prod).gc(&mut interp.alloc));
+    let (mut res, divisors) = match nums.as_slice() {
+        [num] => (Number::Integer(1), std::slice::from_ref(num)),
+        [first, rest @ ..] => (*first, rest),
+        [] => unreachable!(),
+    };
+    for divisor in divisors {
+        res = match res.div(*divisor) {
+            Some(res) => res,
+            None => bail!(interp, "/: division by zero"),
+        };
+    }
+    ret!(interp, res.into_value())
 }
 
-fn ieq(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
-    if args.len() != 2 {
-        interp.error = Some(Error {
-            message: "i= takes 2 arguments".to_string(),
-        });
-        return;
+// compare_chain returns whether test holds for the ordering of each adjacent pair of
+// arguments, checking every argument is a number even once the result is known
+// comparisons involving NaN are false
+fn compare_chain(
+    interp: &mut Interpreter,
+    args: &[Ptr<Value>],
+    name: &str,
+    test: fn(Ordering) -> bool,
+) {
+    let mut nums = Vec::with_capacity(args.len());
+    for arg in args {
+        nums.push(expect_num!(
+            interp,
+            *arg,
+            "all arguments to '{}' must be numbers",
+            name
+        ));
     }
+    let res = nums
+        .windows(2)
+        .all(|pair| pair[0].compare(pair[1]).is_some_and(test));
+    ret!(interp, Value::Bool(res))
+}
 
-    let i1 = match interp.alloc.get_val(args[0]) {
-        Value::Integer(i) => i,
-        _ => {
-            interp.error = Some(Error {
-                message: "the first argument to 'i=' must be an integer".to_string(),
-            });
-            return;
-        }
-    };
+fn num_eq(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    compare_chain(interp, args, "=", |ord| ord == Ordering::Equal)
+}
 
-    let i2 = match interp.alloc.get_val(args[1]) {
-        Value::Integer(i) => i,
-        _ => {
-            interp.error = Some(Error {
-                message: "the second argument to 'i=' must be an integer".to_string(),
-            });
-            return;
-        }
-    };
+fn num_lt(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    compare_chain(interp, args, "<", |ord| ord == Ordering::Less)
+}
+
+fn num_le(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    compare_chain(interp, args, "<=", |ord| ord != Ordering::Greater)
+}
+
+fn num_gt(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    compare_chain(interp, args, ">", |ord| ord == Ordering::Greater)
+}
 
-    interp
-        .results
-        .push(Value::Bool(i1 == i2).gc(&mut interp.alloc));
+fn num_ge(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    compare_chain(interp, args, ">=", |ord| ord != Ordering::Less)
 }
 
 fn minus(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
-    if args.len() != 2 {
-        interp.error = Some(Error {
-            message: "'-' takes 2 arguments".to_string(),
-        });
-        return;
+    let n1 = expect_num!(
+        interp,
+        args[0],
+        "the first argument to '-' must be a number"
+    );
+    let n2 = expect_num!(
+        interp,
+        args[1],
+        "the second argument to '-' must be a number"
+    );
+    ret!(interp, n1.sub(n2).into_value())
+}
+
+// the most digits number->string writes after the point
+const MAX_NOTATION_DIGITS: i64 = 100;
+
+// number_to_string writes a number as it is printed, or in the notation given by a symbol,
+// fixed or scientific, followed by the number of digits after the point, 6 if not given
+fn number_to_string(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let num = expect_num!(
+        interp,
+        args[0],
+        "the first argument to 'number->string' must be a number"
+    );
+    if args.len() == 1 {
+        let s = interp.alloc.get_val(args[0]).to_string(&interp.alloc);
+        ret!(interp, Value::String(s))
     }
 
-    let i1 = match interp.alloc.get_val(args[0]) {
-        Value::Integer(i) => i,
-        _ => {
-            interp.error = Some(Error {
-                message: "the first argument to '-' must be an integer".to_string(),
-            });
-            return;
-        }
+    let notation = match interp.alloc.get_val(args[1]) {
+        Value::Symbol(name) => Notation::from_name(name),
+        _ => None,
     };
-
-    let i2 = match interp.alloc.get_val(args[1]) {
-        Value::Integer(i) => i,
-        _ => {
-            interp.error = Some(Error {
-                message: "the second argument to '-' must be an integer".to_string(),
-            });
-            return;
-        }
+    let notation = match notation {
+        Some(notation) => notation,
+        None => bail!(
+            interp,
+            "the second argument to 'number->string' must be fixed or scientific"
+        ),
     };
-
-    interp
-        .results
-        .push(Value::Integer(i1 - i2).gc(&mut interp.alloc));
+    let digits = match args.get(2) {
+        Some(arg) => expect_int!(
+            interp,
+            *arg,
+            "the third argument to 'number->string' must be an integer"
+        ),
+        None => 6,
+    };
+    if !(0..=MAX_NOTATION_DIGITS).contains(&digits) {
+        bail!(
+            interp,
+            "the third argument to 'number->string' must be between 0 and {}",
+            MAX_NOTATION_DIGITS
+        );
+    }
+    let s = format_float_in(num.to_f64(), notation, digits as usize);
+    ret!(interp, Value::String(s))
 }
 
 fn cons(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
-    if args.len() != 2 {
-        interp.error = Some(Error {
-            message: "cons takes 2 arguments".to_string(),
-        });
-        return;
-    }
+    ret!(interp, Value::Cons(args[0], args[1]))
+}
 
-    interp
-        .results
-        .push(Value::Cons(args[0], args[1]).gc(&mut interp.alloc))
+fn car(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    match interp.alloc.get_val(args[0]) {
+        Value::Cons(hd, _) => interp.results.push(*hd),
+        _ => bail!(interp, "the first argument to 'car' must be a pair"),
+    }
 }
 
-fn call_with_cc(interp: &mut Interpreter, env: Ptr<Environment>, args: &[Ptr<Value>]) {
-    if args.len() != 1 {
-        interp.error = Some(Error {
-            message: "call/cc takes 1 argument".to_string(),
-        });
-        return;
+fn cdr(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    match interp.alloc.get_val(args[0]) {
+        Value::Cons(_, tl) => interp.results.push(*tl),
+        _ => bail!(interp, "the first argument to 'cdr' must be a pair"),
     }
+}
+
+fn list(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let res = Value::from_vec(args, &mut interp.alloc);
+    interp.results.push(res);
+}
+
+fn is_null(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let res = matches!(interp.alloc.get_val(args[0]), Value::Nil);
+    ret!(interp, Value::Bool(res))
+}
+
+fn is_pair(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let res = matches!(interp.alloc.get_val(args[0]), Value::Cons(_, _));
+    ret!(interp, Value::Bool(res))
+}
+
+fn is_symbol(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let res = matches!(interp.alloc.get_val(args[0]), Value::Symbol(_));
+    ret!(interp, Value::Bool(res))
+}
+
+fn is_integer(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let res = matches!(interp.alloc.get_val(args[0]), Value::Integer(_));
+    ret!(interp, Value::Bool(res))
+}
+
+fn is_procedure_native(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let res = is_procedure(interp, args[0]);
+    ret!(interp, Value::Bool(res))
+}
+
+fn not(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let res = !is_truthy(interp, args[0]);
+    ret!(interp, Value::Bool(res))
+}
 
+fn call_with_cc(interp: &mut Interpreter, env: Ptr<Environment>, args: &[Ptr<Value>]) {
     let next_steps = clone_steps(&interp.next_steps);
     let cont_val = Continuation {
         // TODO: can we eliminate the amount of copied data for a continuation
         next_steps,
         results: interp.results.clone(),
         saved_results: interp.saved_results.clone(),
+        frames: interp.frames.clone().unwrap_or_default(),
+        one_shot: interp.options.one_shot_continuations,
+        invoked: false,
+        depth: interp.call_depth,
     };
     let cont = Value::Continuation(cont_val).gc(&mut interp.alloc);
-    let func_call = vec![args[0], cont];
-    interp.handle_func_call(func_call, env)
+    interp.apply(args[0], &[cont], env)
 }
 
 fn last(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
-    if args.is_empty() {
-        interp.error = Some(Error {
-            message: "last requires at least 1 argument".to_string(),
-        });
-        return;
-    }
-
     interp.results.push(args[args.len() - 1]);
 }
 
 fn gc_profile(interp: &mut Interpreter, _env: Ptr<Environment>, _args: &[Ptr<Value>]) {
     let info = interp.alloc.profile();
-    println!(
-        "values: size: {}, allocated: {}",
+    interp.print(format!(
+        "values: size: {}, allocated: {}, capacity: {}",
         info.values_heap_size,
-        info.values_heap_size - info.values_heap_free
-    );
-    println!(
-        "environments: size: {}, allocated: {}",
+        info.values_heap_size - info.values_heap_free,
+        info.values_heap_capacity
+    ));
+    interp.print(format!(
+        "environments: size: {}, allocated: {}, capacity: {}",
         info.environments_heap_size,
-        info.environments_heap_size - info.environments_heap_free
-    );
-    interp.results.push(Value::Nil.gc(&mut interp.alloc));
+        info.environments_heap_size - info.environments_heap_free,
+        info.environments_heap_capacity
+    ));
+    ret!(interp, Value::Nil)
 }
 
 fn gc_run(interp: &mut Interpreter, env: Ptr<Environment>, _args: &[Ptr<Value>]) {
-    interp.alloc.gc(env);
-    interp.results.push(Value::Nil.gc(&mut interp.alloc));
+    interp.collect_garbage(env, GcCause::Explicit);
+    ret!(interp, Value::Nil)
 }
 
-pub(super) fn build(alloc: &mut Allocator) -> Ptr<Environment> {
-    let mut bindings = HashMap::new();
+fn make_guardian(interp: &mut Interpreter, _env: Ptr<Environment>, _args: &[Ptr<Value>]) {
+    let guardian = interp.alloc.new_guardian();
+    interp.results.push(guardian);
+}
 
-    bindings.insert("+".to_string(), Value::NativeFunction(plus).gc(alloc));
-    bindings.insert("*".to_string(), Value::NativeFunction(times).gc(alloc));
-    bindings.insert("i=".to_string(), Value::NativeFunction(ieq).gc(alloc));
-    bindings.insert("-".to_string(), Value::NativeFunction(minus).gc(alloc));
-    bindings.insert("cons".to_string(), Value::NativeFunction(cons).gc(alloc));
-    bindings.insert(
-        "call/cc".to_string(),
-        Value::NativeFunction(call_with_cc).gc(alloc),
-    );
-    bindings.insert("last".to_string(), Value::NativeFunction(last).gc(alloc));
-    bindings.insert(
-        "gc-profile".to_string(),
-        Value::NativeFunction(gc_profile).gc(alloc),
-    );
-    bindings.insert(
-        "gc-run".to_string(),
-        Value::NativeFunction(gc_run).gc(alloc),
-    );
-    bindings.insert("nil".to_string(), Value::Nil.gc(alloc));
+// guardian_mut returns the guardian at ptr, or sets an error naming func
+fn guardian_mut<'a>(
+    interp: &'a mut Interpreter,
+    ptr: Ptr<Value>,
+    func: &str,
+) -> Option<&'a mut Guardian> {
+    if let Value::Guardian(_) = interp.alloc.get_val(ptr) {
+        match interp.alloc.get_val_mut(ptr) {
+            Value::Guardian(guardian) => return Some(guardian),
+            _ => unreachable!(),
+        }
+    }
+    interp.error = Some(Error {
+        message: format!("the first argument to '{}' must be a guardian", func),
+        location: None,
+    });
+    None
+}
 
-    Environment::new_with_bindings(bindings).gc(alloc)
+fn guardian_register(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    if let Some(guardian) = guardian_mut(interp, args[0], "guardian-register!") {
+        guardian.registered.push(args[1]);
+        ret!(interp, Value::Nil)
+    }
+}
+
+// guardian_pop returns an object collected since it was registered, or #f if there is none
+fn guardian_pop(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    if let Some(guardian) = guardian_mut(interp, args[0], "guardian-pop!") {
+        match guardian.ready.pop_front() {
+            Some(obj) => interp.results.push(obj),
+            None => ret!(interp, Value::Bool(false)),
+        }
+    }
+}
+
+fn repl_prompt(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    interp.repl.prompt = match interp.alloc.get_val(args[0]) {
+        // symbols cannot contain whitespace, so separate the prompt from the input
+        Value::Symbol(s) => format!("{} ", s),
+        Value::String(s) => s.clone(),
+        val => val.to_string(&interp.alloc),
+    };
+    ret!(interp, Value::Nil)
+}
+
+fn repl_banner(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    interp.repl.banner = match interp.alloc.get_val(args[0]) {
+        Value::Bool(false) => None,
+        Value::String(s) => Some(s.clone()),
+        val => Some(val.to_string(&interp.alloc)),
+    };
+    ret!(interp, Value::Nil)
+}
+
+// exit_status converts code to a process exit status, which must fit in a byte
+// larger codes would be truncated by the operating system, so (exit 256) would report success
+pub(super) fn exit_status(code: i64) -> Result<i32, String> {
+    u8::try_from(code)
+        .map(i32::from)
+        .map_err(|_| format!("exit status must be between 0 and 255, got {}", code))
+}
+
+fn exit(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let code = match args {
+        [] => 0,
+        [arg, ..] => match interp.alloc.get_val(*arg) {
+            Value::Integer(i) => match exit_status(*i) {
+                Ok(code) => code,
+                Err(message) => bail!(interp, "{}", message),
+            },
+            Value::Bool(true) => 0,
+            Value::Bool(false) => 1,
+            _ => bail!(
+                interp,
+                "the argument to 'exit' must be an integer or boolean"
+            ),
+        },
+    };
+
+    // abort the current evaluation, the caller terminates with exit_code
+    interp.exit_code = Some(code);
+    bail!(interp, "exit with status {}", code)
+}
+
+fn is_eq(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    ret!(interp, Value::Bool(eq(&interp.alloc, args[0], args[1])))
+}
+
+fn is_equal(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    ret!(interp, Value::Bool(equal(&interp.alloc, args[0], args[1])))
+}
+
+// set_items returns the members of the set at ptr, or sets an error naming func
+fn set_items(interp: &mut Interpreter, ptr: Ptr<Value>, func: &str) -> Option<Vec<Ptr<Value>>> {
+    match interp.alloc.get_val(ptr) {
+        Value::Set(set) => Some(set.items.clone()),
+        _ => {
+            interp.error = Some(Error {
+                message: format!("the first argument to '{}' must be a set", func),
+                location: None,
+            });
+            None
+        }
+    }
+}
+
+// build_set allocates a set holding the distinct values of items
+fn build_set(interp: &mut Interpreter, items: &[Ptr<Value>]) -> Ptr<Value> {
+    let mut set = Set::new();
+    for &item in items {
+        let hash = hash(&interp.alloc, item);
+        if !set.contains(&interp.alloc, hash, item) {
+            set.insert(hash, item);
+        }
+    }
+    Value::Set(set).gc(&mut interp.alloc)
+}
+
+fn make_set(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let set = build_set(interp, args);
+    interp.results.push(set);
+}
+
+fn set_add(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let item = args[1];
+    let hash = hash(&interp.alloc, item);
+    let present = match interp.alloc.get_val(args[0]) {
+        Value::Set(set) => set.contains(&interp.alloc, hash, item),
+        _ => bail!(interp, "the first argument to 'set-add!' must be a set"),
+    };
+
+    if !present {
+        if let Value::Set(set) = interp.alloc.get_val_mut(args[0]) {
+            set.insert(hash, item);
+        }
+    }
+    ret!(interp, Value::Nil)
+}
+
+fn set_member(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let hash = hash(&interp.alloc, args[1]);
+    let res = match interp.alloc.get_val(args[0]) {
+        Value::Set(set) => set.contains(&interp.alloc, hash, args[1]),
+        _ => bail!(interp, "the first argument to 'set-member?' must be a set"),
+    };
+    ret!(interp, Value::Bool(res))
+}
+
+fn set_union(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let mut items = Vec::new();
+    for &arg in args {
+        match set_items(interp, arg, "set-union") {
+            Some(set) => items.extend(set),
+            None => return,
+        }
+    }
+
+    let set = build_set(interp, &items);
+    interp.results.push(set);
+}
+
+fn set_difference(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let mut items = match set_items(interp, args[0], "set-difference") {
+        Some(items) => items,
+        None => return,
+    };
+    for &arg in &args[1..] {
+        let alloc = &interp.alloc;
+        match alloc.get_val(arg) {
+            Value::Set(set) => items.retain(|&item| !set.contains(alloc, hash(alloc, item), item)),
+            _ => bail!(interp, "all arguments to 'set-difference' must be sets"),
+        }
+    }
+
+    let set = build_set(interp, &items);
+    interp.results.push(set);
+}
+
+fn set_to_list(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    if let Some(items) = set_items(interp, args[0], "set->list") {
+        let list = Value::from_vec(&items, &mut interp.alloc);
+        interp.results.push(list);
+    }
+}
+
+fn copy(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let copy = deep_copy(&mut interp.alloc, args[0]);
+    interp.results.push(copy);
+}
+
+fn procedure_arity(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let arity = match interp.alloc.get_val(args[0]) {
+        Value::Function(f) => Arity::exactly(f.args.len()),
+        Value::NativeFunction(native) => native.arity,
+        Value::Continuation(_) => Arity::exactly(1),
+        _ => bail!(
+            interp,
+            "the argument to 'procedure-arity' must be a procedure"
+        ),
+    };
+
+    let min = Value::Integer(arity.min as i64).gc(&mut interp.alloc);
+    let max = match arity.max {
+        Some(max) => Value::Integer(max as i64),
+        None => Value::Bool(false),
+    }
+    .gc(&mut interp.alloc);
+    ret!(interp, Value::Cons(min, max))
+}
+
+fn continuation_one_shot(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let one_shot = match interp.alloc.get_val(args[0]) {
+        Value::Continuation(c) => c.one_shot,
+        _ => bail!(
+            interp,
+            "the argument to 'continuation-one-shot?' must be a continuation"
+        ),
+    };
+    ret!(interp, Value::Bool(one_shot))
+}
+
+fn dump_continuation(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let description = match interp.alloc.get_val(args[0]) {
+        Value::Continuation(c) => c.describe(&interp.alloc),
+        _ => bail!(
+            interp,
+            "the argument to 'dump-continuation' must be a continuation"
+        ),
+    };
+    interp.print(description);
+    ret!(interp, Value::Nil)
+}
+
+fn set_object_property(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    interp.alloc.set_property(args[0], args[1], args[2]);
+    ret!(interp, Value::Nil)
+}
+
+fn object_property(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let val = match interp.alloc.get_property(args[0], args[1]) {
+        Some(val) => val,
+        None => Value::Bool(false).gc(&mut interp.alloc),
+    };
+    interp.results.push(val);
+}
+
+fn map(interp: &mut Interpreter, env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let items = match list_items(&interp.alloc, args[1]) {
+        Some(items) => items,
+        None => bail!(interp, "the second argument to 'map' must be a list"),
+    };
+
+    let mut mapped = Vec::with_capacity(items.len());
+    for item in items {
+        match interp.call(args[0], &[item], env) {
+            Ok(val) => mapped.push(val),
+            Err(err) => {
+                interp.error = Some(err);
+                return;
+            }
+        }
+    }
+
+    let res = Value::from_vec(&mapped, &mut interp.alloc);
+    interp.results.push(res);
+}
+
+// is_truthy reports whether val counts as true in a condition
+fn is_truthy(interp: &Interpreter, val: Ptr<Value>) -> bool {
+    !matches!(interp.alloc.get_val(val), Value::Bool(false))
+}
+
+// is_native reports whether ptr is the native function func
+fn is_native(interp: &Interpreter, ptr: Ptr<Value>, func: NativeFn) -> bool {
+    match interp.alloc.get_val(ptr) {
+        Value::NativeFunction(native) => std::ptr::fn_addr_eq(native.func, func),
+        _ => false,
+    }
+}
+
+fn is_procedure(interp: &Interpreter, ptr: Ptr<Value>) -> bool {
+    matches!(
+        interp.alloc.get_val(ptr),
+        Value::Function(_) | Value::NativeFunction(_) | Value::Continuation(_)
+    )
+}
+
+fn make_comparator(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    if !args.iter().all(|&arg| is_procedure(interp, arg)) {
+        bail!(
+            interp,
+            "the arguments to 'make-comparator' must be procedures"
+        );
+    }
+    ret!(
+        interp,
+        Value::Comparator(Comparator {
+            equality: args[0],
+            hash: args.get(1).copied(),
+        })
+    )
+}
+
+fn is_comparator(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let res = matches!(interp.alloc.get_val(args[0]), Value::Comparator(_));
+    ret!(interp, Value::Bool(res))
+}
+
+fn make_hash_table(interp: &mut Interpreter, env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let comparator = match args.first().map(|&arg| interp.alloc.get_val(arg)) {
+        Some(Value::Comparator(_)) if args.len() == 1 => args[0],
+        Some(_) => {
+            make_comparator(interp, env, args);
+            match interp.results.pop() {
+                Some(comparator) => comparator,
+                None => return,
+            }
+        }
+        None => {
+            // keys are compared with equal? by default
+            let equality = Value::NativeFunction(NativeFunction {
+                name: "equal?".to_string(),
+                func: is_equal,
+                arity: Arity::exactly(2),
+            })
+            .gc(&mut interp.alloc);
+            Value::Comparator(Comparator {
+                equality,
+                hash: None,
+            })
+            .gc(&mut interp.alloc)
+        }
+    };
+    ret!(interp, Value::HashTable(HashTable::new(comparator)))
+}
+
+fn is_hash_table(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let res = matches!(interp.alloc.get_val(args[0]), Value::HashTable(_));
+    ret!(interp, Value::Bool(res))
+}
+
+// find_key hashes key with the comparator of table, returning the hash and the equal key
+// stored in the table, if any
+// custom comparator procedures may run arbitrary code, including mutating the table
+fn find_key(
+    interp: &mut Interpreter,
+    env: Ptr<Environment>,
+    table: Ptr<Value>,
+    key: Ptr<Value>,
+    func: &str,
+) -> Result<(u64, Option<Ptr<Value>>), Error> {
+    let comparator = match interp.alloc.get_val(table) {
+        Value::HashTable(table) => table.comparator,
+        _ => {
+            return Err(Error {
+                message: format!("the first argument to '{}' must be a hash table", func),
+                location: None,
+            })
+        }
+    };
+    let (equality, hash_func) = match interp.alloc.get_val(comparator) {
+        Value::Comparator(c) => (c.equality, c.hash),
+        _ => unreachable!(),
+    };
+
+    let builtin_eq = is_native(interp, equality, is_eq);
+    let builtin_equal = is_native(interp, equality, is_equal);
+    let hash = match hash_func {
+        _ if builtin_eq => eq_hash(&interp.alloc, key),
+        _ if builtin_equal => hash(&interp.alloc, key),
+        Some(hash_func) => {
+            let res = interp.call(hash_func, &[key], env)?;
+            match interp.alloc.get_val(res) {
+                Value::Integer(i) => *i as u64,
+                _ => {
+                    return Err(Error {
+                        message: "hash functions must return an integer".to_string(),
+                        location: None,
+                    })
+                }
+            }
+        }
+        None => 0,
+    };
+
+    let candidates = match interp.alloc.get_val(table) {
+        Value::HashTable(table) => table.buckets.get(&hash).cloned().unwrap_or_default(),
+        _ => unreachable!(),
+    };
+    for (candidate, _) in candidates {
+        let found = if builtin_eq {
+            eq(&interp.alloc, candidate, key)
+        } else if builtin_equal {
+            equal(&interp.alloc, candidate, key)
+        } else {
+            let res = interp.call(equality, &[candidate, key], env)?;
+            is_truthy(interp, res)
+        };
+        if found {
+            return Ok((hash, Some(candidate)));
+        }
+    }
+    Ok((hash, None))
+}
+
+// table_mut returns the hash table at ptr, which find_key has checked
+fn table_mut(interp: &mut Interpreter, ptr: Ptr<Value>) -> &mut HashTable {
+    match interp.alloc.get_val_mut(ptr) {
+        Value::HashTable(table) => table,
+        _ => unreachable!(),
+    }
+}
+
+fn hash_table_set(interp: &mut Interpreter, env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let (hash, found) = match find_key(interp, env, args[0], args[1], "hash-table-set!") {
+        Ok(res) => res,
+        Err(err) => {
+            interp.error = Some(err);
+            return;
+        }
+    };
+
+    let table = table_mut(interp, args[0]);
+    let bucket = table.buckets.entry(hash).or_default();
+    match found.and_then(|key| bucket.iter_mut().find(|(k, _)| *k == key)) {
+        Some(entry) => entry.1 = args[2],
+        None => {
+            bucket.push((args[1], args[2]));
+            table.len += 1;
+        }
+    }
+    ret!(interp, Value::Nil)
+}
+
+fn hash_table_ref(interp: &mut Interpreter, env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let (hash, found) = match find_key(interp, env, args[0], args[1], "hash-table-ref") {
+        Ok(res) => res,
+        Err(err) => {
+            interp.error = Some(err);
+            return;
+        }
+    };
+
+    let val = found.and_then(|key| {
+        table_mut(interp, args[0])
+            .buckets
+            .get(&hash)?
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, val)| *val)
+    });
+    match val.or_else(|| args.get(2).copied()) {
+        Some(val) => interp.results.push(val),
+        None => bail!(
+            interp,
+            "hash-table-ref: no value for key {}",
+            interp.alloc.get_val(args[1]).to_string(&interp.alloc)
+        ),
+    }
+}
+
+fn hash_table_contains(interp: &mut Interpreter, env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    match find_key(interp, env, args[0], args[1], "hash-table-contains?") {
+        Ok((_, found)) => ret!(interp, Value::Bool(found.is_some())),
+        Err(err) => interp.error = Some(err),
+    }
+}
+
+fn hash_table_delete(interp: &mut Interpreter, env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let (hash, found) = match find_key(interp, env, args[0], args[1], "hash-table-delete!") {
+        Ok(res) => res,
+        Err(err) => {
+            interp.error = Some(err);
+            return;
+        }
+    };
+
+    if let Some(key) = found {
+        let table = table_mut(interp, args[0]);
+        if let Some(bucket) = table.buckets.get_mut(&hash) {
+            let before = bucket.len();
+            bucket.retain(|(k, _)| *k != key);
+            table.len -= before - bucket.len();
+        }
+    }
+    ret!(interp, Value::Nil)
+}
+
+fn hash_table_count(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let len = match interp.alloc.get_val(args[0]) {
+        Value::HashTable(table) => table.len,
+        _ => bail!(
+            interp,
+            "the first argument to 'hash-table-count' must be a hash table"
+        ),
+    };
+    ret!(interp, Value::Integer(len as i64))
+}
+
+fn hash_table_keys(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let keys = match interp.alloc.get_val(args[0]) {
+        Value::HashTable(table) => table.entries().map(|(key, _)| *key).collect::<Vec<_>>(),
+        _ => bail!(
+            interp,
+            "the first argument to 'hash-table-keys' must be a hash table"
+        ),
+    };
+    let res = Value::from_vec(&keys, &mut interp.alloc);
+    interp.results.push(res);
+}
+
+// table_entries returns the entries of the hash table at ptr, or sets an error naming func
+fn table_entries(
+    interp: &mut Interpreter,
+    ptr: Ptr<Value>,
+    func: &str,
+) -> Option<Vec<(Ptr<Value>, Ptr<Value>)>> {
+    match interp.alloc.get_val(ptr) {
+        Value::HashTable(table) => Some(table.entries().copied().collect()),
+        _ => {
+            interp.error = Some(Error {
+                message: format!("the first argument to '{}' must be a hash table", func),
+                location: None,
+            });
+            None
+        }
+    }
+}
+
+fn hash_table_to_alist(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    if let Some(entries) = table_entries(interp, args[0], "hash-table->alist") {
+        let pairs = entries
+            .into_iter()
+            .map(|(key, val)| Value::Cons(key, val).gc(&mut interp.alloc))
+            .collect::<Vec<_>>();
+        let res = Value::from_vec(&pairs, &mut interp.alloc);
+        interp.results.push(res);
+    }
+}
+
+// alist_to_hash_table makes a hash table from an association list, taking the rest of its
+// arguments like make-hash-table
+// when a key occurs more than once, its first association is kept
+fn alist_to_hash_table(interp: &mut Interpreter, env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let items = match list_items(&interp.alloc, args[0]) {
+        Some(items) => items,
+        None => bail!(
+            interp,
+            "the first argument to 'alist->hash-table' must be a list"
+        ),
+    };
+    let mut entries = Vec::new();
+    for item in items {
+        match interp.alloc.get_val(item) {
+            Value::Cons(key, val) => entries.push((*key, *val)),
+            val => bail!(
+                interp,
+                "alist->hash-table: expected a pair, got {}",
+                val.to_string(&interp.alloc)
+            ),
+        }
+    }
+
+    make_hash_table(interp, env, &args[1..]);
+    let table = match interp.results.last() {
+        Some(&table) if interp.error.is_none() => table,
+        _ => return,
+    };
+    for (key, val) in entries {
+        let hash = match find_key(interp, env, table, key, "alist->hash-table") {
+            Ok((_, Some(_))) => continue,
+            Ok((hash, None)) => hash,
+            Err(err) => {
+                interp.error = Some(err);
+                return;
+            }
+        };
+        let table = table_mut(interp, table);
+        table.buckets.entry(hash).or_default().push((key, val));
+        table.len += 1;
+    }
+}
+
+// hash_table_walk calls a procedure with the key and value of each entry of a hash table
+// entries added or removed by the procedure may or may not be visited
+fn hash_table_walk(interp: &mut Interpreter, env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    if let Some(entries) = table_entries(interp, args[0], "hash-table-walk") {
+        for (key, val) in entries {
+            if let Err(err) = interp.call(args[1], &[key, val], env) {
+                interp.error = Some(err);
+                return;
+            }
+        }
+        ret!(interp, Value::Nil)
+    }
+}
+
+// Missing gives the value a hash table update starts from when the key has none
+enum Missing {
+    Error,
+    Thunk(Ptr<Value>),
+    Value(Ptr<Value>),
+}
+
+// update_entry sets the value of a key in a table to the result of calling an updater
+// procedure with its current value, which is given by missing if the table has none
+fn update_entry(
+    interp: &mut Interpreter,
+    env: Ptr<Environment>,
+    args: &[Ptr<Value>],
+    missing: Missing,
+    func: &str,
+) {
+    let (table, key, updater) = (args[0], args[1], args[2]);
+    let (hash, found) = match find_key(interp, env, table, key, func) {
+        Ok(res) => res,
+        Err(err) => {
+            interp.error = Some(err);
+            return;
+        }
+    };
+    let current = found.and_then(|found| {
+        table_mut(interp, table)
+            .buckets
+            .get(&hash)?
+            .iter()
+            .find(|(k, _)| *k == found)
+            .map(|(_, val)| *val)
+    });
+    let current = match (current, missing) {
+        (Some(val), _) | (None, Missing::Value(val)) => Ok(val),
+        (None, Missing::Thunk(thunk)) => interp.call(thunk, &[], env),
+        (None, Missing::Error) => bail!(
+            interp,
+            "{}: no value for key {}",
+            func,
+            interp.alloc.get_val(key).to_string(&interp.alloc)
+        ),
+    };
+    match current.and_then(|val| interp.call(updater, &[val], env)) {
+        // the updater may have changed the table, so the key is looked up again to store it
+        Ok(val) => hash_table_set(interp, env, &[table, key, val]),
+        Err(err) => interp.error = Some(err),
+    }
+}
+
+fn hash_table_update(interp: &mut Interpreter, env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let missing = match args.get(3) {
+        Some(&thunk) => Missing::Thunk(thunk),
+        None => Missing::Error,
+    };
+    update_entry(interp, env, args, missing, "hash-table-update!")
+}
+
+fn hash_table_update_default(interp: &mut Interpreter, env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let missing = Missing::Value(args[3]);
+    update_entry(interp, env, args, missing, "hash-table-update!/default")
+}
+
+fn vector(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    ret!(interp, Value::Vector(args.to_vec()))
+}
+
+fn list_to_vector(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    match list_items(&interp.alloc, args[0]) {
+        Some(items) => ret!(interp, Value::Vector(items)),
+        None => bail!(
+            interp,
+            "the first argument to 'list->vector' must be a list"
+        ),
+    }
+}
+
+fn vector_to_list(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let items = match interp.alloc.get_val(args[0]) {
+        Value::Vector(items) => items.clone(),
+        _ => bail!(
+            interp,
+            "the first argument to 'vector->list' must be a vector"
+        ),
+    };
+    let res = Value::from_vec(&items, &mut interp.alloc);
+    interp.results.push(res);
+}
+
+fn vector_length(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let len = match interp.alloc.get_val(args[0]) {
+        Value::Vector(items) => items.len(),
+        _ => bail!(
+            interp,
+            "the first argument to 'vector-length' must be a vector"
+        ),
+    };
+    ret!(interp, Value::Integer(len as i64))
+}
+
+// vector_index returns the index args[1] into the vector args[0], or sets an error naming func
+fn vector_index(interp: &mut Interpreter, args: &[Ptr<Value>], func: &str) -> Option<usize> {
+    let len = match interp.alloc.get_val(args[0]) {
+        Value::Vector(items) => items.len(),
+        _ => {
+            interp.error = Some(Error {
+                message: format!("the first argument to '{}' must be a vector", func),
+                location: None,
+            });
+            return None;
+        }
+    };
+    match interp.alloc.get_val(args[1]) {
+        Value::Integer(i) if *i >= 0 && (*i as usize) < len => Some(*i as usize),
+        _ => {
+            interp.error = Some(Error {
+                message: format!(
+                    "{}: index {} out of range for vector of length {}",
+                    func,
+                    interp.alloc.get_val(args[1]).to_string(&interp.alloc),
+                    len
+                ),
+                location: None,
+            });
+            None
+        }
+    }
+}
+
+fn vector_ref(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    if let Some(i) = vector_index(interp, args, "vector-ref") {
+        if let Value::Vector(items) = interp.alloc.get_val(args[0]) {
+            interp.results.push(items[i]);
+        }
+    }
+}
+
+fn vector_set(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    if let Some(i) = vector_index(interp, args, "vector-set!") {
+        if let Value::Vector(items) = interp.alloc.get_val_mut(args[0]) {
+            items[i] = args[2];
+        }
+        ret!(interp, Value::Nil)
+    }
+}
+
+// merge_sort stably sorts items with the Scheme predicate less
+// elements are only reordered when less holds strictly, so equal elements keep their order,
+// and an inconsistent predicate produces some permutation of items rather than failing
+fn merge_sort(
+    interp: &mut Interpreter,
+    env: Ptr<Environment>,
+    less: Ptr<Value>,
+    mut items: Vec<Ptr<Value>>,
+) -> Result<Vec<Ptr<Value>>, Error> {
+    let mut width = 1;
+    while width < items.len() {
+        let mut merged = Vec::with_capacity(items.len());
+        for chunk in items.chunks(2 * width) {
+            let (left, right) = chunk.split_at(width.min(chunk.len()));
+            let (mut i, mut j) = (0, 0);
+            while i < left.len() && j < right.len() {
+                let res = interp.call(less, &[right[j], left[i]], env)?;
+                if is_truthy(interp, res) {
+                    merged.push(right[j]);
+                    j += 1;
+                } else {
+                    merged.push(left[i]);
+                    i += 1;
+                }
+            }
+            merged.extend_from_slice(&left[i..]);
+            merged.extend_from_slice(&right[j..]);
+        }
+        items = merged;
+        width *= 2;
+    }
+    Ok(items)
+}
+
+// sort returns a sorted copy of a list or vector
+// errors raised by the predicate, including escaping continuations, abort the sort
+fn sort(interp: &mut Interpreter, env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let (items, is_vector) = match interp.alloc.get_val(args[0]) {
+        Value::Vector(items) => (items.clone(), true),
+        _ => match list_items(&interp.alloc, args[0]) {
+            Some(items) => (items, false),
+            None => bail!(
+                interp,
+                "the first argument to 'sort' must be a list or vector"
+            ),
+        },
+    };
+
+    let sorted = match merge_sort(interp, env, args[1], items) {
+        Ok(sorted) => sorted,
+        Err(err) => {
+            interp.error = Some(err);
+            return;
+        }
+    };
+    if is_vector {
+        ret!(interp, Value::Vector(sorted))
+    }
+    let res = Value::from_vec(&sorted, &mut interp.alloc);
+    interp.results.push(res);
+}
+
+// vector_binary_search finds the index of an element of a sorted vector for which cmp,
+// called with the element and key, returns 0; cmp returns a negative integer if the element
+// is before key and a positive one if it's after
+fn vector_binary_search(interp: &mut Interpreter, env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let items = match interp.alloc.get_val(args[0]) {
+        Value::Vector(items) => items.clone(),
+        _ => bail!(
+            interp,
+            "the first argument to 'vector-binary-search' must be a vector"
+        ),
+    };
+
+    let (mut lo, mut hi) = (0, items.len());
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        let res = match interp.call(args[2], &[items[mid], args[1]], env) {
+            Ok(res) => res,
+            Err(err) => {
+                interp.error = Some(err);
+                return;
+            }
+        };
+        let order = expect_int!(
+            interp,
+            res,
+            "the comparison procedure given to 'vector-binary-search' must return an integer"
+        );
+        match order {
+            0 => ret!(interp, Value::Integer(mid as i64)),
+            _ if order < 0 => lo = mid + 1,
+            _ => hi = mid,
+        }
+    }
+    ret!(interp, Value::Bool(false))
+}
+
+fn make_bitvector(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let len = expect_int!(
+        interp,
+        args[0],
+        "the first argument to 'make-bitvector' must be an integer"
+    );
+    if len < 0 {
+        bail!(
+            interp,
+            "make-bitvector: length must not be negative, got {}",
+            len
+        );
+    }
+    let fill = args.get(1).is_some_and(|&fill| is_truthy(interp, fill));
+    match Bitvector::new(len as usize, fill) {
+        Some(bv) => ret!(interp, Value::Bitvector(bv)),
+        None => bail!(interp, "make-bitvector: length {} is too large", len),
+    }
+}
+
+// bitvector_index returns the index args[1] into the bitvector args[0],
+// or sets an error naming func
+fn bitvector_index(interp: &mut Interpreter, args: &[Ptr<Value>], func: &str) -> Option<usize> {
+    let len = match interp.alloc.get_val(args[0]) {
+        Value::Bitvector(bv) => bv.len(),
+        _ => {
+            interp.error = Some(Error {
+                message: format!("the first argument to '{}' must be a bitvector", func),
+                location: None,
+            });
+            return None;
+        }
+    };
+    match interp.alloc.get_val(args[1]) {
+        Value::Integer(i) if *i >= 0 && (*i as usize) < len => Some(*i as usize),
+        _ => {
+            interp.error = Some(Error {
+                message: format!(
+                    "{}: index {} out of range for bitvector of length {}",
+                    func,
+                    interp.alloc.get_val(args[1]).to_string(&interp.alloc),
+                    len
+                ),
+                location: None,
+            });
+            None
+        }
+    }
+}
+
+fn bitvector_ref(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    if let Some(i) = bitvector_index(interp, args, "bitvector-ref") {
+        if let Value::Bitvector(bv) = interp.alloc.get_val(args[0]) {
+            let bit = bv.get(i);
+            ret!(interp, Value::Bool(bit))
+        }
+    }
+}
+
+fn bitvector_set(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    if let Some(i) = bitvector_index(interp, args, "bitvector-set!") {
+        let bit = is_truthy(interp, args[2]);
+        if let Value::Bitvector(bv) = interp.alloc.get_val_mut(args[0]) {
+            bv.set(i, bit);
+        }
+        ret!(interp, Value::Nil)
+    }
+}
+
+fn bitvector_length(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let len = match interp.alloc.get_val(args[0]) {
+        Value::Bitvector(bv) => bv.len(),
+        _ => bail!(
+            interp,
+            "the first argument to 'bitvector-length' must be a bitvector"
+        ),
+    };
+    ret!(interp, Value::Integer(len as i64))
+}
+
+fn bitvector_count(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let count = match interp.alloc.get_val(args[0]) {
+        Value::Bitvector(bv) => bv.count(),
+        _ => bail!(
+            interp,
+            "the first argument to 'bitvector-count' must be a bitvector"
+        ),
+    };
+    ret!(interp, Value::Integer(count as i64))
+}
+
+// bitvector_op combines bitvectors of equal length bitwise, returning a new bitvector
+fn bitvector_op(
+    interp: &mut Interpreter,
+    args: &[Ptr<Value>],
+    func: &str,
+    op: fn(u64, u64) -> u64,
+) {
+    let mut res: Option<Bitvector> = None;
+    for &arg in args {
+        let bv = match interp.alloc.get_val(arg) {
+            Value::Bitvector(bv) => bv,
+            _ => bail!(interp, "all arguments to '{}' must be bitvectors", func),
+        };
+        res = match res {
+            None => Some(bv.clone()),
+            Some(acc) if acc.len() == bv.len() => Some(acc.zip(bv, op)),
+            Some(acc) => bail!(
+                interp,
+                "{}: bitvectors must have the same length, got {} and {}",
+                func,
+                acc.len(),
+                bv.len()
+            ),
+        };
+    }
+    ret!(interp, Value::Bitvector(res.unwrap()))
+}
+
+fn bitvector_and(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    bitvector_op(interp, args, "bitvector-and", |a, b| a & b)
+}
+
+fn bitvector_or(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    bitvector_op(interp, args, "bitvector-or", |a, b| a | b)
+}
+
+fn bitvector_xor(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    bitvector_op(interp, args, "bitvector-xor", |a, b| a ^ b)
+}
+
+fn make_queue(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    ret!(interp, Value::Queue(args.iter().copied().collect()))
+}
+
+// queue_mut returns the queue at ptr, or sets an error naming func
+fn queue_mut<'a>(
+    interp: &'a mut Interpreter,
+    ptr: Ptr<Value>,
+    func: &str,
+) -> Option<&'a mut VecDeque<Ptr<Value>>> {
+    if let Value::Queue(_) = interp.alloc.get_val(ptr) {
+        match interp.alloc.get_val_mut(ptr) {
+            Value::Queue(queue) => return Some(queue),
+            _ => unreachable!(),
+        }
+    }
+    interp.error = Some(Error {
+        message: format!("the first argument to '{}' must be a queue", func),
+        location: None,
+    });
+    None
+}
+
+fn enqueue(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    if let Some(queue) = queue_mut(interp, args[0], "enqueue!") {
+        queue.extend(&args[1..]);
+        ret!(interp, Value::Nil)
+    }
+}
+
+fn dequeue(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    if let Some(queue) = queue_mut(interp, args[0], "dequeue!") {
+        match queue.pop_front() {
+            Some(item) => interp.results.push(item),
+            None => bail!(interp, "dequeue!: queue is empty"),
+        }
+    }
+}
+
+fn queue_length(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    if let Some(queue) = queue_mut(interp, args[0], "queue-length") {
+        let len = queue.len();
+        ret!(interp, Value::Integer(len as i64))
+    }
+}
+
+fn queue_to_list(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    if let Some(queue) = queue_mut(interp, args[0], "queue->list") {
+        let items = queue.iter().copied().collect::<Vec<_>>();
+        let res = Value::from_vec(&items, &mut interp.alloc);
+        interp.results.push(res);
+    }
+}
+
+fn make_heap(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    if !is_procedure(interp, args[0]) {
+        bail!(
+            interp,
+            "the first argument to 'make-heap' must be a procedure"
+        );
+    }
+    ret!(
+        interp,
+        Value::Heap(Heap {
+            less: args[0],
+            items: Vec::new(),
+        })
+    )
+}
+
+// take_heap removes the items from the heap at ptr while they're reordered,
+// returning them with the heap's predicate, or sets an error naming func
+// the predicate runs Scheme code, so the items are taken out for the duration of the operation
+// and any changes the predicate makes to the same heap are discarded
+fn take_heap(
+    interp: &mut Interpreter,
+    ptr: Ptr<Value>,
+    func: &str,
+) -> Option<(Ptr<Value>, Vec<Ptr<Value>>)> {
+    match interp.alloc.get_val_mut(ptr) {
+        Value::Heap(heap) => Some((heap.less, std::mem::take(&mut heap.items))),
+        _ => {
+            interp.error = Some(Error {
+                message: format!("the first argument to '{}' must be a heap", func),
+                location: None,
+            });
+            None
+        }
+    }
+}
+
+fn put_heap(interp: &mut Interpreter, ptr: Ptr<Value>, items: Vec<Ptr<Value>>) {
+    if let Value::Heap(heap) = interp.alloc.get_val_mut(ptr) {
+        heap.items = items;
+    }
+}
+
+// sift_up restores the heap order after pushing onto the end of items
+fn sift_up(
+    interp: &mut Interpreter,
+    env: Ptr<Environment>,
+    less: Ptr<Value>,
+    items: &mut [Ptr<Value>],
+) -> Result<(), Error> {
+    let mut i = items.len() - 1;
+    while i > 0 {
+        let parent = (i - 1) / 2;
+        let res = interp.call(less, &[items[i], items[parent]], env)?;
+        if !is_truthy(interp, res) {
+            break;
+        }
+        items.swap(i, parent);
+        i = parent;
+    }
+    Ok(())
+}
+
+// sift_down restores the heap order after replacing the root of items
+fn sift_down(
+    interp: &mut Interpreter,
+    env: Ptr<Environment>,
+    less: Ptr<Value>,
+    items: &mut [Ptr<Value>],
+) -> Result<(), Error> {
+    let mut i = 0;
+    loop {
+        let mut smallest = i;
+        for child in [2 * i + 1, 2 * i + 2] {
+            if child < items.len() {
+                let res = interp.call(less, &[items[child], items[smallest]], env)?;
+                if is_truthy(interp, res) {
+                    smallest = child;
+                }
+            }
+        }
+        if smallest == i {
+            return Ok(());
+        }
+        items.swap(i, smallest);
+        i = smallest;
+    }
+}
+
+fn heap_push(interp: &mut Interpreter, env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let (less, mut items) = match take_heap(interp, args[0], "heap-push!") {
+        Some(heap) => heap,
+        None => return,
+    };
+    let original = items.clone();
+    items.push(args[1]);
+    if let Err(err) = sift_up(interp, env, less, &mut items) {
+        // leave the heap as it was
+        put_heap(interp, args[0], original);
+        interp.error = Some(err);
+        return;
+    }
+    put_heap(interp, args[0], items);
+    ret!(interp, Value::Nil)
+}
+
+fn heap_pop(interp: &mut Interpreter, env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let (less, mut items) = match take_heap(interp, args[0], "heap-pop!") {
+        Some(heap) => heap,
+        None => return,
+    };
+    if items.is_empty() {
+        bail!(interp, "heap-pop!: heap is empty");
+    }
+    let top = items.swap_remove(0);
+    let res = if items.is_empty() {
+        Ok(())
+    } else {
+        sift_down(interp, env, less, &mut items)
+    };
+    put_heap(interp, args[0], items);
+    match res {
+        Ok(()) => interp.results.push(top),
+        Err(err) => interp.error = Some(err),
+    }
+}
+
+fn heap_peek(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let top = match interp.alloc.get_val(args[0]) {
+        Value::Heap(heap) => heap.items.first().copied(),
+        _ => bail!(interp, "the first argument to 'heap-peek' must be a heap"),
+    };
+    match top {
+        Some(top) => interp.results.push(top),
+        None => bail!(interp, "heap-peek: heap is empty"),
+    }
+}
+
+fn heap_length(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let len = match interp.alloc.get_val(args[0]) {
+        Value::Heap(heap) => heap.items.len(),
+        _ => bail!(interp, "the first argument to 'heap-length' must be a heap"),
+    };
+    ret!(interp, Value::Integer(len as i64))
+}
+
+fn make_array(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let dims = match list_items(&interp.alloc, args[0]) {
+        Some(dims) => dims,
+        None => bail!(
+            interp,
+            "the first argument to 'make-array' must be a list of dimensions"
+        ),
+    };
+    let mut shape = Vec::with_capacity(dims.len());
+    for dim in dims {
+        match interp.alloc.get_val(dim) {
+            Value::Integer(i) if *i >= 0 => shape.push(*i as usize),
+            _ => bail!(
+                interp,
+                "make-array: dimensions must be non-negative integers, got {}",
+                interp.alloc.get_val(dim).to_string(&interp.alloc)
+            ),
+        }
+    }
+
+    let mut items = Vec::new();
+    let reserved = shape
+        .iter()
+        .try_fold(1usize, |len, &dim| len.checked_mul(dim))
+        .filter(|&len| items.try_reserve_exact(len).is_ok());
+    let len = match reserved {
+        Some(len) => len,
+        None => bail!(interp, "make-array: shape {:?} is too large", shape),
+    };
+    let init = match args.get(1) {
+        Some(&init) => init,
+        None => Value::Nil.gc(&mut interp.alloc),
+    };
+    items.resize(len, init);
+    ret!(interp, Value::Array(Array { shape, items }))
+}
+
+// array_offset returns the position of the element of the array args[0] at the indices
+// in args[1..], or sets an error naming func
+fn array_offset(interp: &mut Interpreter, args: &[Ptr<Value>], func: &str) -> Option<usize> {
+    let mut indices = Vec::with_capacity(args.len() - 1);
+    for &arg in &args[1..] {
+        match interp.alloc.get_val(arg) {
+            Value::Integer(i) => indices.push(*i),
+            _ => {
+                interp.error = Some(Error {
+                    message: format!("{}: indices must be integers", func),
+                    location: None,
+                });
+                return None;
+            }
+        }
+    }
+
+    let (offset, shape) = match interp.alloc.get_val(args[0]) {
+        Value::Array(array) => (array.offset(&indices), array.shape.clone()),
+        _ => {
+            interp.error = Some(Error {
+                message: format!("the first argument to '{}' must be an array", func),
+                location: None,
+            });
+            return None;
+        }
+    };
+    if offset.is_none() {
+        interp.error = Some(Error {
+            message: format!(
+                "{}: indices {:?} out of range for array of shape {:?}",
+                func, indices, shape
+            ),
+            location: None,
+        });
+    }
+    offset
+}
+
+fn array_ref(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    if let Some(offset) = array_offset(interp, args, "array-ref") {
+        if let Value::Array(array) = interp.alloc.get_val(args[0]) {
+            interp.results.push(array.items[offset]);
+        }
+    }
+}
+
+// array_set takes the indices followed by the new value
+fn array_set(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let (val, args) = args.split_last().unwrap();
+    if let Some(offset) = array_offset(interp, args, "array-set!") {
+        if let Value::Array(array) = interp.alloc.get_val_mut(args[0]) {
+            array.items[offset] = *val;
+        }
+        ret!(interp, Value::Nil)
+    }
+}
+
+fn array_shape(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let shape = match interp.alloc.get_val(args[0]) {
+        Value::Array(array) => array.shape.clone(),
+        _ => bail!(
+            interp,
+            "the first argument to 'array-shape' must be an array"
+        ),
+    };
+    let dims = shape
+        .into_iter()
+        .map(|dim| Value::Integer(dim as i64).gc(&mut interp.alloc))
+        .collect::<Vec<_>>();
+    let res = Value::from_vec(&dims, &mut interp.alloc);
+    interp.results.push(res);
+}
+
+// array_map replaces each element of an array with the result of applying a procedure to it
+fn array_map(interp: &mut Interpreter, env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let len = match interp.alloc.get_val(args[0]) {
+        Value::Array(array) => array.items.len(),
+        _ => bail!(
+            interp,
+            "the first argument to 'array-map!' must be an array"
+        ),
+    };
+
+    for i in 0..len {
+        // the procedure may change the array, so each element is read just before it's used
+        let item = match interp.alloc.get_val(args[0]) {
+            Value::Array(array) => array.items[i],
+            _ => unreachable!(),
+        };
+        let res = match interp.call(args[1], &[item], env) {
+            Ok(res) => res,
+            Err(err) => {
+                interp.error = Some(err);
+                return;
+            }
+        };
+        if let Value::Array(array) = interp.alloc.get_val_mut(args[0]) {
+            array.items[i] = res;
+        }
+    }
+    ret!(interp, Value::Nil)
+}
+
+// expect_s32 returns the value at ptr as an s32vector element, or sets an error naming func
+fn expect_s32(interp: &mut Interpreter, ptr: Ptr<Value>, func: &str) -> Option<i32> {
+    match interp.alloc.get_val(ptr) {
+        Value::Integer(i) if i32::try_from(*i).is_ok() => Some(*i as i32),
+        val => {
+            interp.error = Some(Error {
+                message: format!(
+                    "{}: elements must be 32-bit integers, got {}",
+                    func,
+                    val.to_string(&interp.alloc)
+                ),
+                location: None,
+            });
+            None
+        }
+    }
+}
+
+fn make_s32vector(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let len = expect_int!(
+        interp,
+        args[0],
+        "the first argument to 'make-s32vector' must be an integer"
+    );
+    if len < 0 {
+        bail!(
+            interp,
+            "make-s32vector: length must not be negative, got {}",
+            len
+        );
+    }
+    let fill = match args.get(1) {
+        Some(&fill) => match expect_s32(interp, fill, "make-s32vector") {
+            Some(fill) => fill,
+            None => return,
+        },
+        None => 0,
+    };
+    ret!(interp, Value::S32Vector(vec![fill; len as usize]))
+}
+
+fn s32vector(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let mut items = Vec::with_capacity(args.len());
+    for &arg in args {
+        match expect_s32(interp, arg, "s32vector") {
+            Some(item) => items.push(item),
+            None => return,
+        }
+    }
+    ret!(interp, Value::S32Vector(items))
+}
+
+fn list_to_s32vector(interp: &mut Interpreter, env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    match list_items(&interp.alloc, args[0]) {
+        Some(items) => s32vector(interp, env, &items),
+        None => bail!(
+            interp,
+            "the first argument to 'list->s32vector' must be a list"
+        ),
+    }
+}
+
+fn s32vector_to_list(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let items = match interp.alloc.get_val(args[0]) {
+        Value::S32Vector(items) => items.clone(),
+        _ => bail!(
+            interp,
+            "the first argument to 's32vector->list' must be an s32vector"
+        ),
+    };
+    let items = items
+        .into_iter()
+        .map(|i| Value::Integer(i as i64).gc(&mut interp.alloc))
+        .collect::<Vec<_>>();
+    let res = Value::from_vec(&items, &mut interp.alloc);
+    interp.results.push(res);
+}
+
+fn s32vector_length(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let len = match interp.alloc.get_val(args[0]) {
+        Value::S32Vector(items) => items.len(),
+        _ => bail!(
+            interp,
+            "the first argument to 's32vector-length' must be an s32vector"
+        ),
+    };
+    ret!(interp, Value::Integer(len as i64))
+}
+
+// s32vector_index returns the index args[1] into the s32vector args[0],
+// or sets an error naming func
+fn s32vector_index(interp: &mut Interpreter, args: &[Ptr<Value>], func: &str) -> Option<usize> {
+    let len = match interp.alloc.get_val(args[0]) {
+        Value::S32Vector(items) => items.len(),
+        _ => {
+            interp.error = Some(Error {
+                message: format!("the first argument to '{}' must be an s32vector", func),
+                location: None,
+            });
+            return None;
+        }
+    };
+    match interp.alloc.get_val(args[1]) {
+        Value::Integer(i) if *i >= 0 && (*i as usize) < len => Some(*i as usize),
+        _ => {
+            interp.error = Some(Error {
+                message: format!(
+                    "{}: index {} out of range for s32vector of length {}",
+                    func,
+                    interp.alloc.get_val(args[1]).to_string(&interp.alloc),
+                    len
+                ),
+                location: None,
+            });
+            None
+        }
+    }
+}
+
+fn s32vector_ref(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    if let Some(i) = s32vector_index(interp, args, "s32vector-ref") {
+        if let Value::S32Vector(items) = interp.alloc.get_val(args[0]) {
+            let item = items[i];
+            ret!(interp, Value::Integer(item as i64))
+        }
+    }
+}
+
+fn s32vector_set(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    if let Some(i) = s32vector_index(interp, args, "s32vector-set!") {
+        let item = match expect_s32(interp, args[2], "s32vector-set!") {
+            Some(item) => item,
+            None => return,
+        };
+        if let Value::S32Vector(items) = interp.alloc.get_val_mut(args[0]) {
+            items[i] = item;
+        }
+        ret!(interp, Value::Nil)
+    }
+}
+
+// expect_f64 returns the number at ptr as an f64vector element, or sets an error naming func
+// exact numbers are converted to the nearest float
+fn expect_f64(interp: &mut Interpreter, ptr: Ptr<Value>, func: &str) -> Option<f64> {
+    match Number::from_value(&interp.alloc, ptr) {
+        Some(num) => Some(num.to_f64()),
+        None => {
+            interp.error = Some(Error {
+                message: format!(
+                    "{}: elements must be numbers, got {}",
+                    func,
+                    interp.alloc.get_val(ptr).to_string(&interp.alloc)
+                ),
+                location: None,
+            });
+            None
+        }
+    }
+}
+
+fn make_f64vector(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let len = expect_int!(
+        interp,
+        args[0],
+        "the first argument to 'make-f64vector' must be an integer"
+    );
+    if len < 0 {
+        bail!(
+            interp,
+            "make-f64vector: length must not be negative, got {}",
+            len
+        );
+    }
+    let fill = match args.get(1) {
+        Some(&fill) => match expect_f64(interp, fill, "make-f64vector") {
+            Some(fill) => fill,
+            None => return,
+        },
+        None => 0.0,
+    };
+    ret!(interp, Value::F64Vector(vec![fill; len as usize]))
+}
+
+fn f64vector(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let mut items = Vec::with_capacity(args.len());
+    for &arg in args {
+        match expect_f64(interp, arg, "f64vector") {
+            Some(item) => items.push(item),
+            None => return,
+        }
+    }
+    ret!(interp, Value::F64Vector(items))
+}
+
+fn list_to_f64vector(interp: &mut Interpreter, env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    match list_items(&interp.alloc, args[0]) {
+        Some(items) => f64vector(interp, env, &items),
+        None => bail!(
+            interp,
+            "the first argument to 'list->f64vector' must be a list"
+        ),
+    }
+}
+
+fn f64vector_to_list(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let items = match interp.alloc.get_val(args[0]) {
+        Value::F64Vector(items) => items.clone(),
+        _ => bail!(
+            interp,
+            "the first argument to 'f64vector->list' must be an f64vector"
+        ),
+    };
+    let items = items
+        .into_iter()
+        .map(|f| Value::Float(f).gc(&mut interp.alloc))
+        .collect::<Vec<_>>();
+    let res = Value::from_vec(&items, &mut interp.alloc);
+    interp.results.push(res);
+}
+
+fn f64vector_length(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let len = match interp.alloc.get_val(args[0]) {
+        Value::F64Vector(items) => items.len(),
+        _ => bail!(
+            interp,
+            "the first argument to 'f64vector-length' must be an f64vector"
+        ),
+    };
+    ret!(interp, Value::Integer(len as i64))
+}
+
+// f64vector_index returns the index args[1] into the f64vector args[0],
+// or sets an error naming func
+fn f64vector_index(interp: &mut Interpreter, args: &[Ptr<Value>], func: &str) -> Option<usize> {
+    let len = match interp.alloc.get_val(args[0]) {
+        Value::F64Vector(items) => items.len(),
+        _ => {
+            interp.error = Some(Error {
+                message: format!("the first argument to '{}' must be an f64vector", func),
+                location: None,
+            });
+            return None;
+        }
+    };
+    match interp.alloc.get_val(args[1]) {
+        Value::Integer(i) if *i >= 0 && (*i as usize) < len => Some(*i as usize),
+        _ => {
+            interp.error = Some(Error {
+                message: format!(
+                    "{}: index {} out of range for f64vector of length {}",
+                    func,
+                    interp.alloc.get_val(args[1]).to_string(&interp.alloc),
+                    len
+                ),
+                location: None,
+            });
+            None
+        }
+    }
+}
+
+fn f64vector_ref(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    if let Some(i) = f64vector_index(interp, args, "f64vector-ref") {
+        if let Value::F64Vector(items) = interp.alloc.get_val(args[0]) {
+            let item = items[i];
+            ret!(interp, Value::Float(item))
+        }
+    }
+}
+
+fn f64vector_set(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    if let Some(i) = f64vector_index(interp, args, "f64vector-set!") {
+        let item = match expect_f64(interp, args[2], "f64vector-set!") {
+            Some(item) => item,
+            None => return,
+        };
+        if let Value::F64Vector(items) = interp.alloc.get_val_mut(args[0]) {
+            items[i] = item;
+        }
+        ret!(interp, Value::Nil)
+    }
+}
+
+// random returns a random integer in [0, n)
+fn random(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let n = expect_int!(
+        interp,
+        args[0],
+        "the first argument to 'random' must be an integer"
+    );
+    if n <= 0 {
+        bail!(interp, "random: bound must be positive, got {}", n);
+    }
+    let res = interp.rng.below(n as u64) as i64;
+    ret!(interp, Value::Integer(res))
+}
+
+// gensym returns a fresh symbol, starting with the given symbol or g,
+// numbered by a counter so that no two are alike and every run makes the same ones
+// numbers giving the name of a symbol already read or made are skipped
+fn gensym(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let prefix = match args.first().map(|&arg| interp.alloc.get_val(arg)) {
+        Some(Value::Symbol(prefix)) => prefix.clone(),
+        Some(_) => bail!(interp, "the first argument to 'gensym' must be a symbol"),
+        None => "g".to_string(),
+    };
+    let name = loop {
+        interp.gensym_count += 1;
+        let name = format!("{}-{}", prefix, interp.gensym_count);
+        if !interp.alloc.is_symbol_used(&name) {
+            break name;
+        }
+    };
+    ret!(interp, Value::Symbol(name))
+}
+
+fn is_string(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let res = matches!(interp.alloc.get_val(args[0]), Value::String(_));
+    ret!(interp, Value::Bool(res))
+}
+
+fn string_length(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let len = match interp.alloc.get_val(args[0]) {
+        Value::String(s) => s.chars().count(),
+        _ => bail!(
+            interp,
+            "the first argument to 'string-length' must be a string"
+        ),
+    };
+    ret!(interp, Value::Integer(len as i64))
+}
+
+fn string_append(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let mut res = String::new();
+    for arg in args {
+        match interp.alloc.get_val(*arg) {
+            Value::String(s) => res.push_str(s),
+            _ => bail!(interp, "the arguments to 'string-append' must be strings"),
+        }
+    }
+    ret!(interp, Value::String(res))
+}
+
+// expect_string returns a copy of the string at ptr, or sets an error naming func
+fn expect_string(interp: &mut Interpreter, ptr: Ptr<Value>, func: &str) -> Option<String> {
+    match interp.alloc.get_val(ptr) {
+        Value::String(s) => Some(s.clone()),
+        _ => {
+            interp.error = Some(Error {
+                message: format!("the argument to '{}' must be a string", func),
+                location: None,
+            });
+            None
+        }
+    }
+}
+
+// path_string returns the string for path, replacing any invalid unicode
+fn path_string(path: &Path) -> Value {
+    Value::String(path.to_string_lossy().into_owned())
+}
+
+fn get_environment_variable(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let name = match expect_string(interp, args[0], "get-environment-variable") {
+        Some(name) => name,
+        None => return,
+    };
+    match std::env::var(name) {
+        Ok(val) => ret!(interp, Value::String(val)),
+        Err(_) => ret!(interp, Value::Bool(false)),
+    }
+}
+
+fn path_join(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let mut path = PathBuf::new();
+    for arg in args {
+        match expect_string(interp, *arg, "path-join") {
+            Some(part) => path.push(part),
+            None => return,
+        }
+    }
+    ret!(interp, path_string(&path))
+}
+
+// path_part returns the part of the path args[0] picked by part, or #f if it has none
+fn path_part(
+    interp: &mut Interpreter,
+    args: &[Ptr<Value>],
+    func: &str,
+    part: fn(&Path) -> Option<&Path>,
+) {
+    let path = match expect_string(interp, args[0], func) {
+        Some(path) => path,
+        None => return,
+    };
+    match part(Path::new(&path)) {
+        Some(part) => ret!(interp, path_string(part)),
+        None => ret!(interp, Value::Bool(false)),
+    }
+}
+
+fn path_dirname(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    path_part(interp, args, "path-dirname", Path::parent)
+}
+
+fn path_basename(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    path_part(interp, args, "path-basename", |path| {
+        path.file_name().map(Path::new)
+    })
+}
+
+fn path_extension(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    path_part(interp, args, "path-extension", |path| {
+        path.extension().map(Path::new)
+    })
+}
+
+// expand_user replaces a leading ~ component with the home directory, taken from HOME or, on
+// Windows, USERPROFILE
+fn expand_user(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let path = match expect_string(interp, args[0], "expand-user") {
+        Some(path) => path,
+        None => return,
+    };
+    let rest = match Path::new(&path).strip_prefix("~") {
+        Ok(rest) => rest,
+        Err(_) => ret!(interp, Value::String(path)),
+    };
+    let home = match std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE")) {
+        Some(home) => PathBuf::from(home),
+        None => bail!(interp, "expand-user: the home directory is unknown"),
+    };
+    if rest.as_os_str().is_empty() {
+        ret!(interp, path_string(&home))
+    }
+    ret!(interp, path_string(&home.join(rest)))
+}
+
+// absolute_path joins a relative path onto the current directory, without touching the file
+fn absolute_path(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let path = match expect_string(interp, args[0], "absolute-path") {
+        Some(path) => PathBuf::from(path),
+        None => return,
+    };
+    if path.is_absolute() {
+        ret!(interp, path_string(&path))
+    }
+    match std::env::current_dir() {
+        Ok(dir) => ret!(interp, path_string(&dir.join(path))),
+        Err(err) => bail!(interp, "absolute-path: {}", err),
+    }
+}
+
+// now returns the number of seconds since the Unix epoch, negative if the clock is before it
+fn now() -> i64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(elapsed) => elapsed.as_secs() as i64,
+        Err(err) => -(err.duration().as_secs() as i64),
+    }
+}
+
+fn current_time(interp: &mut Interpreter, _env: Ptr<Environment>, _args: &[Ptr<Value>]) {
+    ret!(interp, Value::Integer(now()))
+}
+
+fn current_date(interp: &mut Interpreter, _env: Ptr<Environment>, _args: &[Ptr<Value>]) {
+    ret!(interp, Value::Date(Date::from_seconds(now())))
+}
+
+fn is_date(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let res = matches!(interp.alloc.get_val(args[0]), Value::Date(_));
+    ret!(interp, Value::Bool(res))
+}
+
+fn seconds_to_date(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let seconds = expect_int!(
+        interp,
+        args[0],
+        "the first argument to 'seconds->date' must be an integer"
+    );
+    ret!(interp, Value::Date(Date::from_seconds(seconds)))
+}
+
+// expect_date returns the date at ptr, or sets an error naming func
+fn expect_date(interp: &mut Interpreter, ptr: Ptr<Value>, func: &str) -> Option<Date> {
+    match interp.alloc.get_val(ptr) {
+        Value::Date(date) => Some(*date),
+        _ => {
+            interp.error = Some(Error {
+                message: format!("the first argument to '{}' must be a date", func),
+                location: None,
+            });
+            None
+        }
+    }
+}
+
+// date_field returns a field of the date args[0], computed by field
+fn date_field(interp: &mut Interpreter, args: &[Ptr<Value>], func: &str, field: fn(Date) -> i64) {
+    if let Some(date) = expect_date(interp, args[0], func) {
+        ret!(interp, Value::Integer(field(date)))
+    }
+}
+
+fn date_to_seconds(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    date_field(interp, args, "date->seconds", Date::to_seconds)
+}
+
+fn date_year(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    date_field(interp, args, "date-year", |date| date.year)
+}
+
+fn date_month(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    date_field(interp, args, "date-month", |date| date.month.into())
+}
+
+fn date_day(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    date_field(interp, args, "date-day", |date| date.day.into())
+}
+
+fn date_hour(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    date_field(interp, args, "date-hour", |date| date.hour.into())
+}
+
+fn date_minute(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    date_field(interp, args, "date-minute", |date| date.minute.into())
+}
+
+fn date_second(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    date_field(interp, args, "date-second", |date| date.second.into())
+}
+
+fn date_week_day(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    date_field(interp, args, "date-week-day", |date| date.week_day().into())
+}
+
+fn date_year_day(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    date_field(interp, args, "date-year-day", |date| date.year_day().into())
+}
+
+fn date_to_string(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let date = match expect_date(interp, args[0], "date->string") {
+        Some(date) => date,
+        None => return,
+    };
+    let fmt = match interp.alloc.get_val(args[1]) {
+        Value::String(fmt) => fmt,
+        _ => bail!(
+            interp,
+            "the second argument to 'date->string' must be a string"
+        ),
+    };
+    match date.format(fmt) {
+        Ok(s) => ret!(interp, Value::String(s)),
+        Err(message) => bail!(interp, "date->string: {}", message),
+    }
+}
+
+fn string_to_date(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let res = match (interp.alloc.get_val(args[0]), interp.alloc.get_val(args[1])) {
+        (Value::String(s), Value::String(fmt)) => Date::parse(s, fmt),
+        _ => bail!(interp, "the arguments to 'string->date' must be strings"),
+    };
+    match res {
+        Ok(date) => ret!(interp, Value::Date(date)),
+        Err(message) => bail!(interp, "string->date: {}", message),
+    }
+}
+
+fn make_string_builder(interp: &mut Interpreter, _env: Ptr<Environment>, _args: &[Ptr<Value>]) {
+    ret!(interp, Value::StringBuilder(String::new()))
+}
+
+// string_builder_mut returns the string builder at ptr, or sets an error naming func
+fn string_builder_mut<'a>(
+    interp: &'a mut Interpreter,
+    ptr: Ptr<Value>,
+    func: &str,
+) -> Option<&'a mut String> {
+    if let Value::StringBuilder(_) = interp.alloc.get_val(ptr) {
+        match interp.alloc.get_val_mut(ptr) {
+            Value::StringBuilder(sb) => return Some(sb),
+            _ => unreachable!(),
+        }
+    }
+    interp.error = Some(Error {
+        message: format!("the first argument to '{}' must be a string builder", func),
+        location: None,
+    });
+    None
+}
+
+// sb_add appends strings and chars to a string builder in place,
+// so building a string piece by piece takes linear time
+fn sb_add(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let mut text = String::new();
+    for arg in &args[1..] {
+        match interp.alloc.get_val(*arg) {
+            Value::String(s) => text.push_str(s),
+            Value::Char(c) => text.push(*c),
+            _ => bail!(
+                interp,
+                "the arguments added by 'sb-add!' must be strings or chars"
+            ),
+        }
+    }
+    if let Some(sb) = string_builder_mut(interp, args[0], "sb-add!") {
+        sb.push_str(&text);
+        ret!(interp, Value::Nil)
+    }
+}
+
+fn sb_to_string(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    if let Some(sb) = string_builder_mut(interp, args[0], "sb->string") {
+        let res = sb.clone();
+        ret!(interp, Value::String(res))
+    }
+}
+
+fn string_to_symbol(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let name = match interp.alloc.get_val(args[0]) {
+        Value::String(s) => s.clone(),
+        _ => bail!(interp, "the argument to 'string->symbol' must be a string"),
+    };
+    ret!(interp, Value::Symbol(name))
+}
+
+fn symbol_to_string(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let name = match interp.alloc.get_val(args[0]) {
+        Value::Symbol(s) => s.clone(),
+        _ => bail!(interp, "the argument to 'symbol->string' must be a symbol"),
+    };
+    ret!(interp, Value::String(name))
+}
+
+fn is_keyword(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let res = matches!(interp.alloc.get_val(args[0]), Value::Keyword(_));
+    ret!(interp, Value::Bool(res))
+}
+
+fn keyword_to_symbol(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let name = match interp.alloc.get_val(args[0]) {
+        Value::Keyword(s) => s.clone(),
+        _ => bail!(
+            interp,
+            "the argument to 'keyword->symbol' must be a keyword"
+        ),
+    };
+    ret!(interp, Value::Symbol(name))
+}
+
+fn special_forms(interp: &mut Interpreter, _env: Ptr<Environment>, _args: &[Ptr<Value>]) {
+    let names = syntax::FORMS
+        .iter()
+        .map(|form| Value::Symbol(form.name.to_string()).gc(&mut interp.alloc))
+        .collect::<Vec<_>>();
+    let res = Value::from_vec(&names, &mut interp.alloc);
+    interp.results.push(res);
+}
+
+fn features(interp: &mut Interpreter, _env: Ptr<Environment>, _args: &[Ptr<Value>]) {
+    let names = interp.features.clone();
+    let names = names
+        .into_iter()
+        .map(|name| Value::Symbol(name).gc(&mut interp.alloc))
+        .collect::<Vec<_>>();
+    let res = Value::from_vec(&names, &mut interp.alloc);
+    interp.results.push(res);
+}
+
+fn is_special_form(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let res = match interp.alloc.get_val(args[0]) {
+        Value::Symbol(s) => syntax::is_special_form(s),
+        _ => false,
+    };
+    ret!(interp, Value::Bool(res))
+}
+
+fn is_char(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let res = matches!(interp.alloc.get_val(args[0]), Value::Char(_));
+    ret!(interp, Value::Bool(res))
+}
+
+fn char_to_integer(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let c = match interp.alloc.get_val(args[0]) {
+        Value::Char(c) => *c,
+        _ => bail!(
+            interp,
+            "the first argument to 'char->integer' must be a character"
+        ),
+    };
+    ret!(interp, Value::Integer(c as i64))
+}
+
+fn integer_to_char(interp: &mut Interpreter, _env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let i = expect_int!(
+        interp,
+        args[0],
+        "the first argument to 'integer->char' must be an integer"
+    );
+    match u32::try_from(i).ok().and_then(char::from_u32) {
+        Some(c) => ret!(interp, Value::Char(c)),
+        None => bail!(interp, "integer->char: {} is not a Unicode scalar value", i),
+    }
+}
+
+pub(super) fn build(
+    alloc: &mut Allocator,
+    plugins: &[Box<dyn Plugin>],
+    language: Language,
+) -> Ptr<Environment> {
+    let mut bindings = HashMap::new();
+    let mut native = |name: &str, func: NativeFn, arity: Arity| {
+        if language.restriction(name).is_some() {
+            return;
+        }
+        let val = Value::NativeFunction(NativeFunction {
+            name: name.to_string(),
+            func,
+            arity,
+        })
+        .gc(alloc);
+        bindings.insert(name.to_string(), val);
+    };
+
+    native("+", plus, Arity::at_least(0));
+    native("*", times, Arity::at_least(0));
+    native("i=", ieq, Arity::exactly(2));
+    native("i<", ilt, Arity::exactly(2));
+    native("=", num_eq, Arity::at_least(2));
+    native("<", num_lt, Arity::at_least(2));
+    native("<=", num_le, Arity::at_least(2));
+    native(">", num_gt, Arity::at_least(2));
+    native(">=", num_ge, Arity::at_least(2));
+    native("-", minus, Arity::exactly(2));
+    native("/", divide, Arity::at_least(1));
+    native("number->string", number_to_string, Arity::between(1, 3));
+    native("cons", cons, Arity::exactly(2));
+    native("car", car, Arity::exactly(1));
+    native("cdr", cdr, Arity::exactly(1));
+    native("list", list, Arity::at_least(0));
+    native("null?", is_null, Arity::exactly(1));
+    native("pair?", is_pair, Arity::exactly(1));
+    native("symbol?", is_symbol, Arity::exactly(1));
+    native("integer?", is_integer, Arity::exactly(1));
+    native("procedure?", is_procedure_native, Arity::exactly(1));
+    native("not", not, Arity::exactly(1));
+    native("call/cc", call_with_cc, Arity::exactly(1));
+    native("last", last, Arity::at_least(1));
+    native("gc-profile", gc_profile, Arity::exactly(0));
+    native("gc-run", gc_run, Arity::exactly(0));
+    native("make-guardian", make_guardian, Arity::exactly(0));
+    native("guardian-register!", guardian_register, Arity::exactly(2));
+    native("guardian-pop!", guardian_pop, Arity::exactly(1));
+    native("repl-prompt!", repl_prompt, Arity::exactly(1));
+    native("repl-banner!", repl_banner, Arity::exactly(1));
+    native("exit", exit, Arity::between(0, 1));
+    native("random", random, Arity::exactly(1));
+    native("gensym", gensym, Arity::between(0, 1));
+    native("eq?", is_eq, Arity::exactly(2));
+    native("equal?", is_equal, Arity::exactly(2));
+    native("make-set", make_set, Arity::at_least(0));
+    native("set-add!", set_add, Arity::exactly(2));
+    native("set-member?", set_member, Arity::exactly(2));
+    native("set-union", set_union, Arity::at_least(0));
+    native("set-difference", set_difference, Arity::at_least(1));
+    native("set->list", set_to_list, Arity::exactly(1));
+    native("deep-copy", copy, Arity::exactly(1));
+    native("copy-tree", copy, Arity::exactly(1));
+    native("procedure-arity", procedure_arity, Arity::exactly(1));
+    native("special-forms", special_forms, Arity::exactly(0));
+    native("special-form?", is_special_form, Arity::exactly(1));
+    native("features", features, Arity::exactly(0));
+    native("map", map, Arity::exactly(2));
+    native(
+        "continuation-one-shot?",
+        continuation_one_shot,
+        Arity::exactly(1),
+    );
+    native("dump-continuation", dump_continuation, Arity::exactly(1));
+
+    native(
+        "set-object-property!",
+        set_object_property,
+        Arity::exactly(3),
+    );
+    native("object-property", object_property, Arity::exactly(2));
+
+    native("make-comparator", make_comparator, Arity::between(1, 2));
+    native("comparator?", is_comparator, Arity::exactly(1));
+    native("make-hash-table", make_hash_table, Arity::between(0, 2));
+    native("hash-table?", is_hash_table, Arity::exactly(1));
+    native("hash-table-set!", hash_table_set, Arity::exactly(3));
+    native("hash-table-ref", hash_table_ref, Arity::between(2, 3));
+    native(
+        "hash-table-contains?",
+        hash_table_contains,
+        Arity::exactly(2),
+    );
+    native("hash-table-delete!", hash_table_delete, Arity::exactly(2));
+    native("hash-table-count", hash_table_count, Arity::exactly(1));
+    native("hash-table-keys", hash_table_keys, Arity::exactly(1));
+    native("hash-table->alist", hash_table_to_alist, Arity::exactly(1));
+    native("alist->hash-table", alist_to_hash_table, Arity::at_least(1));
+    native("hash-table-walk", hash_table_walk, Arity::exactly(2));
+    native(
+        "hash-table-update!",
+        hash_table_update,
+        Arity::between(3, 4),
+    );
+    native(
+        "hash-table-update!/default",
+        hash_table_update_default,
+        Arity::exactly(4),
+    );
+
+    native("vector", vector, Arity::at_least(0));
+    native("list->vector", list_to_vector, Arity::exactly(1));
+    native("vector->list", vector_to_list, Arity::exactly(1));
+    native("vector-length", vector_length, Arity::exactly(1));
+    native("vector-ref", vector_ref, Arity::exactly(2));
+    native("vector-set!", vector_set, Arity::exactly(3));
+    native("sort", sort, Arity::exactly(2));
+    native(
+        "vector-binary-search",
+        vector_binary_search,
+        Arity::exactly(3),
+    );
+
+    native("make-bitvector", make_bitvector, Arity::between(1, 2));
+    native("bitvector-ref", bitvector_ref, Arity::exactly(2));
+    native("bitvector-set!", bitvector_set, Arity::exactly(3));
+    native("bitvector-length", bitvector_length, Arity::exactly(1));
+    native("bitvector-count", bitvector_count, Arity::exactly(1));
+    native("bitvector-and", bitvector_and, Arity::at_least(1));
+    native("bitvector-or", bitvector_or, Arity::at_least(1));
+    native("bitvector-xor", bitvector_xor, Arity::at_least(1));
+
+    native("make-queue", make_queue, Arity::at_least(0));
+    native("enqueue!", enqueue, Arity::at_least(2));
+    native("dequeue!", dequeue, Arity::exactly(1));
+    native("queue-length", queue_length, Arity::exactly(1));
+    native("queue->list", queue_to_list, Arity::exactly(1));
+
+    native("make-heap", make_heap, Arity::exactly(1));
+    native("heap-push!", heap_push, Arity::exactly(2));
+    native("heap-pop!", heap_pop, Arity::exactly(1));
+    native("heap-peek", heap_peek, Arity::exactly(1));
+    native("heap-length", heap_length, Arity::exactly(1));
+
+    native("make-array", make_array, Arity::between(1, 2));
+    native("array-ref", array_ref, Arity::at_least(1));
+    native("array-set!", array_set, Arity::at_least(2));
+    native("array-shape", array_shape, Arity::exactly(1));
+    native("array-map!", array_map, Arity::exactly(2));
+
+    native("make-s32vector", make_s32vector, Arity::between(1, 2));
+    native("s32vector", s32vector, Arity::at_least(0));
+    native("list->s32vector", list_to_s32vector, Arity::exactly(1));
+    native("s32vector->list", s32vector_to_list, Arity::exactly(1));
+    native("s32vector-length", s32vector_length, Arity::exactly(1));
+    native("s32vector-ref", s32vector_ref, Arity::exactly(2));
+    native("s32vector-set!", s32vector_set, Arity::exactly(3));
+    native("make-f64vector", make_f64vector, Arity::between(1, 2));
+    native("f64vector", f64vector, Arity::at_least(0));
+    native("list->f64vector", list_to_f64vector, Arity::exactly(1));
+    native("f64vector->list", f64vector_to_list, Arity::exactly(1));
+    native("f64vector-length", f64vector_length, Arity::exactly(1));
+    native("f64vector-ref", f64vector_ref, Arity::exactly(2));
+    native("f64vector-set!", f64vector_set, Arity::exactly(3));
+
+    native("string?", is_string, Arity::exactly(1));
+    native("string-length", string_length, Arity::exactly(1));
+    native("string-append", string_append, Arity::at_least(0));
+    native("string->symbol", string_to_symbol, Arity::exactly(1));
+    native("keyword?", is_keyword, Arity::exactly(1));
+    native("keyword->symbol", keyword_to_symbol, Arity::exactly(1));
+    native("symbol->string", symbol_to_string, Arity::exactly(1));
+    native(
+        "make-string-builder",
+        make_string_builder,
+        Arity::exactly(0),
+    );
+    native("sb-add!", sb_add, Arity::at_least(2));
+    native("sb->string", sb_to_string, Arity::exactly(1));
+
+    native(
+        "get-environment-variable",
+        get_environment_variable,
+        Arity::exactly(1),
+    );
+    native("path-join", path_join, Arity::at_least(1));
+    native("path-dirname", path_dirname, Arity::exactly(1));
+    native("path-basename", path_basename, Arity::exactly(1));
+    native("path-extension", path_extension, Arity::exactly(1));
+    native("expand-user", expand_user, Arity::exactly(1));
+    native("absolute-path", absolute_path, Arity::exactly(1));
+
+    native("current-time", current_time, Arity::exactly(0));
+    native("current-date", current_date, Arity::exactly(0));
+    native("date?", is_date, Arity::exactly(1));
+    native("seconds->date", seconds_to_date, Arity::exactly(1));
+    native("date->seconds", date_to_seconds, Arity::exactly(1));
+    native("date-year", date_year, Arity::exactly(1));
+    native("date-month", date_month, Arity::exactly(1));
+    native("date-day", date_day, Arity::exactly(1));
+    native("date-hour", date_hour, Arity::exactly(1));
+    native("date-minute", date_minute, Arity::exactly(1));
+    native("date-second", date_second, Arity::exactly(1));
+    native("date-week-day", date_week_day, Arity::exactly(1));
+    native("date-year-day", date_year_day, Arity::exactly(1));
+    native("date->string", date_to_string, Arity::exactly(2));
+    native("string->date", string_to_date, Arity::exactly(2));
+
+    native("char?", is_char, Arity::exactly(1));
+    native("char->integer", char_to_integer, Arity::exactly(1));
+    native("integer->char", integer_to_char, Arity::exactly(1));
+
+    bindings.insert("nil".to_string(), Value::Nil.gc(alloc));
+
+    let mut registry = StdlibRegistry::new(alloc, &mut bindings);
+    for plugin in plugins {
+        plugin.register(&mut registry);
+    }
+
+    Environment::new_with_bindings(bindings).gc(alloc)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::interpreter::test::{evaluator, run};
+    use crate::interpreter::{Interpreter, Options, Value};
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn compares_structurally() {
+        assert_eq!(
+            run("(equal? (quote (1 (2 a))) (cons 1 (quote ((2 a)))))"),
+            Ok("#t".to_string())
+        );
+        assert_eq!(
+            run("(equal? (quote (1 2)) (quote (1 3)))"),
+            Ok("#f".to_string())
+        );
+    }
+
+    #[test]
+    fn reports_procedure_arity() {
+        assert_eq!(
+            run("(procedure-arity (lambda (a b) a))"),
+            Ok("(2 . 2)".to_string())
+        );
+        assert_eq!(run("(procedure-arity exit)"), Ok("(0 . 1)".to_string()));
+        assert_eq!(run("(procedure-arity +)"), Ok("(0 . #f)".to_string()));
+    }
+
+    #[test]
+    fn compares_numbers() {
+        assert_eq!(run("(= 2 2 2)"), Ok("#t".to_string()));
+        assert_eq!(run("(= 2 2 3)"), Ok("#f".to_string()));
+        assert_eq!(run("(< 1 2 3)"), Ok("#t".to_string()));
+        assert_eq!(run("(< 1 3 2)"), Ok("#f".to_string()));
+        assert_eq!(run("(<= 1 1 2)"), Ok("#t".to_string()));
+        assert_eq!(run("(> 3 2 2)"), Ok("#f".to_string()));
+        assert_eq!(run("(>= 3 2 2)"), Ok("#t".to_string()));
+        assert_eq!(
+            run("(< 2 1 #t)"),
+            Err("all arguments to '<' must be numbers".to_string())
+        );
+    }
+
+    #[test]
+    fn describes_continuations() {
+        let mut interp = Interpreter::new(Options::default());
+        let res = interp
+            .eval("(car (cdr (list 1 (call/cc (lambda (c) c)) (+ 2 3))))")
+            .unwrap();
+        match interp.value(res.ptr()) {
+            Value::Continuation(c) => assert_eq!(
+                c.describe(&interp.alloc),
+                "continuation at call depth 0: 4 pending steps, 2 results, 3 saved result stacks\n  \
+                 0: eval (+ 2 3)\n  1: apply\n  2: apply\n  3: apply"
+            ),
+            _ => panic!("expected a continuation"),
+        }
+    }
+
+    #[test]
+    fn computes_with_floats() {
+        assert_eq!(run("(+ 1 2.5)"), Ok("3.5".to_string()));
+        assert_eq!(run("(* 2 1.5 2)"), Ok("6.0".to_string()));
+        assert_eq!(run("(- 1 0.25)"), Ok("0.75".to_string()));
+        assert_eq!(run("(+ 1 2)"), Ok("3".to_string()));
+        assert_eq!(run("(< 1 1.5 2)"), Ok("#t".to_string()));
+        assert_eq!(run("(= 1 1.0)"), Ok("#t".to_string()));
+        assert_eq!(run("(equal? 1 1.0)"), Ok("#f".to_string()));
+        assert_eq!(run("(equal? 0.5 (* 0.25 2))"), Ok("#t".to_string()));
+        assert_eq!(run("(integer? 1.0)"), Ok("#f".to_string()));
+    }
+
+    #[test]
+    fn converts_numbers_to_strings() {
+        assert_eq!(run("(number->string 42)"), Ok("\"42\"".to_string()));
+        assert_eq!(run("(number->string 1/3)"), Ok("\"1/3\"".to_string()));
+        assert_eq!(
+            run("(number->string (+ 0.1 0.2))"),
+            Ok("\"0.30000000000000004\"".to_string())
+        );
+        assert_eq!(
+            run("(number->string 3.14159 'fixed 2)"),
+            Ok("\"3.14\"".to_string())
+        );
+        assert_eq!(
+            run("(number->string 1/8 'fixed)"),
+            Ok("\"0.125000\"".to_string())
+        );
+        assert_eq!(
+            run("(number->string 123456 'scientific 3)"),
+            Ok("\"1.235e5\"".to_string())
+        );
+        assert_eq!(
+            run("(number->string 1.5 'engineering 2)"),
+            Err("the second argument to 'number->string' must be fixed or scientific".to_string())
+        );
+        assert_eq!(
+            run("(number->string 1.5 'fixed -1)"),
+            Err("the third argument to 'number->string' must be between 0 and 100".to_string())
+        );
+    }
+
+    #[test]
+    fn works_with_characters() {
+        assert_eq!(
+            run("(list #\\a #\\space #\\x7)"),
+            Ok("(#\\a #\\space #\\alarm)".to_string())
+        );
+        assert_eq!(run("(char->integer #\\A)"), Ok("65".to_string()));
+        assert_eq!(run("(integer->char 955)"), Ok("#\\λ".to_string()));
+        assert_eq!(run("(integer->char 1)"), Ok("#\\x1".to_string()));
+        assert_eq!(run("(eq? #\\a (integer->char 97))"), Ok("#t".to_string()));
+        assert_eq!(
+            run("(integer->char 55296)"),
+            Err("integer->char: 55296 is not a Unicode scalar value".to_string())
+        );
+    }
+
+    #[test]
+    fn computes_with_rationals() {
+        assert_eq!(run("(+ 1/3 1/6)"), Ok("1/2".to_string()));
+        assert_eq!(run("(* 2/3 3/2)"), Ok("1".to_string()));
+        assert_eq!(run("(/ 1 3)"), Ok("1/3".to_string()));
+        assert_eq!(run("(/ 6 3)"), Ok("2".to_string()));
+        assert_eq!(run("(/ 4)"), Ok("1/4".to_string()));
+        assert_eq!(run("(/ 1 2 2)"), Ok("1/4".to_string()));
+        assert_eq!(run("(/ 1.0 4)"), Ok("0.25".to_string()));
+        assert_eq!(run("(- 1/2 1)"), Ok("-1/2".to_string()));
+        assert_eq!(run("4/2"), Ok("2".to_string()));
+        assert_eq!(run("(< 1/3 0.5 2/3)"), Ok("#t".to_string()));
+        assert_eq!(run("(equal? 1/2 (/ 2 4))"), Ok("#t".to_string()));
+        assert_eq!(run("(/ 1 0)"), Err("/: division by zero".to_string()));
+    }
+
+    #[test]
+    fn checks_native_arity() {
+        assert_eq!(
+            run("(cons 1)"),
+            Err("wrong number of arguments to #<builtin cons>: expected 2, received 1".to_string())
+        );
+    }
+
+    #[test]
+    fn prints_procedures() {
+        let mut eval = evaluator();
+        assert_eq!(eval("+"), Ok("#<builtin +>".to_string()));
+        assert_eq!(
+            eval("(lambda (a b) a)"),
+            Ok("#<procedure (a b)>".to_string())
+        );
+        eval("(define fact (lambda (n) (if (i= n 0) 1 (* n (fact (- n 1))))))").unwrap();
+        eval("(define factorial fact)").unwrap();
+        assert_eq!(eval("factorial"), Ok("#<procedure fact (n)>".to_string()));
+        assert_eq!(
+            eval("(fact 1 2)"),
+            Err(
+                "wrong number of arguments to #<procedure fact (n)>: expected 1, received 2"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            eval("(1 2)"),
+            Err("attempt to call a non-function value: 1".to_string())
+        );
+    }
+
+    #[test]
+    fn stores_object_properties() {
+        let source = "
+            (define f (lambda (x) x))
+            (set-object-property! f (quote doc) (quote identity))
+            (set-object-property! f (quote doc) (quote id))
+            (cons (object-property f (quote doc))
+                  (object-property (lambda (x) x) (quote doc)))";
+        assert_eq!(run(source), Ok("(id . #f)".to_string()));
+    }
+
+    #[test]
+    fn maps_over_lists() {
+        assert_eq!(
+            run("(cons 0 (map (lambda (x) (* x x)) (quote (1 2 3))))"),
+            Ok("(0 1 4 9)".to_string())
+        );
+        assert_eq!(
+            run("(map (lambda (x) (+ x (quote a))) (quote (1)))"),
+            Err("all arguments to '+' must be numbers".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_escaping_native_calls() {
+        assert!(run("(call/cc (lambda (k) (map k (quote (1 2)))))").is_err());
+        assert_eq!(
+            run("(map (lambda (x) (call/cc (lambda (k) (k x)))) (quote (1 2)))"),
+            Ok("(1 2)".to_string())
+        );
+    }
+
+    #[test]
+    fn sets_deduplicate_equal_values() {
+        let source = "
+            (define s (make-set 1 (quote (a b)) 1))
+            (set-add! s (quote (a b)))
+            (set-add! s 2)
+            (set->list s)";
+        assert_eq!(run(source), Ok("(1 (a b) 2)".to_string()));
+        assert_eq!(
+            run("(set-member? (make-set (quote (a))) (quote (a)))"),
+            Ok("#t".to_string())
+        );
+    }
+
+    #[test]
+    fn compares_and_hashes_cyclic_and_long_data() {
+        assert_eq!(
+            run("(define a '#0=(1 #0#)) (define b '#1=(1 (1 #1#))) (equal? a b)"),
+            Ok("#t".to_string())
+        );
+        assert_eq!(
+            run("(equal? '#0=(1 #0#) '#1=(1 (2 #1#)))"),
+            Ok("#f".to_string())
+        );
+        assert_eq!(
+            run("(define v (vector 1 0)) (vector-set! v 1 v) (equal? v (vector 1 v))"),
+            Ok("#t".to_string())
+        );
+        assert_eq!(
+            run("(define s (make-set '#0=(1 #0#))) (set-member? s '#1=(1 (1 #1#)))"),
+            Ok("#t".to_string())
+        );
+
+        let lists = "
+            (define (build n acc) (if (= n 0) acc (build (- n 1) (cons n acc))))
+            (define a (build 50000 '()))
+            (define b (build 50000 '()))";
+        assert_eq!(
+            run(&format!("{} (cons (equal? a b) (equal? a (cdr b)))", lists)),
+            Ok("(#t . #f)".to_string())
+        );
+        assert_eq!(
+            run(&format!("{} (set-member? (make-set a) b)", lists)),
+            Ok("#t".to_string())
+        );
+    }
+
+    #[test]
+    fn deep_copies_mutable_containers() {
+        assert_eq!(
+            run("(define v (vector 1 2)) (eq? v (deep-copy v))"),
+            Ok("#f".to_string())
+        );
+        let source = "
+            (define inner (vector 1))
+            (define v (vector inner inner))
+            (define copy (deep-copy v))
+            (vector-set! (vector-ref copy 0) 0 2)";
+        // the copy shares its elements with itself but not with the original
+        assert_eq!(
+            run(&format!("{} (list v copy)", source)),
+            Ok("(#(#(1) #(1)) #(#(2) #(2)))".to_string())
+        );
+
+        let source = "
+            (define t (make-hash-table))
+            (hash-table-set! t 'k (vector 1))
+            (define q (make-queue (vector 1)))
+            (define sb (make-string-builder))
+            (define copy (deep-copy (list t q sb)))
+            (vector-set! (hash-table-ref (car copy) 'k) 0 2)
+            (enqueue! (car (cdr copy)) 3)
+            (vector-set! (dequeue! (car (cdr copy))) 0 2)
+            (sb-add! (car (cdr (cdr copy))) \"copy\")";
+        assert_eq!(
+            run(&format!(
+                "{} (list (hash-table-ref t 'k) (queue->list q) (sb->string sb))",
+                source
+            )),
+            Ok("(#(1) (#(1)) \"\")".to_string())
+        );
+    }
+
+    #[test]
+    fn combines_sets() {
+        assert_eq!(
+            run("(set->list (set-union (make-set 1 2) (make-set 2 3)))"),
+            Ok("(1 2 3)".to_string())
+        );
+        assert_eq!(
+            run("(set->list (set-difference (make-set 1 2 3) (make-set 2)))"),
+            Ok("(1 3)".to_string())
+        );
+    }
+
+    #[test]
+    fn hash_tables_compare_keys_with_their_comparator() {
+        let source = "
+            (define equal-table (make-hash-table))
+            (define eq-table (make-hash-table eq?))
+            (define key (quote (1 2)))
+            (hash-table-set! equal-table key 1)
+            (hash-table-set! eq-table key 1)";
+        assert_eq!(
+            run(&format!(
+                "{} (cons (hash-table-ref equal-table (quote (1 2)) 0)
+                          (hash-table-ref eq-table (quote (1 2)) 0))",
+                source
+            )),
+            Ok("(1 . 0)".to_string())
+        );
+        assert_eq!(
+            run(&format!("{} (hash-table-ref eq-table key)", source)),
+            Ok("1".to_string())
+        );
+        // keywords are eq? when their names are, so each read of one finds the same entry
+        assert_eq!(
+            run(&format!(
+                "{} (hash-table-set! eq-table #:size 2) (hash-table-ref eq-table #:size 0)",
+                source
+            )),
+            Ok("2".to_string())
+        );
+    }
+
+    #[test]
+    fn hash_tables_use_custom_comparators() {
+        let source = "
+            (define same-parity? (lambda (a b) (i= (parity a) (parity b))))
+            (define parity (lambda (n) (if (i= n 0) 0 (if (i= n 1) 1 (parity (- n 2))))))
+            (define table (make-hash-table (make-comparator same-parity? parity)))
+            (hash-table-set! table 1 (quote odd))
+            (hash-table-set! table 2 (quote even))
+            (hash-table-set! table 3 (quote odd-again))";
+        assert_eq!(
+            run(&format!(
+                "{} (cons (hash-table-ref table 5) (hash-table-count table))",
+                source
+            )),
+            Ok("(odd-again . 2)".to_string())
+        );
+        assert_eq!(
+            run(&format!(
+                "{} (hash-table-delete! table 7) (hash-table-contains? table 1)",
+                source
+            )),
+            Ok("#f".to_string())
+        );
+        assert_eq!(
+            run("(define t (make-hash-table i=)) (hash-table-set! t 1 1) (hash-table-ref t 2 #f)"),
+            Ok("#f".to_string())
+        );
+    }
+
+    #[test]
+    fn hash_tables_convert_and_update() {
+        let source = "
+            (define counts (make-hash-table))
+            (map (lambda (word) (hash-table-update!/default counts word (lambda (n) (+ n 1)) 0))
+                 '(a b a c a b))
+            (define seen (make-queue))
+            (hash-table-walk counts (lambda (word n) (enqueue! seen (list word n))))";
+        assert_eq!(
+            run(&format!(
+                "{} (sort (queue->list seen) (lambda (a b) (i< (car (cdr a)) (car (cdr b)))))",
+                source
+            )),
+            Ok("((c 1) (b 2) (a 3))".to_string())
+        );
+        assert_eq!(
+            run(&format!(
+                "{} (hash-table-update! counts 'd (lambda (n) (* n 10)) (lambda () 5)) \
+                 (hash-table-update! counts 'a (lambda (n) (* n 10))) \
+                 (define copy (alist->hash-table (hash-table->alist counts))) \
+                 (list (hash-table-count copy) (hash-table-ref copy 'a) (hash-table-ref copy 'd))",
+                source
+            )),
+            Ok("(4 30 50)".to_string())
+        );
+
+        // the first association of a key is kept
+        let source = "(define t (alist->hash-table (list (cons 1 'one) (cons 1 'uno)) eq?))";
+        assert_eq!(
+            run(&format!(
+                "{} (cons (hash-table-ref t 1) (hash-table-count t))",
+                source
+            )),
+            Ok("(one . 1)".to_string())
+        );
+        assert_eq!(
+            run("(hash-table->alist (alist->hash-table (list (cons 'k 'v))))"),
+            Ok("((k . v))".to_string())
+        );
+
+        assert_eq!(
+            run("(hash-table-update! (make-hash-table) 1 (lambda (n) n))"),
+            Err("hash-table-update!: no value for key 1".to_string())
+        );
+        assert_eq!(
+            run("(alist->hash-table '(1))"),
+            Err("alist->hash-table: expected a pair, got 1".to_string())
+        );
+        assert_eq!(
+            run("(hash-table-walk 1 car)"),
+            Err("the first argument to 'hash-table-walk' must be a hash table".to_string())
+        );
+        assert_eq!(
+            run("(define t (alist->hash-table (list (cons 1 2)))) (hash-table-walk t car)"),
+            Err("wrong number of arguments to #<builtin car>: expected 1, received 2".to_string())
+        );
+    }
+
+    #[test]
+    fn hash_tables_report_errors() {
+        assert_eq!(
+            run("(hash-table-ref (make-hash-table) 1)"),
+            Err("hash-table-ref: no value for key 1".to_string())
+        );
+        assert_eq!(
+            run("(hash-table-set! (make-hash-table (lambda (a b) (eq? a b)) (lambda (k) k)) (quote a) 1)"),
+            Err("hash functions must return an integer".to_string())
+        );
+        assert_eq!(
+            run("(define t (make-hash-table (lambda (a b) (+ a b)))) (hash-table-set! t 1 1) (hash-table-set! t (quote a) 2)"),
+            Err("all arguments to '+' must be numbers".to_string())
+        );
+    }
+
+    #[test]
+    fn sorts_stably() {
+        assert_eq!(run("(sort (quote (3 1 2)) i<)"), Ok("(1 2 3)".to_string()));
+        assert_eq!(
+            run("(sort (vector (vector 1 (quote a)) (vector 0 (quote b))
+                              (vector 1 (quote c)) (vector 0 (quote d)))
+                      (lambda (x y) (i< (vector-ref x 0) (vector-ref y 0))))"),
+            Ok("#(#(0 b) #(0 d) #(1 a) #(1 c))".to_string())
+        );
+    }
+
+    #[test]
+    fn sort_propagates_comparator_errors() {
+        let mut eval = evaluator();
+
+        assert_eq!(
+            eval("(sort (quote (2 1)) (lambda (a b) (+ a (quote b))))"),
+            Err("all arguments to '+' must be numbers".to_string())
+        );
+        // escaping from the comparator is an error, not a jump out of the sort
+        assert_eq!(
+            eval("(call/cc (lambda (k) (sort (vector 2 1) (lambda (a b) (k 0)))))"),
+            Err("continuation resumed outside of the native call it was captured in".to_string())
+        );
+        // continuations used within a single comparator call are fine
+        assert_eq!(
+            eval("(sort (vector 2 3 1) (lambda (a b) (call/cc (lambda (k) (k (i< a b))))))"),
+            Ok("#(1 2 3)".to_string())
+        );
+        assert_eq!(
+            eval("(sort (list->vector (quote (2 1))) i<)"),
+            Ok("#(1 2)".to_string())
+        );
+    }
+
+    #[test]
+    fn evaluates_vector_literals() {
+        assert_eq!(
+            run("#(1 (a b) \"c\" #(2))"),
+            Ok("#(1 (a b) \"c\" #(2))".to_string())
+        );
+        assert_eq!(run("(vector-ref #(1 2 3) 1)"), Ok("2".to_string()));
+        assert_eq!(run("(vector-length #())"), Ok("0".to_string()));
+        // elements are not evaluated
+        assert_eq!(run("(vector-ref #((+ 1 2)) 0)"), Ok("(+ 1 2)".to_string()));
+    }
+
+    #[test]
+    fn binary_searches_vectors() {
+        let cmp = "(lambda (x key) (- x key))";
+        assert_eq!(
+            run(&format!(
+                "(vector-binary-search (vector 1 3 5 7) 5 {})",
+                cmp
+            )),
+            Ok("2".to_string())
+        );
+        assert_eq!(
+            run(&format!(
+                "(vector-binary-search (vector 1 3 5 7) 4 {})",
+                cmp
+            )),
+            Ok("#f".to_string())
+        );
+        assert_eq!(
+            run("(vector-binary-search (vector 1) 1 (lambda (x key) #t))"),
+            Err(
+                "the comparison procedure given to 'vector-binary-search' must return an integer"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn bitvectors() {
+        let source = "
+            (define a (make-bitvector 70))
+            (define b (make-bitvector 70 #t))
+            (bitvector-set! a 3 #t)
+            (bitvector-set! a 69 #t)
+            (bitvector-set! b 3 #f)";
+        assert_eq!(
+            run(&format!(
+                "{} (vector (bitvector-count a) (bitvector-count b) (bitvector-ref a 69))",
+                source
+            )),
+            Ok("#(2 69 #t)".to_string())
+        );
+        assert_eq!(
+            run(&format!(
+                "{} (vector (bitvector-count (bitvector-and a b))
+                            (bitvector-count (bitvector-or a b))
+                            (bitvector-count (bitvector-xor a b)))",
+                source
+            )),
+            Ok("#(1 70 69)".to_string())
+        );
+        assert_eq!(run("(make-bitvector 4 #t)"), Ok("#*1111".to_string()));
+        assert_eq!(
+            run("(bitvector-ref (make-bitvector 4) 4)"),
+            Err("bitvector-ref: index 4 out of range for bitvector of length 4".to_string())
+        );
+        assert_eq!(
+            run("(bitvector-and (make-bitvector 4) (make-bitvector 5))"),
+            Err("bitvector-and: bitvectors must have the same length, got 4 and 5".to_string())
+        );
+        assert_eq!(
+            run("(make-bitvector 9223372036854775807)"),
+            Err("make-bitvector: length 9223372036854775807 is too large".to_string())
+        );
+    }
+
+    #[test]
+    fn guardians_return_unreachable_objects() {
+        let mut eval = evaluator();
+        eval("(define g (make-guardian)) (define kept (list 1))").unwrap();
+        eval("(guardian-register! g kept) (guardian-register! g (list 2 3))").unwrap();
+        assert_eq!(eval("(guardian-pop! g)"), Ok("#f".to_string()));
+        eval("(gc-run)").unwrap();
+        assert_eq!(eval("(guardian-pop! g)"), Ok("(2 3)".to_string()));
+        assert_eq!(eval("(guardian-pop! g)"), Ok("#f".to_string()));
+        assert_eq!(
+            eval("(guardian-pop! 1)"),
+            Err("the first argument to 'guardian-pop!' must be a guardian".to_string())
+        );
+    }
+
+    #[test]
+    fn queues_are_first_in_first_out() {
+        let source = "
+            (define q (make-queue 1))
+            (enqueue! q 2 3)
+            (define first (dequeue! q))
+            (enqueue! q 4)";
+        assert_eq!(
+            run(&format!("{} (cons first (queue->list q))", source)),
+            Ok("(1 2 3 4)".to_string())
+        );
+        assert_eq!(
+            run(&format!("{} (queue-length q)", source)),
+            Ok("3".to_string())
+        );
+        assert_eq!(
+            run("(dequeue! (make-queue))"),
+            Err("dequeue!: queue is empty".to_string())
+        );
+    }
+
+    #[test]
+    fn heaps_pop_in_order() {
+        let source = "
+            (define h (make-heap i<))
+            (heap-push! h 5)
+            (heap-push! h 1)
+            (heap-push! h 4)
+            (heap-push! h 2)
+            (heap-push! h 3)";
+        assert_eq!(
+            run(&format!(
+                "{} (vector (heap-peek h) (heap-pop! h) (heap-pop! h) (heap-pop! h)
+                            (heap-pop! h) (heap-pop! h) (heap-length h))",
+                source
+            )),
+            Ok("#(1 1 2 3 4 5 0)".to_string())
+        );
+        assert_eq!(
+            run("(heap-pop! (make-heap i<))"),
+            Err("heap-pop!: heap is empty".to_string())
+        );
+    }
+
+    #[test]
+    fn heaps_keep_their_items_when_the_predicate_fails() {
+        let mut eval = evaluator();
+        eval("(define h (make-heap i<)) (heap-push! h 2) (heap-push! h 1)").unwrap();
+        assert_eq!(
+            eval("(heap-push! h (quote a))"),
+            Err("the first argument to 'i<' must be an integer".to_string())
+        );
+        assert_eq!(
+            eval("(vector (heap-length h) (heap-pop! h) (heap-pop! h))"),
+            Ok("#(2 1 2)".to_string())
+        );
+    }
+
+    #[test]
+    fn arrays() {
+        let source = "
+            (define a (make-array (quote (2 3)) 0))
+            (array-set! a 0 1 5)
+            (array-set! a 1 2 7)
+            (array-map! a (lambda (x) (+ x 1)))";
+        assert_eq!(
+            run(&format!("{} a", source)),
+            Ok("#2a((1 6 1) (1 1 8))".to_string())
+        );
+        assert_eq!(
+            run(&format!(
+                "{} (cons (array-ref a 1 2) (array-shape a))",
+                source
+            )),
+            Ok("(8 2 3)".to_string())
+        );
+        assert_eq!(
+            run("(array-ref (make-array (quote (2 3))) 2 0)"),
+            Err("array-ref: indices [2, 0] out of range for array of shape [2, 3]".to_string())
+        );
+        assert_eq!(
+            run("(array-ref (make-array (quote ())) )"),
+            Ok("()".to_string())
+        );
+        assert_eq!(
+            run("(make-array (quote (4294967296 4294967296)))"),
+            Err("make-array: shape [4294967296, 4294967296] is too large".to_string())
+        );
+        assert_eq!(
+            run("(make-array (quote (1000000000000000000)))"),
+            Err("make-array: shape [1000000000000000000] is too large".to_string())
+        );
+    }
+
+    #[test]
+    fn s32vectors() {
+        let source = "
+            (define v (make-s32vector 3 7))
+            (s32vector-set! v 1 (- 0 2))";
+        assert_eq!(
+            run(&format!("{} v", source)),
+            Ok("#s32(7 -2 7)".to_string())
+        );
+        assert_eq!(
+            run(&format!(
+                "{} (equal? (s32vector->list v) (cons 7 (cons (- 0 2) (cons 7 nil))))",
+                source
+            )),
+            Ok("#t".to_string())
+        );
+        assert_eq!(
+            run("(s32vector-length (list->s32vector (quote (1 2))))"),
+            Ok("2".to_string())
+        );
+        assert_eq!(
+            run("(s32vector 1 2147483648)"),
+            Err("s32vector: elements must be 32-bit integers, got 2147483648".to_string())
+        );
+    }
+
+    #[test]
+    fn f64vectors() {
+        let source = "
+            (define v (make-f64vector 3 1/2))
+            (f64vector-set! v 1 -2)";
+        assert_eq!(
+            run(&format!("{} v", source)),
+            Ok("#f64(0.5 -2.0 0.5)".to_string())
+        );
+        assert_eq!(
+            run(&format!("{} (f64vector->list v)", source)),
+            Ok("(0.5 -2.0 0.5)".to_string())
+        );
+        assert_eq!(
+            run(&format!("{} (equal? v (f64vector 0.5 -2.0 0.5))", source)),
+            Ok("#t".to_string())
+        );
+        assert_eq!(
+            run("(f64vector-ref (list->f64vector (quote (1 2.5))) 1)"),
+            Ok("2.5".to_string())
+        );
+        assert_eq!(
+            run("(f64vector-length (make-f64vector 4))"),
+            Ok("4".to_string())
+        );
+        assert_eq!(
+            run("(f64vector 1 #t)"),
+            Err("f64vector: elements must be numbers, got #t".to_string())
+        );
+        assert_eq!(
+            run("(f64vector-ref (f64vector 1) 1)"),
+            Err("f64vector-ref: index 1 out of range for f64vector of length 1".to_string())
+        );
+    }
+
+    #[test]
+    fn works_with_paths() {
+        let joined = Path::new("usr").join("lib").join("libscheme.so.1");
+        assert_eq!(
+            run("(path-join \"usr\" \"lib\" \"libscheme.so.1\")"),
+            Ok(format!("{:?}", joined.to_string_lossy()))
+        );
+        let source = format!("(define p {:?})", joined.to_string_lossy());
+        assert_eq!(
+            run(&format!("{} (path-dirname p)", source)),
+            Ok(format!(
+                "{:?}",
+                Path::new("usr").join("lib").to_string_lossy()
+            ))
+        );
+        assert_eq!(
+            run(&format!(
+                "{} (list (path-basename p) (path-extension p))",
+                source
+            )),
+            Ok("(\"libscheme.so.1\" \"1\")".to_string())
+        );
+        assert_eq!(
+            run("(list (path-dirname \"/\") (path-basename \"/\") (path-extension \"README\"))"),
+            Ok("(#f #f #f)".to_string())
+        );
+        assert_eq!(
+            run("(path-join \"a\" 'b)"),
+            Err("the argument to 'path-join' must be a string".to_string())
+        );
+
+        assert_eq!(
+            run("(expand-user \"notes/~\")"),
+            Ok("\"notes/~\"".to_string())
+        );
+        if let Some(home) = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE")) {
+            let home = PathBuf::from(home);
+            assert_eq!(
+                run("(expand-user \"~\")"),
+                Ok(format!("{:?}", home.to_string_lossy()))
+            );
+            assert_eq!(
+                run(&format!(
+                    "(expand-user {:?})",
+                    Path::new("~").join("notes").to_string_lossy()
+                )),
+                Ok(format!("{:?}", home.join("notes").to_string_lossy()))
+            );
+        }
+
+        let dir = std::env::current_dir().unwrap();
+        assert_eq!(
+            run("(absolute-path \"notes\")"),
+            Ok(format!("{:?}", dir.join("notes").to_string_lossy()))
+        );
+        assert_eq!(
+            run(&format!("(absolute-path {:?})", dir.to_string_lossy())),
+            Ok(format!("{:?}", dir.to_string_lossy()))
+        );
+        assert_eq!(
+            run("(string? (get-environment-variable \"PATH\"))"),
+            Ok("#t".to_string())
+        );
+        assert_eq!(
+            run("(get-environment-variable \"SCHEME_INTERP_RS_UNSET\")"),
+            Ok("#f".to_string())
+        );
+    }
+
+    #[test]
+    fn works_with_dates() {
+        let source = "(define d (string->date \"14/Nov/2023:22:13:20\" \"%d/%b/%Y:%T\"))";
+        assert_eq!(
+            run(&format!("{} d", source)),
+            Ok("<date 2023-11-14T22:13:20Z>".to_string())
+        );
+        assert_eq!(
+            run(&format!(
+                "{} (list (date-year d) (date-month d) (date-day d) (date-hour d) (date-minute d)
+                          (date-second d) (date-week-day d) (date-year-day d) (date->seconds d))",
+                source
+            )),
+            Ok("(2023 11 14 22 13 20 2 318 1700000000)".to_string())
+        );
+        assert_eq!(
+            run("(date->string (seconds->date 1700000000) \"%a %F %T\")"),
+            Ok("\"Tue 2023-11-14 22:13:20\"".to_string())
+        );
+        assert_eq!(
+            run(&format!("{} (equal? d (seconds->date 1700000000))", source)),
+            Ok("#t".to_string())
+        );
+        assert_eq!(
+            run("(list (date? (current-date)) (integer? (current-time)))"),
+            Ok("(#t #t)".to_string())
+        );
+        assert_eq!(
+            run("(< (- (current-time) (date->seconds (current-date))) 2)"),
+            Ok("#t".to_string())
+        );
+        assert_eq!(
+            run("(string->date \"2023-02-30\" \"%F\")"),
+            Err("string->date: day 30 out of range for 2023-02".to_string())
+        );
+        assert_eq!(
+            run("(date-year 2023)"),
+            Err("the first argument to 'date-year' must be a date".to_string())
+        );
+    }
+
+    #[test]
+    fn seeded_randomness_is_reproducible() {
+        use crate::interpreter::{Interpreter, Options};
+
+        let source = "(vector (random 1000) (random 1000) (gensym) (gensym (quote tmp)))";
+        let run_seeded = |seed| {
+            let mut interp = Interpreter::new(Options {
+                seed: Some(seed),
+                ..Options::default()
+            });
+            let res = interp.eval(source).unwrap();
+            interp.to_string(&res)
+        };
+        assert_eq!(run_seeded(7), run_seeded(7));
+        assert_ne!(run_seeded(7), run_seeded(8));
+        assert!(run_seeded(7).ends_with(" g-1 tmp-2)"));
+        assert_eq!(
+            run("(random 0)"),
+            Err("random: bound must be positive, got 0".to_string())
+        );
+    }
+
+    #[test]
+    fn gensym_avoids_existing_symbols() {
+        assert_eq!(
+            run("(list 'g-1 'g-3 (gensym) (gensym) (gensym))"),
+            Ok("(g-1 g-3 g-2 g-4 g-5)".to_string())
+        );
+        assert_eq!(
+            run("(define name (string->symbol \"tmp-1\")) (eq? name (gensym 'tmp))"),
+            Ok("#f".to_string())
+        );
+    }
+
+    #[test]
+    fn runs_are_reproducible() {
+        let source = "
+            (define t (make-hash-table))
+            (hash-table-set! t 1 'a)
+            (hash-table-set! t \"two\" 'b)
+            (hash-table-set! t '(3) 'c)
+            (hash-table-set! t #\\4 'd)
+            (hash-table-set! t 'five 'e)
+            (list (hash-table-keys t) (gensym) (gensym))";
+        let first = run(source).unwrap();
+        for _ in 0..5 {
+            assert_eq!(run(source).unwrap(), first);
+        }
+        assert!(first.ends_with(" g-1 g-2)"));
+    }
+
+    #[test]
+    fn converts_between_strings_and_symbols() {
+        assert_eq!(run("(string->symbol \"abc\")"), Ok("abc".to_string()));
+        assert_eq!(
+            run("(string->symbol \"hello world\")"),
+            Ok("|hello world|".to_string())
+        );
+        assert_eq!(run("(string->symbol \"42\")"), Ok("|42|".to_string()));
+        assert_eq!(run("(string->symbol \"\")"), Ok("||".to_string()));
+        assert_eq!(
+            run("(eq? '|a b| (string->symbol \"a b\"))"),
+            Ok("#t".to_string())
+        );
+        assert_eq!(run("(symbol->string '|a\\|b|)"), Ok("\"a|b\"".to_string()));
+        assert_eq!(run("'|(|"), Ok("|(|".to_string()));
+    }
+
+    #[test]
+    fn keywords_are_distinct_from_symbols() {
+        assert_eq!(run("#:size"), Ok("#:size".to_string()));
+        assert_eq!(run("(keyword? #:size)"), Ok("#t".to_string()));
+        assert_eq!(run("(keyword? 'size)"), Ok("#f".to_string()));
+        assert_eq!(run("(eq? #:size #:size)"), Ok("#t".to_string()));
+        assert_eq!(run("(eq? #:size 'size)"), Ok("#f".to_string()));
+        assert_eq!(
+            run("(eq? (keyword->symbol #:size) 'size)"),
+            Ok("#t".to_string())
+        );
+        assert_eq!(
+            run("(keyword->symbol 'size)"),
+            Err("the argument to 'keyword->symbol' must be a keyword".to_string())
+        );
+    }
+
+    #[test]
+    fn works_with_strings() {
+        assert_eq!(
+            run("\"a\\tb\\\"c\\\"\""),
+            Ok("\"a\\tb\\\"c\\\"\"".to_string())
+        );
+        assert_eq!(
+            run("(string-append \"héllo\" \", \" \"world\")"),
+            Ok("\"héllo, world\"".to_string())
+        );
+        assert_eq!(run("(string-length \"héllo\")"), Ok("5".to_string()));
+        assert_eq!(run("(string? \"\")"), Ok("#t".to_string()));
+        assert_eq!(run("(string? (quote a))"), Ok("#f".to_string()));
+        assert_eq!(
+            run("(equal? \"ab\" (string-append \"a\" \"b\"))"),
+            Ok("#t".to_string())
+        );
+        assert_eq!(
+            run("(eq? \"ab\" (string-append \"a\" \"b\"))"),
+            Ok("#f".to_string())
+        );
+    }
+
+    #[test]
+    fn builds_strings() {
+        let source = "
+            (define sb (make-string-builder))
+            (sb-add! sb \"héllo\" #\\,)
+            (sb-add! sb #\\space \"world\")";
+        assert_eq!(
+            run(&format!("{} (sb->string sb)", source)),
+            Ok("\"héllo, world\"".to_string())
+        );
+        // the string returned is a copy, unaffected by later additions
+        assert_eq!(
+            run(&format!(
+                "{} (define s (sb->string sb)) (sb-add! sb \"!\") (cons s (sb->string sb))",
+                source
+            )),
+            Ok("(\"héllo, world\" . \"héllo, world!\")".to_string())
+        );
+        assert_eq!(
+            run("(sb-add! (make-string-builder) 1)"),
+            Err("the arguments added by 'sb-add!' must be strings or chars".to_string())
+        );
+        assert_eq!(
+            run("(sb->string \"abc\")"),
+            Err("the first argument to 'sb->string' must be a string builder".to_string())
+        );
+    }
 }
@@ -0,0 +1,257 @@
+use crate::interpreter::allocator::{Allocator, Ptr};
+use crate::interpreter::value::Value;
+
+// Part describes one subexpression of a special form
+pub(super) enum Part {
+    // any expression, evaluated by the form
+    Expr(&'static str),
+    // a symbol, e.g. the name bound by define
+    Symbol(&'static str),
+    // a proper list of symbols
+    Params(&'static str),
+    // any datum, left unevaluated
+    Datum(&'static str),
+    // one or more forms, only allowed as the last part
+    Body(&'static str),
+}
+
+// Form is the schema special forms are validated against before evaluation
+pub(super) struct Form {
+    pub(super) name: &'static str,
+    pub(super) parts: &'static [Part],
+}
+
+pub(super) const IF: Form = Form {
+    name: "if",
+    parts: &[
+        Part::Expr("condition"),
+        Part::Expr("then branch"),
+        Part::Expr("else branch"),
+    ],
+};
+
+pub(super) const LAMBDA: Form = Form {
+    name: "lambda",
+    parts: &[Part::Params("parameter list"), Part::Body("body")],
+};
+
+pub(super) const QUOTE: Form = Form {
+    name: "quote",
+    parts: &[Part::Datum("datum")],
+};
+
+pub(super) const QUASIQUOTE: Form = Form {
+    name: "quasiquote",
+    parts: &[Part::Datum("template")],
+};
+
+pub(super) const DEFINE: Form = Form {
+    name: "define",
+    parts: &[Part::Symbol("name"), Part::Expr("value")],
+};
+
+pub(super) const DEFINE_CONSTANT: Form = Form {
+    name: "define-constant",
+    parts: &[Part::Symbol("name"), Part::Expr("value")],
+};
+
+pub(super) const DEFINE_ENUMERATION: Form = Form {
+    name: "define-enumeration",
+    parts: &[
+        Part::Symbol("type name"),
+        Part::Params("member list"),
+        Part::Symbol("predicate name"),
+    ],
+};
+
+pub(super) const COND_EXPAND: Form = Form {
+    name: "cond-expand",
+    parts: &[Part::Body("clauses")],
+};
+
+pub(super) const INCLUDE: Form = Form {
+    name: "include",
+    parts: &[Part::Body("file names")],
+};
+
+// FORMS are all the special forms
+// a special form's keyword can be shadowed by a local binding, such as a lambda parameter,
+// within whose scope the name is an ordinary variable
+// top-level definitions do not shadow special forms
+pub(super) const FORMS: &[&Form] = &[
+    &IF,
+    &LAMBDA,
+    &QUOTE,
+    &QUASIQUOTE,
+    &DEFINE,
+    &DEFINE_CONSTANT,
+    &DEFINE_ENUMERATION,
+    &COND_EXPAND,
+    &INCLUDE,
+];
+
+// is_special_form reports whether name is the keyword of a special form
+pub(super) fn is_special_form(name: &str) -> bool {
+    FORMS.iter().any(|form| form.name == name)
+}
+
+impl Part {
+    fn description(&self) -> &'static str {
+        match self {
+            Part::Expr(d) | Part::Symbol(d) | Part::Params(d) | Part::Datum(d) | Part::Body(d) => d,
+        }
+    }
+
+    fn check(&self, alloc: &Allocator, node: Ptr<Value>) -> Result<(), String> {
+        match self {
+            Part::Expr(_) | Part::Datum(_) | Part::Body(_) => Ok(()),
+            Part::Symbol(d) => match alloc.get_val(node) {
+                Value::Symbol(_) => Ok(()),
+                val => Err(format!(
+                    "{} must be a symbol, got {}",
+                    d,
+                    val.to_string(alloc)
+                )),
+            },
+            Part::Params(d) => {
+                let mut ptr = node;
+                loop {
+                    match alloc.get_val(ptr) {
+                        Value::Nil => return Ok(()),
+                        Value::Cons(hd, tl) => {
+                            if let Value::Symbol(_) = alloc.get_val(*hd) {
+                                ptr = *tl;
+                                continue;
+                            }
+                            return Err(format!(
+                                "{} must only contain symbols, got {}",
+                                d,
+                                alloc.get_val(*hd).to_string(alloc)
+                            ));
+                        }
+                        _ => {
+                            return Err(format!(
+                                "{} must be a list of symbols, got {}",
+                                d,
+                                alloc.get_val(node).to_string(alloc)
+                            ))
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Form {
+    // validate checks the subexpressions of a use of the form, excluding its keyword
+    pub(super) fn validate(&self, alloc: &Allocator, args: &[Ptr<Value>]) -> Result<(), String> {
+        let expected = self.parts.len();
+        let has_body = matches!(self.parts.last(), Some(Part::Body(_)));
+        let wrong_count = if has_body {
+            args.len() < expected
+        } else {
+            args.len() != expected
+        };
+        if wrong_count {
+            let mut message = format!(
+                "{}: expected {}{} subexpression{}, got {}",
+                self.name,
+                if has_body { "at least " } else { "" },
+                expected,
+                if expected == 1 { "" } else { "s" },
+                args.len()
+            );
+            if args.len() < expected {
+                let missing = self.parts[args.len()..]
+                    .iter()
+                    .map(Part::description)
+                    .collect::<Vec<_>>();
+                message.push_str(&format!("; missing {}", missing.join(" and ")));
+            }
+            return Err(message);
+        }
+
+        for (part, &arg) in self.parts.iter().zip(args) {
+            part.check(alloc, arg)
+                .map_err(|err| format!("{}: {}", self.name, err))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::interpreter::test::run;
+
+    #[test]
+    fn reports_missing_parts() {
+        assert_eq!(
+            run("(if #t 1)"),
+            Err("if: expected 3 subexpressions, got 2; missing else branch".to_string())
+        );
+        assert_eq!(
+            run("(lambda)"),
+            Err(
+                "lambda: expected at least 2 subexpressions, got 0; missing parameter list and body"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            run("(quote 1 2)"),
+            Err("quote: expected 1 subexpression, got 2".to_string())
+        );
+    }
+
+    #[test]
+    fn reports_malformed_parts() {
+        assert_eq!(
+            run("(define 1 2)"),
+            Err("define: name must be a symbol, got 1".to_string())
+        );
+        assert_eq!(
+            run("(lambda (x 1) x)"),
+            Err("lambda: parameter list must only contain symbols, got 1".to_string())
+        );
+    }
+
+    #[test]
+    fn lists_special_forms() {
+        assert_eq!(
+            run("(special-forms)"),
+            Ok(
+                "(if lambda quote quasiquote define define-constant define-enumeration cond-expand include)"
+                    .to_string()
+            )
+        );
+        assert_eq!(run("(special-form? 'if)"), Ok("#t".to_string()));
+        assert_eq!(run("(special-form? 'car)"), Ok("#f".to_string()));
+    }
+
+    #[test]
+    fn local_bindings_shadow_special_forms() {
+        assert_eq!(
+            run("((lambda (if) (if 1 2 3)) (lambda (a b c) c))"),
+            Ok("3".to_string())
+        );
+        assert_eq!(
+            run("((lambda (quote) (quote 1)) (lambda (x) (+ x 1)))"),
+            Ok("2".to_string())
+        );
+        assert_eq!(
+            run("((lambda (lambda) (lambda 1 2)) (lambda (a b) (+ a b)))"),
+            Ok("3".to_string())
+        );
+        assert_eq!(
+            run("((lambda (define) (define 5)) (lambda (x) (* x 2)))"),
+            Ok("10".to_string())
+        );
+        // the keyword is special again outside the binding's scope
+        assert_eq!(run("((lambda (if) if) 1) (if #f 1 2)"), Ok("2".to_string()));
+    }
+
+    #[test]
+    fn accepts_empty_parameter_list() {
+        assert_eq!(run("((lambda () 1))"), Ok("1".to_string()));
+    }
+}
@@ -1,28 +1,334 @@
 use super::allocator::{Allocator, Environment, Ptr};
-use crate::interpreter::{Interpreter, Step};
-use crate::parse::AST;
+use super::date::Date;
+use super::number::{format_float, Number};
+use crate::interpreter::{Frame, Interpreter, Step};
+use crate::lex::{self, Token, CHAR_NAMES};
+use crate::parse::{Node, AST};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::hash::{BuildHasherDefault, Hash, Hasher};
 
 pub struct Function {
+    // the name the function was first defined as, None for anonymous functions
+    pub(super) name: Option<String>,
     pub(super) args: Vec<String>,
     pub(super) env: Ptr<Environment>,
     pub(super) body: Ptr<Value>,
 }
 
+// Arity is the range of argument counts a procedure accepts
+// max is None for variadic procedures
+#[derive(Clone, Copy)]
+pub struct Arity {
+    pub(super) min: usize,
+    pub(super) max: Option<usize>,
+}
+
+impl Arity {
+    pub fn exactly(n: usize) -> Self {
+        Arity {
+            min: n,
+            max: Some(n),
+        }
+    }
+
+    pub fn at_least(n: usize) -> Self {
+        Arity { min: n, max: None }
+    }
+
+    pub fn between(min: usize, max: usize) -> Self {
+        Arity {
+            min,
+            max: Some(max),
+        }
+    }
+
+    pub(super) fn accepts(&self, n: usize) -> bool {
+        n >= self.min && self.max.is_none_or(|max| n <= max)
+    }
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.max {
+            Some(max) if max == self.min => write!(f, "{}", max),
+            Some(max) => write!(f, "{} to {}", self.min, max),
+            None => write!(f, "at least {}", self.min),
+        }
+    }
+}
+
+pub type NativeFn = fn(&mut Interpreter, Ptr<Environment>, &[Ptr<Value>]);
+
+pub struct NativeFunction {
+    pub(super) name: String,
+    pub(super) func: NativeFn,
+    pub(super) arity: Arity,
+}
+
 pub struct Continuation {
     pub(super) next_steps: Vec<Step>,
     pub(super) results: Vec<Ptr<Value>>,
     pub(super) saved_results: Vec<Vec<Ptr<Value>>>,
+    // the active calls, restored for backtraces when the continuation is invoked
+    pub(super) frames: Vec<Frame>,
+    // a one-shot continuation gives up its state when first invoked
+    pub(super) one_shot: bool,
+    pub(super) invoked: bool,
+    // the Interpreter::call nesting depth the continuation was captured at
+    pub(super) depth: usize,
+}
+
+impl Continuation {
+    // describe summarizes the captured state, listing pending steps from the next to run
+    pub(super) fn describe(&self, alloc: &Allocator) -> String {
+        if self.one_shot && self.invoked {
+            return "one-shot continuation, already invoked".to_string();
+        }
+
+        let mut out = format!(
+            "continuation at call depth {}: {} pending steps, {} results, {} saved result stacks",
+            self.depth,
+            self.next_steps.len(),
+            self.results.len(),
+            self.saved_results.len()
+        );
+        for (i, step) in self.next_steps.iter().rev().enumerate() {
+            out.push_str(&format!("\n  {}: {}", i, step.kind.to_string(alloc)));
+        }
+        out
+    }
+}
+
+// Guardian holds registered objects weakly, handing each back once it becomes unreachable
+// so a program can release the resources it refers to
+#[derive(Default)]
+pub struct Guardian {
+    pub(super) registered: Vec<Ptr<Value>>,
+    // collected objects, kept alive until they are popped
+    pub(super) ready: VecDeque<Ptr<Value>>,
+}
+
+// Set holds values distinct under equal?, in insertion order
+pub struct Set {
+    pub(super) items: Vec<Ptr<Value>>,
+    // maps the structural hash of an item to its indices in items
+    index: HashMap<u64, Vec<usize>>,
+}
+
+impl Set {
+    pub(super) fn new() -> Self {
+        Set {
+            items: Vec::new(),
+            index: HashMap::new(),
+        }
+    }
+
+    pub(super) fn contains(&self, alloc: &Allocator, hash: u64, item: Ptr<Value>) -> bool {
+        match self.index.get(&hash) {
+            Some(indices) => indices.iter().any(|&i| equal(alloc, self.items[i], item)),
+            None => false,
+        }
+    }
+
+    // insert adds item, which must not already be a member of the set
+    pub(super) fn insert(&mut self, hash: u64, item: Ptr<Value>) {
+        self.index.entry(hash).or_default().push(self.items.len());
+        self.items.push(item);
+    }
+}
+
+// Comparator pairs an equality predicate with a hash function consistent with it
+// without a hash function every key hashes alike, so lookups compare against each key
+pub struct Comparator {
+    pub(super) equality: Ptr<Value>,
+    pub(super) hash: Option<Ptr<Value>>,
+}
+
+// key/value pairs whose keys share a hash
+type Bucket = Vec<(Ptr<Value>, Ptr<Value>)>;
+
+// buckets are hashed without a random key, so that tables iterate in the same order every run
+type Buckets = HashMap<u64, Bucket, BuildHasherDefault<DefaultHasher>>;
+
+// HashTable maps keys to values, comparing keys with its comparator
+// a key is hashed once, when inserted: mutating a key compared with equal? afterwards leaves
+// it under the hash of its old contents, so it may no longer be found
+// eq? hashes keys by identity, which stays valid since the collector never moves objects,
+// and the table keeps its keys and values alive
+pub struct HashTable {
+    pub(super) comparator: Ptr<Value>,
+    // maps the hash of a key to its entries
+    pub(super) buckets: Buckets,
+    pub(super) len: usize,
+}
+
+impl HashTable {
+    pub(super) fn new(comparator: Ptr<Value>) -> Self {
+        HashTable {
+            comparator,
+            buckets: Buckets::default(),
+            len: 0,
+        }
+    }
+
+    pub(super) fn entries(&self) -> impl Iterator<Item = &(Ptr<Value>, Ptr<Value>)> {
+        self.buckets.values().flatten()
+    }
+}
+
+// Bitvector is a fixed length sequence of bits, packed into words
+#[derive(Clone, PartialEq, Hash)]
+pub struct Bitvector {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl Bitvector {
+    // new returns a bitvector of len bits set to fill, or None if it can't be allocated
+    pub(super) fn new(len: usize, fill: bool) -> Option<Self> {
+        let mut words = Vec::new();
+        words.try_reserve_exact(len.div_ceil(64)).ok()?;
+        words.resize(len.div_ceil(64), if fill { !0 } else { 0 });
+        let mut bv = Bitvector { words, len };
+        bv.clear_unused();
+        Some(bv)
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(super) fn get(&self, i: usize) -> bool {
+        self.words[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    pub(super) fn set(&mut self, i: usize, bit: bool) {
+        if bit {
+            self.words[i / 64] |= 1 << (i % 64);
+        } else {
+            self.words[i / 64] &= !(1 << (i % 64));
+        }
+    }
+
+    pub(super) fn count(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    // zip combines two bitvectors of the same length word by word
+    pub(super) fn zip(&self, other: &Bitvector, op: fn(u64, u64) -> u64) -> Bitvector {
+        let mut bv = Bitvector {
+            words: self
+                .words
+                .iter()
+                .zip(&other.words)
+                .map(|(a, b)| op(*a, *b))
+                .collect(),
+            len: self.len,
+        };
+        bv.clear_unused();
+        bv
+    }
+
+    // clear_unused zeroes the bits of the last word past the end,
+    // so counting and comparison can work on whole words
+    fn clear_unused(&mut self) {
+        if !self.len.is_multiple_of(64) {
+            if let Some(last) = self.words.last_mut() {
+                *last &= (1 << (self.len % 64)) - 1;
+            }
+        }
+    }
+}
+
+// Heap is a binary min-heap ordered by a Scheme less-than predicate
+pub struct Heap {
+    pub(super) less: Ptr<Value>,
+    pub(super) items: Vec<Ptr<Value>>,
+}
+
+// Array is a multidimensional array, stored contiguously in row-major order
+pub struct Array {
+    pub(super) shape: Vec<usize>,
+    pub(super) items: Vec<Ptr<Value>>,
+}
+
+impl Array {
+    // offset returns the position in items of the element at indices, if they're in bounds
+    pub(super) fn offset(&self, indices: &[i64]) -> Option<usize> {
+        if indices.len() != self.shape.len() {
+            return None;
+        }
+        let mut offset = 0;
+        for (&i, &dim) in indices.iter().zip(&self.shape) {
+            if i < 0 || i as usize >= dim {
+                return None;
+            }
+            offset = offset * dim + i as usize;
+        }
+        Some(offset)
+    }
+
+    // to_string prints the array as nested lists prefixed by its rank, e.g. #2a((1 2) (3 4))
+    fn to_string(&self, alloc: &Allocator) -> String {
+        fn write(alloc: &Allocator, shape: &[usize], items: &[Ptr<Value>], out: &mut String) {
+            match shape.split_first() {
+                None => out.push_str(&alloc.get_val(items[0]).to_string(alloc)),
+                Some((&dim, rest)) => {
+                    let stride = rest.iter().product::<usize>();
+                    out.push('(');
+                    for i in 0..dim {
+                        if i > 0 {
+                            out.push(' ');
+                        }
+                        write(alloc, rest, &items[i * stride..(i + 1) * stride], out);
+                    }
+                    out.push(')');
+                }
+            }
+        }
+
+        let mut out = format!("#{}a", self.shape.len());
+        write(alloc, &self.shape, &self.items, &mut out);
+        out
+    }
 }
 
-pub(super) enum Value {
+pub enum Value {
     Integer(i64),
+    Float(f64),
+    // numerator and denominator in lowest terms, the denominator greater than 1
+    Rational(i64, i64),
     Bool(bool),
     Function(Function),
-    NativeFunction(fn(&mut Interpreter, Ptr<Environment>, &[Ptr<Value>])),
+    NativeFunction(NativeFunction),
     Symbol(String),
+    // keywords evaluate to themselves and are compared by name, like symbols
+    Keyword(String),
     Nil,
     Cons(Ptr<Value>, Ptr<Value>),
     Continuation(Continuation),
+    Set(Set),
+    Comparator(Comparator),
+    HashTable(HashTable),
+    Vector(Vec<Ptr<Value>>),
+    Bitvector(Bitvector),
+    Queue(VecDeque<Ptr<Value>>),
+    // a mutable string which is appended to in place
+    StringBuilder(String),
+    Guardian(Guardian),
+    Heap(Heap),
+    Array(Array),
+    S32Vector(Vec<i32>),
+    F64Vector(Vec<f64>),
+    Date(Date),
+    Bytevector(Vec<u8>),
+    String(String),
+    Char(char),
 }
 
 impl Value {
@@ -30,40 +336,712 @@ impl Value {
         alloc.new_val(self)
     }
 
-    pub(super) fn from_ast(node: AST, alloc: &mut Allocator) -> Ptr<Self> {
-        match node {
+    // from_ast converts a parsed node to a value, recording where each part was read from
+    pub(super) fn from_ast(node: Node, alloc: &mut Allocator) -> Ptr<Self> {
+        let mut labels = Labels::default();
+        let val = Value::from_ast_with(node, alloc, &mut labels);
+        if !labels.targets.is_empty() {
+            resolve_labels(alloc, val, &labels.targets, &mut HashSet::new());
+        }
+        val
+    }
+
+    fn from_ast_with(node: Node, alloc: &mut Allocator, labels: &mut Labels) -> Ptr<Self> {
+        let val = match node.ast {
             AST::Symbol(s) => Value::Symbol(s).gc(alloc),
+            AST::Keyword(s) => Value::Keyword(s).gc(alloc),
             AST::Integer(i) => Value::Integer(i).gc(alloc),
+            AST::Float(f) => Value::Float(f).gc(alloc),
+            AST::Rational(num, den) => Number::rational(num.into(), den.into())
+                .into_value()
+                .gc(alloc),
             AST::Bool(b) => Value::Bool(b).gc(alloc),
+            AST::String(s) => Value::String(s).gc(alloc),
+            AST::Char(c) => Value::Char(c).gc(alloc),
             AST::List(l) => {
-                let mut res = Value::Nil.gc(alloc);
-                let mut iter = l.into_iter();
-                while let Some(entry) = iter.next_back() {
-                    res = Value::Cons(Value::from_ast(entry, alloc), res).gc(alloc);
-                }
-                res
+                // entries are read in order so labels are defined before they are referred to
+                let entries = l
+                    .into_iter()
+                    .map(|entry| Value::from_ast_with(entry, alloc, labels))
+                    .collect::<Vec<_>>();
+                Value::from_vec(&entries, alloc)
             }
+            AST::Vector(v) => {
+                let items = v
+                    .into_iter()
+                    .map(|entry| Value::from_ast_with(entry, alloc, labels))
+                    .collect();
+                Value::Vector(items).gc(alloc)
+            }
+            AST::Bytevector(bytes) => Value::Bytevector(bytes).gc(alloc),
+            AST::Labeled(label, datum) => {
+                // references within the datum are read before it exists,
+                // so they are read as a placeholder replaced once the datum is built
+                let placeholder = Value::Nil.gc(alloc);
+                labels.placeholders.insert(label, placeholder);
+                let datum = Value::from_ast_with(*datum, alloc, labels);
+                labels.targets.insert(placeholder, datum);
+                return datum;
+            }
+            // the parser only allows references to labels defined earlier
+            AST::LabelRef(label) => return labels.placeholders[&label],
+        };
+        alloc.set_location(val, node.location);
+        val
+    }
+
+    // from_vec builds a proper list of items
+    pub(super) fn from_vec(items: &[Ptr<Value>], alloc: &mut Allocator) -> Ptr<Self> {
+        let mut res = Value::Nil.gc(alloc);
+        for item in items.iter().rev() {
+            res = Value::Cons(*item, res).gc(alloc);
+        }
+        res
+    }
+
+    // type_name describes the kind of value, e.g. integer or pair
+    pub(super) fn type_name(&self) -> &'static str {
+        match self {
+            Value::Integer(_) => "integer",
+            Value::Float(_) => "float",
+            Value::Rational(_, _) => "rational",
+            Value::Bool(_) => "boolean",
+            Value::Function(_) | Value::NativeFunction(_) => "procedure",
+            Value::Symbol(_) => "symbol",
+            Value::Keyword(_) => "keyword",
+            Value::Nil => "empty list",
+            Value::Cons(_, _) => "pair",
+            Value::Continuation(_) => "continuation",
+            Value::Set(_) => "set",
+            Value::Comparator(_) => "comparator",
+            Value::HashTable(_) => "hash table",
+            Value::Vector(_) => "vector",
+            Value::Bitvector(_) => "bitvector",
+            Value::Queue(_) => "queue",
+            Value::StringBuilder(_) => "string builder",
+            Value::Guardian(_) => "guardian",
+            Value::Heap(_) => "heap",
+            Value::Array(_) => "array",
+            Value::S32Vector(_) => "s32vector",
+            Value::F64Vector(_) => "f64vector",
+            Value::Date(_) => "date",
+            Value::Bytevector(_) => "bytevector",
+            Value::String(_) => "string",
+            Value::Char(_) => "char",
         }
     }
 
     pub(super) fn to_string(&self, alloc: &Allocator) -> String {
         match self {
             Value::Integer(i) => i.to_string(),
+            Value::Float(f) => format_float(*f),
+            Value::Rational(num, den) => format!("{}/{}", num, den),
             Value::Bool(b) => (if *b { "#t" } else { "#f" }).to_string(),
-            Value::Function(_f) => "<lisp function>".to_string(),
-            Value::NativeFunction(_f) => "<native function>".to_string(),
-            Value::Symbol(s) => s.clone(),
+            Value::Function(f) => match &f.name {
+                Some(name) => format!("#<procedure {} ({})>", name, f.args.join(" ")),
+                None => format!("#<procedure ({})>", f.args.join(" ")),
+            },
+            Value::NativeFunction(f) => format!("#<builtin {}>", f.name),
+            Value::Symbol(s) => write_symbol(s),
+            Value::Keyword(s) => format!("#:{}", s),
+            Value::String(s) => write_string(s),
+            Value::Char(c) => write_char(*c),
             Value::Nil => "()".to_string(),
-            Value::Cons(a, b) => format!(
-                "({} . {})",
-                alloc.get_val(*a).to_string(alloc),
-                alloc.get_val(*b).to_string(alloc)
-            ),
+            Value::Cons(_, _) | Value::Vector(_) => Printer::new(alloc, self).write(self),
             Value::Continuation(_c) => "<continuation>".to_string(),
+            Value::Set(_s) => "<set>".to_string(),
+            Value::Comparator(_c) => "<comparator>".to_string(),
+            Value::HashTable(_t) => "<hash-table>".to_string(),
+            Value::Bitvector(bv) => format!(
+                "#*{}",
+                (0..bv.len())
+                    .map(|i| if bv.get(i) { '1' } else { '0' })
+                    .collect::<String>()
+            ),
+            Value::Queue(_q) => "<queue>".to_string(),
+            Value::StringBuilder(_sb) => "<string-builder>".to_string(),
+            Value::Guardian(_g) => "<guardian>".to_string(),
+            Value::Heap(_h) => "<heap>".to_string(),
+            Value::Array(array) => array.to_string(alloc),
+            Value::Date(date) => format!("<date {}>", date),
+            Value::S32Vector(items) => format!(
+                "#s32({})",
+                items
+                    .iter()
+                    .map(|i| i.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            Value::F64Vector(items) => format!(
+                "#f64({})",
+                items
+                    .iter()
+                    .map(|f| format_float(*f))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            Value::Bytevector(bytes) => format!(
+                "#u8({})",
+                bytes
+                    .iter()
+                    .map(|b| b.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+        }
+    }
+}
+
+// write_string quotes s, escaping it so that it reads back as the same string
+fn write_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for chr in s.chars() {
+        match chr {
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            _ => out.push(chr),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// write_symbol writes the symbol named s, between bars if it would not read back as
+// the same symbol otherwise
+fn write_symbol(s: &str) -> String {
+    match lex::tokenize(s).as_deref() {
+        Ok([token]) if token.token == Token::Symbol(s.to_string()) => s.to_string(),
+        _ => {
+            let mut out = String::with_capacity(s.len() + 2);
+            out.push('|');
+            for chr in s.chars() {
+                match chr {
+                    '\n' => out.push_str("\\n"),
+                    '\t' => out.push_str("\\t"),
+                    '\\' => out.push_str("\\\\"),
+                    '|' => out.push_str("\\|"),
+                    _ if chr.is_control() => out.push_str(&format!("\\x{:x};", chr as u32)),
+                    _ => out.push(chr),
+                }
+            }
+            out.push('|');
+            out
+        }
+    }
+}
+
+// write_char writes c as a character literal
+fn write_char(c: char) -> String {
+    match CHAR_NAMES.iter().find(|(_, chr)| *chr == c) {
+        Some((name, _)) => format!("#\\{}", name),
+        None if c.is_control() => format!("#\\x{:x}", c as u32),
+        None => format!("#\\{}", c),
+    }
+}
+
+// list_items returns the elements of the proper list at ptr
+pub(super) fn list_items(alloc: &Allocator, ptr: Ptr<Value>) -> Option<Vec<Ptr<Value>>> {
+    let mut items = Vec::new();
+    let mut list = ptr;
+    loop {
+        match alloc.get_val(list) {
+            Value::Cons(hd, tl) => {
+                items.push(*hd);
+                list = *tl;
+            }
+            Value::Nil => return Some(items),
+            _ => return None,
+        }
+    }
+}
+
+// eq reports whether a and b are the same object
+// atoms which are not individually identifiable compare by value
+pub(super) fn eq(alloc: &Allocator, a: Ptr<Value>, b: Ptr<Value>) -> bool {
+    if a == b {
+        return true;
+    }
+
+    match (alloc.get_val(a), alloc.get_val(b)) {
+        (Value::Integer(i1), Value::Integer(i2)) => i1 == i2,
+        (Value::Float(f1), Value::Float(f2)) => f1.to_bits() == f2.to_bits(),
+        (Value::Rational(n1, d1), Value::Rational(n2, d2)) => (n1, d1) == (n2, d2),
+        (Value::Bool(b1), Value::Bool(b2)) => b1 == b2,
+        (Value::Char(c1), Value::Char(c2)) => c1 == c2,
+        (Value::Symbol(s1), Value::Symbol(s2)) => s1 == s2,
+        (Value::Keyword(s1), Value::Keyword(s2)) => s1 == s2,
+        (Value::Nil, Value::Nil) => true,
+        _ => false,
+    }
+}
+
+// equal reports whether a and b are structurally equal
+// procedures, continuations, sets and hash tables are only equal to themselves
+// cyclic data is equal when its unfoldings are: pairs of pairs and vectors already being
+// compared are assumed equal, and the comparison keeps its own stack for long lists
+pub(super) fn equal(alloc: &Allocator, a: Ptr<Value>, b: Ptr<Value>) -> bool {
+    let mut compared = HashSet::new();
+    let mut stack = vec![(a, b)];
+    while let Some((a, b)) = stack.pop() {
+        if a == b {
+            continue;
+        }
+        let same = match (alloc.get_val(a), alloc.get_val(b)) {
+            (Value::Integer(i1), Value::Integer(i2)) => i1 == i2,
+            (Value::Float(f1), Value::Float(f2)) => f1.to_bits() == f2.to_bits(),
+            (Value::Rational(n1, d1), Value::Rational(n2, d2)) => (n1, d1) == (n2, d2),
+            (Value::Bool(b1), Value::Bool(b2)) => b1 == b2,
+            (Value::Char(c1), Value::Char(c2)) => c1 == c2,
+            (Value::Symbol(s1), Value::Symbol(s2)) => s1 == s2,
+            (Value::Keyword(s1), Value::Keyword(s2)) => s1 == s2,
+            (Value::String(s1), Value::String(s2)) => s1 == s2,
+            (Value::Nil, Value::Nil) => true,
+            (Value::Cons(hd1, tl1), Value::Cons(hd2, tl2)) => {
+                if compared.insert((a, b)) {
+                    stack.push((*tl1, *tl2));
+                    stack.push((*hd1, *hd2));
+                }
+                true
+            }
+            (Value::Bitvector(bv1), Value::Bitvector(bv2)) => bv1 == bv2,
+            (Value::S32Vector(items1), Value::S32Vector(items2)) => items1 == items2,
+            (Value::Date(d1), Value::Date(d2)) => d1 == d2,
+            (Value::F64Vector(items1), Value::F64Vector(items2)) => {
+                items1.len() == items2.len()
+                    && items1
+                        .iter()
+                        .zip(items2)
+                        .all(|(f1, f2)| f1.to_bits() == f2.to_bits())
+            }
+            (Value::Bytevector(bytes1), Value::Bytevector(bytes2)) => bytes1 == bytes2,
+            (Value::Vector(items1), Value::Vector(items2)) => {
+                if items1.len() != items2.len() {
+                    false
+                } else {
+                    if compared.insert((a, b)) {
+                        stack.extend(items1.iter().copied().zip(items2.iter().copied()).rev());
+                    }
+                    true
+                }
+            }
+            _ => false,
+        };
+        if !same {
+            return false;
+        }
+    }
+    true
+}
+
+// the number of pairs and values hash looks at, which bounds the time taken by long lists
+// and keeps cyclic data finite
+const HASH_BUDGET: usize = 64;
+
+// hash computes a hash consistent with equal
+// it walks the first HASH_BUDGET values of the unfolding of ptr, which equal data shares
+pub(super) fn hash(alloc: &Allocator, ptr: Ptr<Value>) -> u64 {
+    let mut state = DefaultHasher::new();
+    let mut stack = vec![ptr];
+    let mut budget = HASH_BUDGET;
+    while let Some(ptr) = stack.pop() {
+        if budget == 0 {
+            break;
+        }
+        budget -= 1;
+        match alloc.get_val(ptr) {
+            Value::Integer(i) => (0, i).hash(&mut state),
+            Value::Bool(b) => (1, b).hash(&mut state),
+            Value::Symbol(s) => (2, s).hash(&mut state),
+            Value::Nil => 3.hash(&mut state),
+            Value::Cons(hd, tl) => {
+                4.hash(&mut state);
+                stack.push(*tl);
+                stack.push(*hd);
+            }
+            Value::Bitvector(bv) => (7, bv).hash(&mut state),
+            Value::S32Vector(items) => (8, items).hash(&mut state),
+            Value::String(s) => (9, s).hash(&mut state),
+            Value::Float(f) => (10, f.to_bits()).hash(&mut state),
+            Value::Char(c) => (11, c).hash(&mut state),
+            Value::Rational(num, den) => (12, num, den).hash(&mut state),
+            Value::Bytevector(bytes) => (13, bytes).hash(&mut state),
+            Value::Keyword(s) => (14, s).hash(&mut state),
+            Value::Date(date) => (16, date).hash(&mut state),
+            Value::F64Vector(items) => {
+                (15, items.len()).hash(&mut state);
+                for f in items {
+                    f.to_bits().hash(&mut state);
+                }
+            }
+            Value::Vector(items) => {
+                (6, items.len()).hash(&mut state);
+                stack.extend(items.iter().rev());
+            }
+            _ => (5, ptr).hash(&mut state),
+        }
+    }
+    state.finish()
+}
+
+// eq_hash computes a hash consistent with eq
+pub(super) fn eq_hash(alloc: &Allocator, ptr: Ptr<Value>) -> u64 {
+    match alloc.get_val(ptr) {
+        Value::Integer(_)
+        | Value::Float(_)
+        | Value::Rational(_, _)
+        | Value::Bool(_)
+        | Value::Char(_)
+        | Value::Symbol(_)
+        | Value::Keyword(_)
+        | Value::Nil => hash(alloc, ptr),
+        _ => {
+            let mut state = DefaultHasher::new();
+            ptr.hash(&mut state);
+            state.finish()
+        }
+    }
+}
+
+// Printer writes pairs and vectors, labelling those which are part of a cycle
+// with #n= where they first appear and #n# where they recur, so cyclic data prints finitely
+// values are identified by their address, which is fixed while the allocator is borrowed
+struct Printer<'a> {
+    alloc: &'a Allocator,
+    cyclic: HashSet<*const Value>,
+    labels: HashMap<*const Value, usize>,
+}
+
+impl<'a> Printer<'a> {
+    fn new(alloc: &'a Allocator, root: &Value) -> Self {
+        let mut cyclic = HashSet::new();
+        find_cycles(alloc, root, &mut cyclic);
+        Printer {
+            alloc,
+            cyclic,
+            labels: HashMap::new(),
+        }
+    }
+
+    fn write(&mut self, val: &Value) -> String {
+        let alloc = self.alloc;
+        let key = val as *const Value;
+        let mut prefix = String::new();
+        if self.cyclic.contains(&key) {
+            if let Some(label) = self.labels.get(&key) {
+                return format!("#{}#", label);
+            }
+            let label = self.labels.len();
+            self.labels.insert(key, label);
+            prefix = format!("#{}=", label);
+        }
+
+        let body = match val {
+            // a proper list is written as its elements, an improper tail after a dot
+            // a labeled pair in the tail is written after a dot, so its label has a datum
+            Value::Cons(hd, tl) => {
+                let mut items = vec![self.write(alloc.get_val(*hd))];
+                let mut tail = *tl;
+                loop {
+                    let val = alloc.get_val(tail);
+                    match val {
+                        Value::Nil => break,
+                        Value::Cons(hd, tl) if !self.cyclic.contains(&(val as *const Value)) => {
+                            items.push(self.write(alloc.get_val(*hd)));
+                            tail = *tl;
+                        }
+                        _ => {
+                            items.push(".".to_string());
+                            items.push(self.write(val));
+                            break;
+                        }
+                    }
+                }
+                format!("({})", items.join(" "))
+            }
+            Value::Vector(items) => format!(
+                "#({})",
+                items
+                    .iter()
+                    .map(|item| self.write(alloc.get_val(*item)))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            _ => val.to_string(alloc),
+        };
+        prefix + &body
+    }
+}
+
+// find_cycles adds the pairs and vectors reachable from root which close a cycle to cyclic
+// the search keeps its own stack, so that long lists do not overflow the native one
+fn find_cycles(alloc: &Allocator, root: &Value, cyclic: &mut HashSet<*const Value>) {
+    enum Visit<'a> {
+        Enter(&'a Value),
+        Leave(*const Value),
+    }
+
+    // the values being visited, and those already visited
+    let mut path = HashSet::new();
+    let mut seen = HashSet::new();
+    let mut stack = vec![Visit::Enter(root)];
+    while let Some(visit) = stack.pop() {
+        let val = match visit {
+            Visit::Enter(val) => val,
+            Visit::Leave(key) => {
+                path.remove(&key);
+                continue;
+            }
+        };
+        let key = val as *const Value;
+        if path.contains(&key) {
+            cyclic.insert(key);
+            continue;
+        }
+        if !seen.insert(key) {
+            continue;
+        }
+
+        let children = match val {
+            Value::Cons(hd, tl) => vec![*hd, *tl],
+            Value::Vector(items) => items.clone(),
+            _ => continue,
+        };
+        path.insert(key);
+        stack.push(Visit::Leave(key));
+        for child in children.into_iter().rev() {
+            stack.push(Visit::Enter(alloc.get_val(child)));
+        }
+    }
+}
+
+// Labels tracks the datum labels read while converting a node to a value
+#[derive(Default)]
+struct Labels {
+    // the placeholder read for references to each label
+    placeholders: HashMap<u64, Ptr<Value>>,
+    // the labelled datum each placeholder stands for
+    targets: HashMap<Ptr<Value>, Ptr<Value>>,
+}
+
+// resolve_labels replaces the label placeholders in the pairs and vectors reachable from ptr
+fn resolve_labels(
+    alloc: &mut Allocator,
+    ptr: Ptr<Value>,
+    targets: &HashMap<Ptr<Value>, Ptr<Value>>,
+    seen: &mut HashSet<Ptr<Value>>,
+) {
+    if !seen.insert(ptr) {
+        return;
+    }
+    let children = match alloc.get_val(ptr) {
+        Value::Cons(hd, tl) => vec![*hd, *tl],
+        Value::Vector(items) => items.clone(),
+        _ => return,
+    };
+    let children = children
+        .into_iter()
+        .map(|child| *targets.get(&child).unwrap_or(&child))
+        .collect::<Vec<_>>();
+    match alloc.get_val_mut(ptr) {
+        Value::Cons(hd, tl) => {
+            *hd = children[0];
+            *tl = children[1];
+        }
+        Value::Vector(items) => items.clone_from(&children),
+        _ => unreachable!(),
+    }
+    for child in children {
+        resolve_labels(alloc, child, targets, seen);
+    }
+}
+
+// shallow_copy duplicates a mutable container other than a pair or set,
+// sharing its contents with the original
+fn shallow_copy(val: &Value) -> Option<Value> {
+    let copy = match val {
+        Value::Vector(items) => Value::Vector(items.clone()),
+        Value::Queue(items) => Value::Queue(items.clone()),
+        Value::Heap(heap) => Value::Heap(Heap {
+            less: heap.less,
+            items: heap.items.clone(),
+        }),
+        Value::Array(array) => Value::Array(Array {
+            shape: array.shape.clone(),
+            items: array.items.clone(),
+        }),
+        Value::HashTable(table) => Value::HashTable(HashTable {
+            comparator: table.comparator,
+            buckets: table.buckets.clone(),
+            len: table.len,
+        }),
+        Value::Bitvector(bv) => Value::Bitvector(bv.clone()),
+        Value::S32Vector(items) => Value::S32Vector(items.clone()),
+        Value::F64Vector(items) => Value::F64Vector(items.clone()),
+        Value::StringBuilder(sb) => Value::StringBuilder(sb.clone()),
+        _ => return None,
+    };
+    Some(copy)
+}
+
+// contained returns the values held by a container which deep_copy copies, in a fixed order
+fn contained(val: &Value) -> Vec<Ptr<Value>> {
+    match val {
+        Value::Vector(items)
+        | Value::Heap(Heap { items, .. })
+        | Value::Array(Array { items, .. }) => items.clone(),
+        Value::Queue(items) => items.iter().copied().collect(),
+        Value::HashTable(table) => table.buckets.values().flatten().map(|(_, v)| *v).collect(),
+        _ => Vec::new(),
+    }
+}
+
+// replace_contained replaces the values returned by contained with children
+fn replace_contained(val: &mut Value, children: Vec<Ptr<Value>>) {
+    match val {
+        Value::Vector(items)
+        | Value::Heap(Heap { items, .. })
+        | Value::Array(Array { items, .. }) => *items = children,
+        Value::Queue(items) => *items = children.into(),
+        Value::HashTable(table) => {
+            let entries = table.buckets.values_mut().flatten();
+            for ((_, v), child) in entries.zip(children) {
+                *v = child;
+            }
+        }
+        _ => {}
+    }
+}
+
+// deep_copy duplicates the pairs, sets and other mutable containers reachable from ptr
+// structure shared within the original, including cycles, is shared within the copy
+// hash table keys are shared rather than copied, as they must not be mutated anyway
+pub(super) fn deep_copy(alloc: &mut Allocator, ptr: Ptr<Value>) -> Ptr<Value> {
+    deep_copy_with(alloc, ptr, &mut HashMap::new())
+}
+
+fn deep_copy_with(
+    alloc: &mut Allocator,
+    ptr: Ptr<Value>,
+    seen: &mut HashMap<Ptr<Value>, Ptr<Value>>,
+) -> Ptr<Value> {
+    if let Some(&copy) = seen.get(&ptr) {
+        return copy;
+    }
+
+    match alloc.get_val(ptr) {
+        Value::Cons(_, _) => {}
+        Value::Set(set) => {
+            let items = set.items.clone();
+            let copy = Value::Set(Set::new()).gc(alloc);
+            seen.insert(ptr, copy);
+            for item in items {
+                let item = deep_copy_with(alloc, item, seen);
+                let hash = hash(alloc, item);
+                if let Value::Set(set) = alloc.get_val_mut(copy) {
+                    set.insert(hash, item);
+                }
+            }
+            return copy;
         }
+        val => {
+            let copy = match shallow_copy(val) {
+                Some(copy) => copy.gc(alloc),
+                None => return ptr,
+            };
+            seen.insert(ptr, copy);
+            let children = contained(alloc.get_val(copy))
+                .into_iter()
+                .map(|child| deep_copy_with(alloc, child, seen))
+                .collect::<Vec<_>>();
+            replace_contained(alloc.get_val_mut(copy), children);
+            return copy;
+        }
+    }
+
+    // walk the spine of the list iteratively so long lists don't exhaust the stack
+    let mut prev: Option<Ptr<Value>> = None;
+    let mut cur = ptr;
+    loop {
+        let next = match seen.get(&cur) {
+            Some(&copy) => copy,
+            None => match alloc.get_val(cur) {
+                Value::Cons(hd, tl) => {
+                    let (hd, tl) = (*hd, *tl);
+                    let copy = Value::Cons(hd, tl).gc(alloc);
+                    seen.insert(cur, copy);
+                    let hd = deep_copy_with(alloc, hd, seen);
+                    *alloc.get_val_mut(copy) = Value::Cons(hd, tl);
+                    if let Some(prev) = prev {
+                        set_cdr(alloc, prev, copy);
+                    }
+                    prev = Some(copy);
+                    cur = tl;
+                    continue;
+                }
+                _ => deep_copy_with(alloc, cur, seen),
+            },
+        };
+
+        if let Some(prev) = prev {
+            set_cdr(alloc, prev, next);
+        }
+        return seen[&ptr];
+    }
+}
+
+fn set_cdr(alloc: &mut Allocator, pair: Ptr<Value>, tail: Ptr<Value>) {
+    if let Value::Cons(_, tl) = alloc.get_val_mut(pair) {
+        *tl = tail;
     }
 }
 
-pub(super) fn clone_steps(cc: &Vec<Step>) -> Vec<Step> {
-    cc.iter().map(|step| step.clone_box()).collect::<Vec<_>>()
+pub(super) fn clone_steps(cc: &[Step]) -> Vec<Step> {
+    cc.to_vec()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn car_cdr(alloc: &Allocator, ptr: Ptr<Value>) -> (Ptr<Value>, Ptr<Value>) {
+        match alloc.get_val(ptr) {
+            Value::Cons(hd, tl) => (*hd, *tl),
+            _ => panic!("expected a pair"),
+        }
+    }
+
+    #[test]
+    fn deep_copy_preserves_sharing() {
+        let mut alloc = Allocator::new();
+        let one = Value::Integer(1).gc(&mut alloc);
+        let shared = Value::from_vec(&[one], &mut alloc);
+        let original = Value::Cons(shared, shared).gc(&mut alloc);
+
+        let copy = deep_copy(&mut alloc, original);
+        assert!(copy != original);
+        assert!(equal(&alloc, copy, original));
+
+        let (hd, tl) = car_cdr(&alloc, copy);
+        assert!(hd == tl);
+        assert!(hd != shared);
+    }
+
+    #[test]
+    fn deep_copy_preserves_cycles() {
+        let mut alloc = Allocator::new();
+        let one = Value::Integer(1).gc(&mut alloc);
+        let two = Value::Integer(2).gc(&mut alloc);
+        let list = Value::from_vec(&[one, two], &mut alloc);
+        let (_, last) = car_cdr(&alloc, list);
+        set_cdr(&mut alloc, last, list);
+
+        let copy = deep_copy(&mut alloc, list);
+        let (_, copy_last) = car_cdr(&alloc, copy);
+        let (_, copy_loop) = car_cdr(&alloc, copy_last);
+        assert!(copy != list);
+        assert!(copy_loop == copy);
+    }
 }
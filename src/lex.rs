@@ -1,13 +1,45 @@
-use std::iter::Peekable;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::BufRead;
 use std::str::Chars;
 
 #[derive(PartialEq, Debug)]
 pub enum Token {
     Lparen,
     Rparen,
+    // brackets delimit lists like parentheses, but must be matched with each other
+    Lbracket,
+    Rbracket,
+    // #( opens a vector literal, closed by a right paren
+    VectorStart,
+    // #u8( opens a bytevector literal, closed by a right paren
+    BytevectorStart,
     Integer(i64),
+    Float(f64),
+    // numerator and denominator, as written
+    Rational(i64, i64),
     Symbol(String),
+    // #:name, a keyword distinct from the symbol name
+    Keyword(String),
     Bool(bool),
+    String(String),
+    Char(char),
+    // datum labels: #n= names the datum that follows, #n# refers back to it
+    Label(u64),
+    LabelRef(u64),
+    // reader shorthand for quote, quasiquote, unquote and unquote-splicing
+    Quote,
+    Quasiquote,
+    Unquote,
+    UnquoteSplicing,
+}
+
+// Span is the range of byte offsets into the source a token was read from
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Span {
+    pub start: usize,
+    // one past the last byte
+    pub end: usize,
 }
 
 #[derive(PartialEq, Debug)]
@@ -15,28 +47,54 @@ pub struct AnnotatedToken {
     pub token: Token,
     pub line: u64,
     pub column: u64,
+    pub span: Span,
 }
 
 impl Token {
+    // annotate attaches a position to the token, its span is filled in by Lexer::next_token
     fn annotate(self, line: u64, column: u64) -> AnnotatedToken {
         AnnotatedToken {
             token: self,
             line,
             column,
+            span: Span::default(),
         }
     }
 }
 
+// Comment is a line, block or datum comment the lexer skipped, kept as trivia
+#[derive(PartialEq, Debug)]
+pub struct Comment {
+    pub line: u64,
+    pub column: u64,
+    pub span: Span,
+}
+
 #[derive(Debug)]
 pub struct Error {
-    line: u64,
-    column: u64,
-    message: &'static str,
+    pub line: u64,
+    pub column: u64,
+    pub message: &'static str,
+}
+
+impl Error {
+    // is_incomplete checks whether the error is the input ending inside a token or comment,
+    // which more input could complete
+    pub fn is_incomplete(&self) -> bool {
+        [
+            UNTERMINATED_STRING_ERROR,
+            UNTERMINATED_SYMBOL_ERROR,
+            UNTERMINATED_BLOCK_COMMENT_ERROR,
+            UNTERMINATED_DATUM_COMMENT_ERROR,
+        ]
+        .contains(&self.message)
+    }
 }
 
-impl ToString for Error {
-    fn to_string(&self) -> String {
-        format!(
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
             "Syntax error at line {}, col {}: {}",
             self.line, self.column, self.message
         )
@@ -44,26 +102,280 @@ impl ToString for Error {
 }
 
 const INVALID_INTEGER_ERROR: &str = "unable to parse integer value";
+const INTEGER_OVERFLOW_ERROR: &str = "integer literal too large, integers must fit in 64 bits";
+const UNKNOWN_CHAR_NAME_ERROR: &str = "unknown character name";
+const MISSING_DIGITS_ERROR: &str = "expected digits after the radix prefix";
+const ZERO_DENOMINATOR_ERROR: &str = "rational literal has a zero denominator";
+const INVALID_FLOAT_ERROR: &str = "unable to parse floating-point value";
 const INVALID_BOOL_ERROR: &str = "invalid boolean format, expected '#t' or '#f'";
+const UNTERMINATED_STRING_ERROR: &str = "unterminated string literal";
+const UNTERMINATED_SYMBOL_ERROR: &str = "unterminated symbol, expected '|'";
+const INVALID_SYMBOL_ESCAPE_ERROR: &str =
+    "invalid escape sequence, expected one of \\n \\t \\\\ \\| \\x<hex>;";
+const INVALID_ESCAPE_ERROR: &str = "invalid escape sequence, expected one of \\n \\t \\\\ \\\"";
+const UNTERMINATED_BLOCK_COMMENT_ERROR: &str = "unterminated block comment, expected '|#'";
+const MISSING_KEYWORD_NAME_ERROR: &str = "expected a name after '#:'";
+const INVALID_LABEL_ERROR: &str = "invalid datum label, expected '#n=' or '#n#'";
+const MISSING_DATUM_ERROR: &str = "expected a datum to comment out after '#;'";
+const UNTERMINATED_DATUM_COMMENT_ERROR: &str =
+    "unterminated datum comment, input ended before the datum after '#;' was complete";
+const CONTROL_CHARACTER_ERROR: &str = "control characters are not allowed in identifiers";
+const IDENTIFIER_TOO_LONG_ERROR: &str = "identifier is longer than 256 characters";
+
+const MAX_IDENTIFIER_LEN: usize = 256;
+
+// compositions of a base letter and a combining mark into a precomposed letter, covering the
+// Latin-1 and Latin Extended blocks
+// each entry lists the mark, then base letters and their compositions pairwise
+const COMPOSITIONS: &[(char, &str, &str)] = &[
+    ('\u{300}', "AEIOUaeiouÜüNn", "ÀÈÌÒÙàèìòùǛǜǸǹ"),
+    (
+        '\u{301}',
+        "AEIOUYaeiouyCcLlNnRrSsZzÜüGgÅåÆæØø",
+        "ÁÉÍÓÚÝáéíóúýĆćĹĺŃńŔŕŚśŹźǗǘǴǵǺǻǼǽǾǿ",
+    ),
+    (
+        '\u{302}',
+        "AEIOUaeiouCcGgHhJjSsWwYy",
+        "ÂÊÎÔÛâêîôûĈĉĜĝĤĥĴĵŜŝŴŵŶŷ",
+    ),
+    ('\u{303}', "ANOanoIiUu", "ÃÑÕãñõĨĩŨũ"),
+    (
+        '\u{304}',
+        "AaEeIiOoUuÜüÄäȦȧÆæǪǫÖöÕõȮȯYy",
+        "ĀāĒēĪīŌōŪūǕǖǞǟǠǡǢǣǬǭȪȫȬȭȰȱȲȳ",
+    ),
+    ('\u{306}', "AaEeGgIiOoUu", "ĂăĔĕĞğĬĭŎŏŬŭ"),
+    ('\u{307}', "CcEeGgIZzAaOo", "ĊċĖėĠġİŻżȦȧȮȯ"),
+    ('\u{308}', "AEIOUaeiouyY", "ÄËÏÖÜäëïöüÿŸ"),
+    ('\u{30a}', "AaUu", "ÅåŮů"),
+    ('\u{30b}', "OoUu", "ŐőŰű"),
+    (
+        '\u{30c}',
+        "CcDdEeLlNnRrSsTtZzAaIiOoUuÜüGgKkƷjHh",
+        "ČčĎďĚěĽľŇňŘřŠšŤťŽžǍǎǏǐǑǒǓǔǙǚǦǧǨǩǮǰȞȟ",
+    ),
+    ('\u{30f}', "AaEeIiOoRrUu", "ȀȁȄȅȈȉȌȍȐȑȔȕ"),
+    ('\u{311}', "AaEeIiOoRrUu", "ȂȃȆȇȊȋȎȏȒȓȖȗ"),
+    ('\u{31b}', "OoUu", "ƠơƯư"),
+    ('\u{326}', "SsTt", "ȘșȚț"),
+    ('\u{327}', "CcGgKkLlNnRrSsTtEe", "ÇçĢģĶķĻļŅņŖŗŞşŢţȨȩ"),
+    ('\u{328}', "AaEeIiUuOo", "ĄąĘęĮįŲųǪǫ"),
+];
+
+// compose returns the precomposed form of base followed by mark, if there is one
+fn compose(base: char, mark: char) -> Option<char> {
+    let (_, bases, composed) = COMPOSITIONS.iter().find(|(m, _, _)| *m == mark)?;
+    let idx = bases.chars().position(|chr| chr == base)?;
+    composed.chars().nth(idx)
+}
+
+// normalize_identifier composes letters followed by combining marks, so that an identifier
+// typed with precomposed letters and one saved in decomposed form bind the same name
+// this matches NFC for the Latin letters in COMPOSITIONS, other text is left as written
+fn normalize_identifier(name: &str) -> String {
+    let mut res = String::with_capacity(name.len());
+    for chr in name.chars() {
+        match res.chars().last().and_then(|base| compose(base, chr)) {
+            Some(composed) => {
+                res.pop();
+                res.push(composed);
+            }
+            None => res.push(chr),
+        }
+    }
+    res
+}
+
+// CHAR_NAMES are the named characters, as written after #\\
+pub const CHAR_NAMES: &[(&str, char)] = &[
+    ("space", ' '),
+    ("newline", '\n'),
+    ("tab", '\t'),
+    ("return", '\r'),
+    ("null", '\0'),
+    ("alarm", '\u{7}'),
+    ("backspace", '\u{8}'),
+    ("escape", '\u{1b}'),
+    ("delete", '\u{7f}'),
+];
+
+// push_digit appends digit to the integer val, or returns None if the result overflows
+// negative values are accumulated directly so the most negative integer can be written
+fn push_digit(val: i64, digit: u32, radix: u32, negative: bool) -> Option<i64> {
+    let shifted = val.checked_mul(radix.into())?;
+    if negative {
+        shifted.checked_sub(digit.into())
+    } else {
+        shifted.checked_add(digit.into())
+    }
+}
+
+// radix returns the radix named by the character after # in a number prefix
+fn radix(chr: char) -> Option<u32> {
+    match chr.to_ascii_lowercase() {
+        'x' => Some(16),
+        'o' => Some(8),
+        'b' => Some(2),
+        'd' => Some(10),
+        _ => None,
+    }
+}
+
+// CharSource produces the characters a Lexer reads, so strings, files and interactive input
+// can share one reader
+pub trait CharSource {
+    // next_char returns the next character, or None at the end of the input
+    fn next_char(&mut self) -> Option<char>;
+}
 
-struct Lexer<'a> {
-    iter: Peekable<Chars<'a>>,
+// StrSource reads the characters of a string
+pub struct StrSource<'a> {
+    chars: Chars<'a>,
+}
+
+impl<'a> StrSource<'a> {
+    pub fn new(source: &'a str) -> Self {
+        StrSource {
+            chars: source.chars(),
+        }
+    }
+}
+
+impl CharSource for StrSource<'_> {
+    fn next_char(&mut self) -> Option<char> {
+        self.chars.next()
+    }
+}
+
+// ReadSource reads characters from a buffered reader a line at a time,
+// treating a read error or invalid UTF-8 as the end of the input
+pub struct ReadSource<R: BufRead> {
+    reader: R,
+    line: VecDeque<char>,
+}
+
+impl<R: BufRead> ReadSource<R> {
+    pub fn new(reader: R) -> Self {
+        ReadSource {
+            reader,
+            line: VecDeque::new(),
+        }
+    }
+}
+
+impl<R: BufRead> CharSource for ReadSource<R> {
+    fn next_char(&mut self) -> Option<char> {
+        if self.line.is_empty() {
+            let mut line = String::new();
+            match self.reader.read_line(&mut line) {
+                Ok(n) if n > 0 => self.line.extend(line.chars()),
+                _ => return None,
+            }
+        }
+        self.line.pop_front()
+    }
+}
+
+pub struct Lexer<S: CharSource> {
+    source: S,
+    // characters read from the source but not yet consumed
+    lookahead: VecDeque<char>,
     line: u64,
     column: u64,
+    // byte offset of the next character
+    offset: usize,
+    // set once a syntax error has been returned, ending iteration
+    failed: bool,
+    // after a syntax error, skip to the next delimiter and keep going instead of stopping
+    recover: bool,
+    // comments skipped so far, recorded only when keeping comments
+    comments: Option<Vec<Comment>>,
 }
 
-impl<'a> Lexer<'a> {
-    fn new(source: &str) -> Lexer {
+impl<S: CharSource> Lexer<S> {
+    pub fn new(source: S) -> Self {
         Lexer {
-            iter: source.chars().peekable(),
+            source,
+            lookahead: VecDeque::new(),
             line: 0,
             column: 0,
+            offset: 0,
+            failed: false,
+            recover: false,
+            comments: None,
+        }
+    }
+
+    // offset returns the byte offset of the next character to be read
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    // recovering makes iteration continue past syntax errors, skipping the rest of each bad
+    // token, so that every error in the source is reported
+    pub fn recovering(mut self) -> Self {
+        self.recover = true;
+        self
+    }
+
+    // keeping_comments makes the lexer record the comments it skips, see take_comments
+    pub fn keeping_comments(mut self) -> Self {
+        self.comments = Some(Vec::new());
+        self
+    }
+
+    // take_comments returns the comments recorded so far, in source order
+    pub fn take_comments(&mut self) -> Vec<Comment> {
+        self.comments
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    // record_comment records a comment from the given position to the next character
+    fn record_comment(&mut self, line: u64, column: u64, start: usize) {
+        let end = self.offset;
+        if let Some(comments) = &mut self.comments {
+            // comments inside a datum comment are part of it
+            comments.retain(|comment| comment.span.start < start);
+            comments.push(Comment {
+                line,
+                column,
+                span: Span { start, end },
+            });
         }
     }
 
+    // skip_to_delimiter discards characters up to the next delimiter
+    fn skip_to_delimiter(&mut self) {
+        while !self.at_delimiter() {
+            self.next_chr();
+        }
+    }
+
+    // peek_nth returns the character n places after the next one without consuming it
+    fn peek_nth(&mut self, n: usize) -> Option<char> {
+        while self.lookahead.len() <= n {
+            let chr = self.source.next_char()?;
+            self.lookahead.push_back(chr);
+        }
+        Some(self.lookahead[n])
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.peek_nth(0)
+    }
+
     fn next_chr(&mut self) -> Option<char> {
-        let next = self.iter.next();
+        let next = match self.lookahead.pop_front() {
+            Some(chr) => Some(chr),
+            None => self.source.next_char(),
+        };
 
+        if let Some(chr) = next {
+            self.offset += chr.len_utf8();
+        }
         match next {
             Some('\n') => {
                 self.column = 0;
@@ -78,67 +390,470 @@ impl<'a> Lexer<'a> {
         next
     }
 
-    // next returns the next token from the source
+    // next_token returns the next token from the source
     // if there are no more tokens it returns Ok(None)
     // it returns Err if there is a syntax error
-    fn next(&mut self) -> Result<Option<AnnotatedToken>, Error> {
-        self.dump_whitespace();
+    pub fn next_token(&mut self) -> Result<Option<AnnotatedToken>, Error> {
+        loop {
+            self.dump_whitespace()?;
+            if self.peek() == Some('#') && self.peek_nth(1) == Some(';') {
+                let (line, column, start) = (self.line, self.column, self.offset);
+                self.skip_datum()?;
+                self.record_comment(line, column, start);
+            } else {
+                break;
+            }
+        }
 
-        let next_chr = match self.iter.peek() {
-            Some(chr) => *chr,
+        let next_chr = match self.peek() {
+            Some(chr) => chr,
             None => return Ok(None),
         };
 
+        let start = self.offset;
+        let mut token = self.read_token(next_chr)?;
+        token.span = Span {
+            start,
+            end: self.offset,
+        };
+        Ok(Some(token))
+    }
+
+    // read_token reads the token starting with next_chr
+    fn read_token(&mut self, next_chr: char) -> Result<AnnotatedToken, Error> {
         if next_chr == '(' {
             self.next_chr();
-            Ok(Some(Token::Lparen.annotate(self.line, self.column - 1)))
+            Ok(Token::Lparen.annotate(self.line, self.column - 1))
         } else if next_chr == ')' {
             self.next_chr();
-            Ok(Some(Token::Rparen.annotate(self.line, self.column - 1)))
+            Ok(Token::Rparen.annotate(self.line, self.column - 1))
+        } else if next_chr == '[' {
+            self.next_chr();
+            Ok(Token::Lbracket.annotate(self.line, self.column - 1))
+        } else if next_chr == ']' {
+            self.next_chr();
+            Ok(Token::Rbracket.annotate(self.line, self.column - 1))
+        } else if next_chr == '"' {
+            self.get_string()
+        } else if next_chr == '|' {
+            self.get_quoted_symbol()
+        } else if let Some(token) = self.get_quote() {
+            Ok(token)
+        } else if next_chr == '#' && self.peek_nth(1) == Some('(') {
+            self.next_chr();
+            self.next_chr();
+            Ok(Token::VectorStart.annotate(self.line, self.column - 2))
+        } else if next_chr == '#' && self.at_bytevector_start() {
+            for _ in 0..4 {
+                self.next_chr();
+            }
+            Ok(Token::BytevectorStart.annotate(self.line, self.column - 4))
+        } else if next_chr == '#' && self.peek_nth(1).is_some_and(|c| c.is_ascii_digit()) {
+            self.get_label()
+        } else if next_chr == '#' && self.peek_nth(1) == Some(':') {
+            self.get_keyword()
+        } else if next_chr == '#' && self.peek_nth(1) == Some('\\') {
+            self.get_char()
+        } else if next_chr == '#' && self.peek_nth(1).and_then(radix).is_some() {
+            self.get_radix_integer()
         } else if next_chr == '#' {
-            self.get_boolean().map(Some)
-        } else if next_chr.is_numeric() {
-            self.get_integer().map(Some)
+            self.get_boolean()
+        } else if self.at_number() {
+            self.get_number()
         } else {
-            Ok(Some(self.get_symbol()))
+            self.get_symbol()
         }
     }
 
-    fn dump_whitespace(&mut self) {
-        while let Some(chr) = self.iter.peek() {
+    // dump_whitespace skips whitespace, line comments and block comments
+    fn dump_whitespace(&mut self) -> Result<(), Error> {
+        while let Some(chr) = self.peek() {
             if chr.is_whitespace() {
                 self.next_chr();
+            } else if chr == ';' {
+                let (line, column, start) = (self.line, self.column, self.offset);
+                while self.peek().is_some_and(|chr| chr != '\n') {
+                    self.next_chr();
+                }
+                self.record_comment(line, column, start);
+            } else if chr == '#' && self.peek_nth(1) == Some('|') {
+                let (line, column, start) = (self.line, self.column, self.offset);
+                self.skip_block_comment()?;
+                self.record_comment(line, column, start);
             } else {
-                return;
+                return Ok(());
+            }
+        }
+        Ok(())
+    }
+
+    // skip_block_comment skips a #| ... |# comment, which may contain nested block comments
+    fn skip_block_comment(&mut self) -> Result<(), Error> {
+        let line = self.line;
+        let column = self.column;
+        self.next_chr();
+        self.next_chr();
+
+        let mut depth = 1;
+        while depth > 0 {
+            match self.next_chr() {
+                Some('|') if self.peek() == Some('#') => {
+                    self.next_chr();
+                    depth -= 1;
+                }
+                Some('#') if self.peek() == Some('|') => {
+                    self.next_chr();
+                    depth += 1;
+                }
+                Some(_) => {}
+                None => {
+                    return Err(Error {
+                        line,
+                        column,
+                        message: UNTERMINATED_BLOCK_COMMENT_ERROR,
+                    })
+                }
             }
         }
+        Ok(())
     }
 
+    // skip_datum skips a #; datum comment along with the datum following it
+    fn skip_datum(&mut self) -> Result<(), Error> {
+        let line = self.line;
+        let column = self.column;
+        self.next_chr();
+        self.next_chr();
+        let missing_datum = Error {
+            line,
+            column,
+            message: MISSING_DATUM_ERROR,
+        };
+
+        let mut depth = 0;
+        loop {
+            match self.next_token()? {
+                Some(AnnotatedToken {
+                    token:
+                        Token::Lparen | Token::Lbracket | Token::VectorStart | Token::BytevectorStart,
+                    ..
+                }) => depth += 1,
+                Some(AnnotatedToken {
+                    token: Token::Rparen | Token::Rbracket,
+                    ..
+                }) => {
+                    // a closing paren with nothing open ends the enclosing list, not a datum
+                    if depth == 0 {
+                        return Err(missing_datum);
+                    }
+                    depth -= 1;
+                }
+                // a quoted datum is still to come
+                Some(AnnotatedToken {
+                    token:
+                        Token::Quote
+                        | Token::Quasiquote
+                        | Token::Unquote
+                        | Token::UnquoteSplicing
+                        | Token::Label(_),
+                    ..
+                }) => continue,
+                Some(_) => {}
+                // the commented out datum may continue in more input
+                None => {
+                    return Err(Error {
+                        message: UNTERMINATED_DATUM_COMMENT_ERROR,
+                        ..missing_datum
+                    })
+                }
+            }
+            if depth == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    // at_bytevector_start checks for the #u8( prefix of a bytevector literal
+    fn at_bytevector_start(&mut self) -> bool {
+        (0..4).all(|i| self.peek_nth(i) == "#u8(".chars().nth(i))
+    }
+
+    // at_delimiter checks whether the next character ends an atom, including the start of a
+    // line comment, as in R7RS
     fn at_delimiter(&mut self) -> bool {
-        match self.iter.peek() {
+        match self.peek() {
             None => true,
-            Some(&chr) => chr.is_whitespace() || chr == '(' || chr == ')',
+            Some(chr) => chr.is_whitespace() || matches!(chr, '(' | ')' | '[' | ']' | '"' | ';'),
         }
     }
 
-    fn get_integer(&mut self) -> Result<AnnotatedToken, Error> {
+    // get_quote reads a quote shorthand token if one is next
+    fn get_quote(&mut self) -> Option<AnnotatedToken> {
+        let (token, len) = match (self.peek()?, self.peek_nth(1)) {
+            ('\'', _) => (Token::Quote, 1),
+            ('`', _) => (Token::Quasiquote, 1),
+            (',', Some('@')) => (Token::UnquoteSplicing, 2),
+            (',', _) => (Token::Unquote, 1),
+            _ => return None,
+        };
+        let (line, column) = (self.line, self.column);
+        for _ in 0..len {
+            self.next_chr();
+        }
+        Some(token.annotate(line, column))
+    }
+
+    // at_number reports whether a number starts at the next character: a digit,
+    // optionally preceded by a minus sign and a decimal point
+    fn at_number(&mut self) -> bool {
+        let mut n = 0;
+        if self.peek() == Some('-') {
+            n += 1;
+        }
+        if self.peek_nth(n) == Some('.') {
+            n += 1;
+        }
+        self.peek_nth(n).is_some_and(char::is_numeric)
+    }
+
+    // get_number reads an integer, or a float if there is a decimal point or exponent
+    fn get_number(&mut self) -> Result<AnnotatedToken, Error> {
         let line = self.line;
         let column = self.column;
 
+        let negative = self.peek() == Some('-');
+        if negative {
+            self.next_chr();
+        }
+
+        // the digits are kept as text too, since a float's integer part may not fit in an i64
+        let mut text = String::from(if negative { "-" } else { "" });
+        let mut val = Some(0);
+        let overflow = Error {
+            line,
+            column,
+            message: INTEGER_OVERFLOW_ERROR,
+        };
+
+        loop {
+            if self.at_delimiter() {
+                return Ok(Token::Integer(val.ok_or(overflow)?).annotate(line, column));
+            }
+            if self.peek() == Some('.') || self.at_exponent() {
+                return self.get_float(line, column, text);
+            }
+            if self.peek() == Some('/') && self.peek_nth(1).is_some_and(|chr| chr.is_ascii_digit())
+            {
+                self.next_chr();
+                return self.get_denominator(line, column, val.ok_or(overflow)?);
+            }
+
+            let chr = self.next_chr().unwrap();
+            let next_digit = chr.to_digit(10).ok_or(Error {
+                line: self.line,
+                column: self.column - 1,
+                message: INVALID_INTEGER_ERROR,
+            })?;
+            text.push(chr);
+            val = val.and_then(|val| push_digit(val, next_digit, 10, negative));
+        }
+    }
+
+    // get_denominator reads the denominator of a rational whose numerator has been read
+    fn get_denominator(
+        &mut self,
+        line: u64,
+        column: u64,
+        numerator: i64,
+    ) -> Result<AnnotatedToken, Error> {
         let mut val = 0;
+        loop {
+            if self.at_delimiter() {
+                if val == 0 {
+                    return Err(Error {
+                        line,
+                        column,
+                        message: ZERO_DENOMINATOR_ERROR,
+                    });
+                }
+                return Ok(Token::Rational(numerator, val).annotate(line, column));
+            }
+
+            let next_digit = self.next_chr().unwrap().to_digit(10).ok_or(Error {
+                line: self.line,
+                column: self.column - 1,
+                message: INVALID_INTEGER_ERROR,
+            })?;
+            val = push_digit(val, next_digit, 10, false).ok_or(Error {
+                line,
+                column,
+                message: INTEGER_OVERFLOW_ERROR,
+            })?;
+        }
+    }
+
+    // at_exponent reports whether an exponent marker with at least one digit is next
+    fn at_exponent(&mut self) -> bool {
+        if !matches!(self.peek(), Some('e') | Some('E')) {
+            return false;
+        }
+        match self.peek_nth(1) {
+            Some('+') | Some('-') => self.peek_nth(2).is_some_and(|chr| chr.is_ascii_digit()),
+            Some(chr) => chr.is_ascii_digit(),
+            None => false,
+        }
+    }
+
+    // get_float reads the rest of a float whose sign and integer part have already been read
+    fn get_float(
+        &mut self,
+        line: u64,
+        column: u64,
+        mut text: String,
+    ) -> Result<AnnotatedToken, Error> {
+        let mut seen_point = false;
+        let mut seen_exponent = false;
+
+        loop {
+            if self.at_delimiter() {
+                // the text is only digits with at most one point and exponent, so parsing succeeds
+                let val = text.parse::<f64>().unwrap();
+                return Ok(Token::Float(val).annotate(line, column));
+            }
+
+            if self.peek() == Some('.') && !seen_point && !seen_exponent {
+                seen_point = true;
+                text.push(self.next_chr().unwrap());
+            } else if !seen_exponent && self.at_exponent() {
+                seen_exponent = true;
+                text.push(self.next_chr().unwrap());
+                if let Some(sign @ '+') | Some(sign @ '-') = self.peek() {
+                    self.next_chr();
+                    text.push(sign);
+                }
+            } else {
+                match self.next_chr().unwrap() {
+                    chr if chr.is_ascii_digit() => text.push(chr),
+                    _ => {
+                        return Err(Error {
+                            line: self.line,
+                            column: self.column - 1,
+                            message: INVALID_FLOAT_ERROR,
+                        })
+                    }
+                }
+            }
+        }
+    }
+
+    // get_char reads a character literal: #\\ followed by a character,
+    // a character name or x and a hexadecimal code point
+    fn get_char(&mut self) -> Result<AnnotatedToken, Error> {
+        let line = self.line;
+        let column = self.column;
+        // sanity check
+        assert_eq!(self.next_chr().unwrap(), '#');
+        assert_eq!(self.next_chr().unwrap(), '\\');
+
+        // the first character is taken even if it is a delimiter, as in #\( or #\space
+        let mut text = String::new();
+        if let Some(chr) = self.next_chr() {
+            text.push(chr);
+        }
+        while !self.at_delimiter() {
+            text.push(self.next_chr().unwrap());
+        }
 
+        let mut chars = text.chars();
+        let chr = match (chars.next(), chars.next()) {
+            (Some(chr), None) => Some(chr),
+            (Some('x'), Some(_)) | (Some('X'), Some(_)) => u32::from_str_radix(&text[1..], 16)
+                .ok()
+                .and_then(char::from_u32),
+            _ => CHAR_NAMES
+                .iter()
+                .find(|(name, _)| *name == text)
+                .map(|(_, chr)| *chr),
+        };
+        match chr {
+            Some(chr) => Ok(Token::Char(chr).annotate(line, column)),
+            None => Err(Error {
+                line,
+                column,
+                message: UNKNOWN_CHAR_NAME_ERROR,
+            }),
+        }
+    }
+
+    // get_radix_integer reads an integer with a radix prefix: #x, #o, #b or #d
+    fn get_radix_integer(&mut self) -> Result<AnnotatedToken, Error> {
+        let line = self.line;
+        let column = self.column;
+        // sanity check
+        assert_eq!(self.next_chr().unwrap(), '#');
+        let radix = radix(self.next_chr().unwrap()).unwrap();
+
+        let negative = self.peek() == Some('-');
+        if negative {
+            self.next_chr();
+        }
+
+        let mut val: i64 = 0;
+        let mut digits = 0;
         loop {
             if self.at_delimiter() {
+                if digits == 0 {
+                    return Err(Error {
+                        line,
+                        column,
+                        message: MISSING_DIGITS_ERROR,
+                    });
+                }
                 return Ok(Token::Integer(val).annotate(line, column));
             }
 
-            let next_digit = self.next_chr().unwrap().to_digit(10).ok_or(Error {
+            let next_digit = self.next_chr().unwrap().to_digit(radix).ok_or(Error {
                 line: self.line,
                 column: self.column - 1,
                 message: INVALID_INTEGER_ERROR,
             })?;
+            digits += 1;
+            val = push_digit(val, next_digit, radix, negative).ok_or(Error {
+                line,
+                column,
+                message: INTEGER_OVERFLOW_ERROR,
+            })?;
+        }
+    }
+
+    // get_label reads a datum label definition #n= or reference #n#
+    fn get_label(&mut self) -> Result<AnnotatedToken, Error> {
+        let line = self.line;
+        let column = self.column;
+        // sanity check
+        assert_eq!(self.next_chr().unwrap(), '#');
+        let invalid = Error {
+            line,
+            column,
+            message: INVALID_LABEL_ERROR,
+        };
 
-            val = val * 10 + (next_digit as i64);
+        let mut label: u64 = 0;
+        loop {
+            match self.next_chr() {
+                Some(chr @ '0'..='9') => {
+                    let digit = chr.to_digit(10).unwrap() as u64;
+                    label = match label.checked_mul(10).and_then(|l| l.checked_add(digit)) {
+                        Some(label) => label,
+                        None => return Err(invalid),
+                    }
+                }
+                Some('=') => return Ok(Token::Label(label).annotate(line, column)),
+                Some('#') if self.at_delimiter() => {
+                    return Ok(Token::LabelRef(label).annotate(line, column))
+                }
+                _ => return Err(invalid),
+            }
         }
     }
 
@@ -159,31 +874,231 @@ impl<'a> Lexer<'a> {
         }
     }
 
-    fn get_symbol(&mut self) -> AnnotatedToken {
+    fn get_string(&mut self) -> Result<AnnotatedToken, Error> {
+        let line = self.line;
+        let column = self.column;
+        // sanity check
+        assert_eq!(self.next_chr().unwrap(), '"');
+        if self.peek() == Some('"') && self.peek_nth(1) == Some('"') {
+            return self.get_long_string(line, column);
+        }
+
+        let mut val = String::new();
+        loop {
+            match self.next_chr() {
+                Some('"') => return Ok(Token::String(val).annotate(line, column)),
+                Some('\\') => match self.next_chr() {
+                    Some('n') => val.push('\n'),
+                    Some('t') => val.push('\t'),
+                    Some('\\') => val.push('\\'),
+                    Some('"') => val.push('"'),
+                    _ => {
+                        return Err(Error {
+                            line: self.line,
+                            column: self.column - 1,
+                            message: INVALID_ESCAPE_ERROR,
+                        })
+                    }
+                },
+                Some(chr) => val.push(chr),
+                None => {
+                    return Err(Error {
+                        line,
+                        column,
+                        message: UNTERMINATED_STRING_ERROR,
+                    })
+                }
+            }
+        }
+    }
+
+    // get_long_string reads the rest of a string between triple quotes, whose opening quote
+    // at line and column has been read
+    // its text is taken verbatim, without escapes, so quotes and newlines can be embedded as they
+    // are; a newline directly after the opening quotes is not part of the string
+    fn get_long_string(&mut self, line: u64, column: u64) -> Result<AnnotatedToken, Error> {
+        self.next_chr();
+        self.next_chr();
+        if self.peek() == Some('\n') {
+            self.next_chr();
+        }
+
+        let mut val = String::new();
+        loop {
+            match self.next_chr() {
+                Some('"') if self.peek() == Some('"') && self.peek_nth(1) == Some('"') => {
+                    self.next_chr();
+                    self.next_chr();
+                    return Ok(Token::String(val).annotate(line, column));
+                }
+                Some(chr) => val.push(chr),
+                None => {
+                    return Err(Error {
+                        line,
+                        column,
+                        message: UNTERMINATED_STRING_ERROR,
+                    })
+                }
+            }
+        }
+    }
+
+    // get_quoted_symbol reads a symbol written between bars, which may contain any characters
+    // its name is taken verbatim, apart from escape sequences
+    fn get_quoted_symbol(&mut self) -> Result<AnnotatedToken, Error> {
         let line = self.line;
         let column = self.column;
+        // sanity check
+        assert_eq!(self.next_chr().unwrap(), '|');
 
         let mut val = String::new();
+        loop {
+            let chr = match self.next_chr() {
+                Some('|') => return Ok(Token::Symbol(val).annotate(line, column)),
+                Some('\\') => self.get_symbol_escape()?,
+                Some(chr) => chr,
+                None => {
+                    return Err(Error {
+                        line,
+                        column,
+                        message: UNTERMINATED_SYMBOL_ERROR,
+                    })
+                }
+            };
+            if val.chars().count() == MAX_IDENTIFIER_LEN {
+                return Err(Error {
+                    line,
+                    column,
+                    message: IDENTIFIER_TOO_LONG_ERROR,
+                });
+            }
+            val.push(chr);
+        }
+    }
+
+    // get_symbol_escape reads the rest of an escape sequence in a quoted symbol
+    fn get_symbol_escape(&mut self) -> Result<char, Error> {
+        let line = self.line;
+        let column = self.column - 1;
+        let chr = match self.next_chr() {
+            Some('n') => Some('\n'),
+            Some('t') => Some('\t'),
+            Some('\\') => Some('\\'),
+            Some('|') => Some('|'),
+            Some('x') => {
+                let mut hex = String::new();
+                while let Some(chr) = self.peek().filter(|chr| chr.is_ascii_hexdigit()) {
+                    self.next_chr();
+                    hex.push(chr);
+                }
+                match self.next_chr() {
+                    Some(';') => u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        chr.ok_or(Error {
+            line,
+            column,
+            message: INVALID_SYMBOL_ESCAPE_ERROR,
+        })
+    }
+
+    // get_keyword reads a keyword: #: followed by an identifier
+    fn get_keyword(&mut self) -> Result<AnnotatedToken, Error> {
+        let line = self.line;
+        let column = self.column;
+        // sanity check
+        assert_eq!(self.next_chr().unwrap(), '#');
+        assert_eq!(self.next_chr().unwrap(), ':');
+
+        if self.at_delimiter() {
+            return Err(Error {
+                line,
+                column,
+                message: MISSING_KEYWORD_NAME_ERROR,
+            });
+        }
+        match self.get_symbol()?.token {
+            Token::Symbol(name) => Ok(Token::Keyword(name).annotate(line, column)),
+            _ => unreachable!(),
+        }
+    }
+
+    // get_symbol reads an identifier, which may contain any non-control characters,
+    // normalizing it so equivalent spellings are the same symbol
+    fn get_symbol(&mut self) -> Result<AnnotatedToken, Error> {
+        let line = self.line;
+        let column = self.column;
+
+        let mut val = String::new();
+        let mut len = 0;
 
         loop {
             if self.at_delimiter() {
-                return Token::Symbol(val).annotate(line, column);
+                let name = normalize_identifier(&val);
+                return Ok(Token::Symbol(name).annotate(line, column));
             }
 
-            val.push(self.next_chr().unwrap());
+            let chr = self.next_chr().unwrap();
+            if chr.is_control() {
+                return Err(Error {
+                    line: self.line,
+                    column: self.column - 1,
+                    message: CONTROL_CHARACTER_ERROR,
+                });
+            }
+            len += 1;
+            if len > MAX_IDENTIFIER_LEN {
+                return Err(Error {
+                    line,
+                    column,
+                    message: IDENTIFIER_TOO_LONG_ERROR,
+                });
+            }
+            val.push(chr);
         }
     }
 }
 
-pub fn tokenize(source: &str) -> Result<Vec<AnnotatedToken>, Error> {
-    let mut lexer = Lexer::new(source);
-    let mut res = Vec::new();
+// iterating over a lexer reads tokens lazily, as they are needed,
+// ending after the first syntax error unless the lexer is recovering
+impl<S: CharSource> Iterator for Lexer<S> {
+    type Item = Result<AnnotatedToken, Error>;
 
-    while let Some(token) = lexer.next()? {
-        res.push(token)
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let res = self.next_token().transpose();
+        if let Some(Err(_)) = res {
+            if self.recover {
+                self.skip_to_delimiter();
+            } else {
+                self.failed = true;
+            }
+        }
+        res
     }
+}
 
-    Ok(res)
+pub fn tokenize(source: &str) -> Result<Vec<AnnotatedToken>, Error> {
+    Lexer::new(StrSource::new(source)).collect()
+}
+
+// tokenize_all tokenizes source, recovering from syntax errors,
+// returning the tokens read and every error found
+pub fn tokenize_all(source: &str) -> (Vec<AnnotatedToken>, Vec<Error>) {
+    let mut tokens = Vec::new();
+    let mut errors = Vec::new();
+    for res in Lexer::new(StrSource::new(source)).recovering() {
+        match res {
+            Ok(token) => tokens.push(token),
+            Err(err) => errors.push(err),
+        }
+    }
+    (tokens, errors)
 }
 
 #[cfg(test)]
@@ -214,6 +1129,546 @@ mod test {
         );
     }
 
+    #[test]
+    fn lexes_negative_integers() {
+        let source = "(- -5 -9223372036854775808 -x -)";
+        let tokens = tokenize(source)
+            .unwrap()
+            .into_iter()
+            .map(|tok| tok.token)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Lparen,
+                Token::Symbol("-".to_string()),
+                Token::Integer(-5),
+                Token::Integer(i64::MIN),
+                Token::Symbol("-x".to_string()),
+                Token::Symbol("-".to_string()),
+                Token::Rparen
+            ]
+        );
+
+        let err = tokenize("-5x").unwrap_err();
+        assert_eq!(
+            (err.line, err.column, err.message),
+            (0, 2, INVALID_INTEGER_ERROR)
+        );
+    }
+
+    #[test]
+    fn rejects_overflowing_integers() {
+        let tokens = tokenize("(9223372036854775807 99999999999999999999.5)")
+            .unwrap()
+            .into_iter()
+            .map(|tok| tok.token)
+            .collect::<Vec<_>>();
+        assert_eq!(tokens[1], Token::Integer(i64::MAX));
+        assert_eq!(tokens[2], Token::Float(99999999999999999999.5));
+
+        for source in [
+            "(1 9223372036854775808)",
+            "(1 -9223372036854775809)",
+            "(1 99999999999999999999/2)",
+            "(1 1/99999999999999999999)",
+            "(1 #x10000000000000000)",
+            "(1 123456789012345678901234567890)",
+            "(1 -123456789012345678901234567890)",
+            "(1 #b111111111111111111111111111111111111111111111111111111111111111111)",
+        ] {
+            let err = tokenize(source).unwrap_err();
+            assert_eq!(
+                (err.line, err.column, err.message),
+                (0, 3, INTEGER_OVERFLOW_ERROR)
+            );
+        }
+    }
+
+    #[test]
+    fn lexes_floats() {
+        let source = "(1.5 -0.25 .5 -.5 2. 1e3 1.5E-2 7)";
+        let tokens = tokenize(source)
+            .unwrap()
+            .into_iter()
+            .map(|tok| tok.token)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Lparen,
+                Token::Float(1.5),
+                Token::Float(-0.25),
+                Token::Float(0.5),
+                Token::Float(-0.5),
+                Token::Float(2.0),
+                Token::Float(1000.0),
+                Token::Float(0.015),
+                Token::Integer(7),
+                Token::Rparen
+            ]
+        );
+
+        let err = tokenize("1.5.2").unwrap_err();
+        assert_eq!(
+            (err.line, err.column, err.message),
+            (0, 3, INVALID_FLOAT_ERROR)
+        );
+        let err = tokenize("1e").unwrap_err();
+        assert_eq!(
+            (err.line, err.column, err.message),
+            (0, 1, INVALID_INTEGER_ERROR)
+        );
+    }
+
+    #[test]
+    fn lexes_radix_prefixes() {
+        let source = "(#xFF #Xff #b1010 #o777 #d99 #x-1a #f)";
+        let tokens = tokenize(source)
+            .unwrap()
+            .into_iter()
+            .map(|tok| tok.token)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Lparen,
+                Token::Integer(255),
+                Token::Integer(255),
+                Token::Integer(10),
+                Token::Integer(511),
+                Token::Integer(99),
+                Token::Integer(-26),
+                Token::Bool(false),
+                Token::Rparen
+            ]
+        );
+
+        let err = tokenize("#b102").unwrap_err();
+        assert_eq!(
+            (err.line, err.column, err.message),
+            (0, 4, INVALID_INTEGER_ERROR)
+        );
+        let err = tokenize("(#x)").unwrap_err();
+        assert_eq!(
+            (err.line, err.column, err.message),
+            (0, 1, MISSING_DIGITS_ERROR)
+        );
+    }
+
+    #[test]
+    fn lexes_quote_shorthand() {
+        let source = "('a `(b ,c ,@d) #;'(e) f)";
+        let tokens = tokenize(source)
+            .unwrap()
+            .into_iter()
+            .map(|tok| tok.token)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Lparen,
+                Token::Quote,
+                Token::Symbol("a".to_string()),
+                Token::Quasiquote,
+                Token::Lparen,
+                Token::Symbol("b".to_string()),
+                Token::Unquote,
+                Token::Symbol("c".to_string()),
+                Token::UnquoteSplicing,
+                Token::Symbol("d".to_string()),
+                Token::Rparen,
+                Token::Symbol("f".to_string()),
+                Token::Rparen
+            ]
+        );
+    }
+
+    #[test]
+    fn lexes_characters() {
+        let source = "(#\\a #\\space #\\newline #\\( #\\x #\\x41 #\\λ)";
+        let tokens = tokenize(source)
+            .unwrap()
+            .into_iter()
+            .map(|tok| tok.token)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Lparen,
+                Token::Char('a'),
+                Token::Char(' '),
+                Token::Char('\n'),
+                Token::Char('('),
+                Token::Char('x'),
+                Token::Char('A'),
+                Token::Char('λ'),
+                Token::Rparen
+            ]
+        );
+
+        let err = tokenize("(#\\spac)").unwrap_err();
+        assert_eq!(
+            (err.line, err.column, err.message),
+            (0, 1, UNKNOWN_CHAR_NAME_ERROR)
+        );
+    }
+
+    #[test]
+    fn lexes_quoted_symbols() {
+        let source = r"(|foo bar| |a\|b| |\x41;\n| || |(|)";
+        let tokens = tokenize(source)
+            .unwrap()
+            .into_iter()
+            .map(|tok| tok.token)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Lparen,
+                Token::Symbol("foo bar".to_string()),
+                Token::Symbol("a|b".to_string()),
+                Token::Symbol("A\n".to_string()),
+                Token::Symbol("".to_string()),
+                Token::Symbol("(".to_string()),
+                Token::Rparen
+            ]
+        );
+
+        let err = tokenize("(|abc").unwrap_err();
+        assert_eq!(
+            (err.line, err.column, err.message),
+            (0, 1, UNTERMINATED_SYMBOL_ERROR)
+        );
+        let err = tokenize(r"|a\q|").unwrap_err();
+        assert_eq!(
+            (err.line, err.column, err.message),
+            (0, 2, INVALID_SYMBOL_ESCAPE_ERROR)
+        );
+    }
+
+    #[test]
+    fn yields_tokens_lazily() {
+        let input = std::io::Cursor::new("(a 1)\n\"unterminated");
+        let mut lexer = Lexer::new(ReadSource::new(input));
+        assert_eq!(lexer.next().unwrap().unwrap().token, Token::Lparen);
+        // the second line has not been read yet
+        assert_eq!(lexer.source.reader.position(), 6);
+
+        let rest = lexer
+            .by_ref()
+            .map(|res| res.map(|tok| tok.token))
+            .collect::<Vec<_>>();
+        assert_eq!(rest.len(), 4);
+        assert_eq!(rest[2].as_ref().unwrap(), &Token::Rparen);
+        assert_eq!(
+            rest[3].as_ref().unwrap_err().message,
+            UNTERMINATED_STRING_ERROR
+        );
+        assert!(lexer.next().is_none());
+    }
+
+    #[test]
+    fn records_byte_spans() {
+        let spans = tokenize("(héllo \"ü\")  ; x\n'1")
+            .unwrap()
+            .into_iter()
+            .map(|tok| (tok.span.start, tok.span.end))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            spans,
+            vec![(0, 1), (1, 7), (8, 12), (12, 13), (19, 20), (20, 21)]
+        );
+    }
+
+    #[test]
+    fn recovers_from_errors() {
+        let (tokens, errors) = tokenize_all("(1x2 #q \"ok\" 2)\n#\\bogus 3 \"open");
+        let tokens = tokens.into_iter().map(|tok| tok.token).collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Lparen,
+                Token::String("ok".to_string()),
+                Token::Integer(2),
+                Token::Rparen,
+                Token::Integer(3)
+            ]
+        );
+        let errors = errors
+            .into_iter()
+            .map(|err| (err.line, err.column, err.message))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            vec![
+                (0, 2, INVALID_INTEGER_ERROR),
+                (0, 6, INVALID_BOOL_ERROR),
+                (1, 0, UNKNOWN_CHAR_NAME_ERROR),
+                (1, 10, UNTERMINATED_STRING_ERROR)
+            ]
+        );
+    }
+
+    #[test]
+    fn lexes_rationals() {
+        let source = "(1/3 -2/4 1/0)";
+        let mut lexer = Lexer::new(StrSource::new(source));
+        let mut tokens = Vec::new();
+        for _ in 0..3 {
+            tokens.push(lexer.next_token().unwrap().unwrap().token);
+        }
+        assert_eq!(
+            tokens,
+            vec![Token::Lparen, Token::Rational(1, 3), Token::Rational(-2, 4)]
+        );
+        let err = lexer.next_token().unwrap_err();
+        assert_eq!(
+            (err.line, err.column, err.message),
+            (0, 10, ZERO_DENOMINATOR_ERROR)
+        );
+    }
+
+    #[test]
+    fn lexes_keywords() {
+        let tokens = tokenize("(#:size a:)")
+            .unwrap()
+            .into_iter()
+            .map(|tok| tok.token)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Lparen,
+                Token::Keyword("size".to_string()),
+                Token::Symbol("a:".to_string()),
+                Token::Rparen
+            ]
+        );
+        let err = tokenize("(#: a)").unwrap_err();
+        assert_eq!((err.column, err.message), (1, MISSING_KEYWORD_NAME_ERROR));
+    }
+
+    #[test]
+    fn skips_line_comments() {
+        let source = "; leading comment\n(a ; trailing comment\n b);";
+        let tokens = tokenize(source)
+            .unwrap()
+            .into_iter()
+            .map(|tok| tok.token)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Lparen,
+                Token::Symbol("a".to_string()),
+                Token::Symbol("b".to_string()),
+                Token::Rparen
+            ]
+        );
+
+        // a comment directly after an atom ends it
+        let source = "x; the answer
+(list x;c
+ 2)
+1;c
+1.5;c
+#t;c
+#\\a;c
+#:k;c";
+        let tokens = tokenize(source)
+            .unwrap()
+            .into_iter()
+            .map(|tok| tok.token)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Symbol("x".to_string()),
+                Token::Lparen,
+                Token::Symbol("list".to_string()),
+                Token::Symbol("x".to_string()),
+                Token::Integer(2),
+                Token::Rparen,
+                Token::Integer(1),
+                Token::Float(1.5),
+                Token::Bool(true),
+                Token::Char('a'),
+                Token::Keyword("k".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_comments() {
+        let source = "; a\n(b #| c |# d) #;(e #;f) ;g";
+        let mut lexer = Lexer::new(StrSource::new(source)).keeping_comments();
+        assert_eq!(lexer.by_ref().count(), 4);
+        let comments = lexer
+            .take_comments()
+            .into_iter()
+            .map(|comment| (comment.line, comment.column, comment.span))
+            .map(|(line, column, span)| (line, column, &source[span.start..span.end]))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            comments,
+            vec![
+                (0, 0, "; a"),
+                (1, 3, "#| c |#"),
+                (1, 14, "#;(e #;f)"),
+                (1, 24, ";g")
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_block_and_datum_comments() {
+        let source = "#| a #| nested |# comment |#(a #;(b (c)) #; d e #;#;f g)";
+        let tokens = tokenize(source)
+            .unwrap()
+            .into_iter()
+            .map(|tok| tok.token)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Lparen,
+                Token::Symbol("a".to_string()),
+                Token::Symbol("e".to_string()),
+                Token::Rparen
+            ]
+        );
+
+        let err = tokenize("(a)\n #| #| |#").unwrap_err();
+        assert_eq!(
+            (err.line, err.column, err.message),
+            (1, 1, UNTERMINATED_BLOCK_COMMENT_ERROR)
+        );
+        let err = tokenize("(a #;)").unwrap_err();
+        assert_eq!(
+            (err.line, err.column, err.message),
+            (0, 3, MISSING_DATUM_ERROR)
+        );
+        assert!(!err.is_incomplete());
+        // the input can end inside the commented out datum, which more input could finish
+        let err = tokenize("(a #;(b\n c").unwrap_err();
+        assert_eq!(
+            (err.line, err.column, err.message),
+            (0, 3, UNTERMINATED_DATUM_COMMENT_ERROR)
+        );
+        assert!(err.is_incomplete());
+    }
+
+    #[test]
+    fn reads_from_any_char_source() {
+        let source = "(define x\n  #t) ; done\n(x)";
+        let mut lexer = Lexer::new(ReadSource::new(std::io::Cursor::new(source)));
+        let mut tokens = Vec::new();
+        while let Some(token) = lexer.next_token().unwrap() {
+            tokens.push(token);
+        }
+        assert_eq!(tokens, tokenize(source).unwrap());
+        assert_eq!((tokens[3].line, tokens[3].column), (1, 2));
+        assert_eq!(tokens[3].span, Span { start: 12, end: 14 });
+    }
+
+    #[test]
+    fn lexes_strings() {
+        let source = "(f \"a \\\"b\\\"\\n\\t\\\\\"\"\")";
+        let tokens = tokenize(source)
+            .unwrap()
+            .into_iter()
+            .map(|tok| tok.token)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Lparen,
+                Token::Symbol("f".to_string()),
+                Token::String("a \"b\"\n\t\\".to_string()),
+                Token::String("".to_string()),
+                Token::Rparen
+            ]
+        );
+
+        let err = tokenize("(f \"abc").unwrap_err();
+        assert_eq!(
+            (err.line, err.column, err.message),
+            (0, 3, UNTERMINATED_STRING_ERROR)
+        );
+        let err = tokenize("\"a\\qb\"").unwrap_err();
+        assert_eq!(
+            (err.line, err.column, err.message),
+            (0, 3, INVALID_ESCAPE_ERROR)
+        );
+    }
+
+    #[test]
+    fn lexes_long_strings() {
+        let source = "(f \"\"\"\n<a href=\"x\">\\n</a>\n\"\"\" \"\"\"\"\"\")";
+        let tokens = tokenize(source)
+            .unwrap()
+            .into_iter()
+            .map(|tok| tok.token)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Lparen,
+                Token::Symbol("f".to_string()),
+                Token::String("<a href=\"x\">\\n</a>\n".to_string()),
+                Token::String("".to_string()),
+                Token::Rparen
+            ]
+        );
+
+        // lines inside the string are counted for the tokens and errors after it
+        let tokens = tokenize("\"\"\"a\nb\nc\"\"\" x").unwrap();
+        assert_eq!((tokens[1].line, tokens[1].column), (2, 5));
+        let err = tokenize("\"\"\"\nSELECT \"a\"\nFROM t\n\"\"\"\n(1x)").unwrap_err();
+        assert_eq!(
+            (err.line, err.column, err.message),
+            (4, 2, INVALID_INTEGER_ERROR)
+        );
+
+        let err = tokenize("(f \"\"\"abc\"\")").unwrap_err();
+        assert_eq!(
+            (err.line, err.column, err.message),
+            (0, 3, UNTERMINATED_STRING_ERROR)
+        );
+        assert!(err.is_incomplete());
+    }
+
+    #[test]
+    fn normalizes_identifiers() {
+        let tokens = tokenize("(λ café cafe\u{301} Ǘ U\u{308}\u{301} e\u{323})").unwrap();
+        let names = tokens
+            .into_iter()
+            .filter_map(|tok| match tok.token {
+                Token::Symbol(s) => Some(s),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["λ", "café", "café", "Ǘ", "Ǘ", "e\u{323}"]);
+    }
+
+    #[test]
+    fn rejects_invalid_identifiers() {
+        let err = tokenize("(ab\u{7}c)").unwrap_err();
+        assert_eq!(
+            (err.line, err.column, err.message),
+            (0, 3, CONTROL_CHARACTER_ERROR)
+        );
+
+        let source = format!("(f {})", "x".repeat(MAX_IDENTIFIER_LEN + 1));
+        let err = tokenize(&source).unwrap_err();
+        assert_eq!(
+            (err.line, err.column, err.message),
+            (0, 3, IDENTIFIER_TOO_LONG_ERROR)
+        );
+        assert!(tokenize(&"x".repeat(MAX_IDENTIFIER_LEN)).is_ok());
+    }
+
     #[test]
     fn generates_error() {
         let source = "hello\n12abc";
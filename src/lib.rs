@@ -0,0 +1,4 @@
+pub mod fmt;
+pub mod interpreter;
+pub mod lex;
+pub mod parse;
@@ -1,9 +1,157 @@
-use crate::interpreter::repl::repl;
+use scheme_interp_rs::fmt;
+use scheme_interp_rs::interpreter::doctest;
+use scheme_interp_rs::interpreter::repl::{self, repl, ReplOptions};
+use scheme_interp_rs::interpreter::script::{self, ErrorFormat, ScriptOptions};
+use scheme_interp_rs::interpreter::{Language, Options};
+use std::path::{Path, PathBuf};
 
-mod interpreter;
-mod lex;
-mod parse;
+const USAGE: &str = "usage: scheme-interp-rs [--init <file>] [--transcript <file>] [--backtrace] \
+                     [--error-exit-code <n>] [--error-format human|json] \
+                     [--one-shot-continuations] [--lock-stdlib] [--freeze-stdlib] \
+                     [--warn-shadowing] [--seed <n>] [--lang beginner|standard|full] \
+                     [--max-allocations <n>] \
+                     [-e <expr> | <file> [<arg>...] | --listen <addr>]
+       scheme-interp-rs test-docs <file>...
+       scheme-interp-rs --fmt <file>";
+const EXIT_USAGE: i32 = 64;
+const EXIT_DATA_ERROR: i32 = 65;
+const EXIT_NO_INPUT: i32 = 66;
+const EXIT_UNAVAILABLE: i32 = 69;
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    std::process::exit(EXIT_USAGE);
+}
+
+// format_file prints the script at path formatted, returning the exit status
+fn format_file(path: &Path) -> i32 {
+    let source = match std::fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("Unable to read {}: {}", path.display(), err);
+            return EXIT_NO_INPUT;
+        }
+    };
+    match fmt::format(&source) {
+        Ok(formatted) => {
+            print!("{}", formatted);
+            0
+        }
+        Err(message) => {
+            eprintln!("{}: {}", path.display(), message);
+            EXIT_DATA_ERROR
+        }
+    }
+}
 
 fn main() {
-    repl();
+    let mut repl_options = ReplOptions {
+        init_file: None,
+        transcript: None,
+    };
+    let mut expr = None;
+    let mut script_file = None;
+    // arguments after the script file are passed to its main procedure
+    let mut script_args = Vec::new();
+    let mut listen_addr = None;
+    let mut options = ScriptOptions {
+        backtrace: false,
+        error_exit_code: script::DEFAULT_ERROR_EXIT_CODE,
+        error_format: ErrorFormat::Human,
+    };
+    let mut interp_options = Options::default();
+    let mut args = std::env::args().skip(1).peekable();
+
+    if args.peek().map(String::as_str) == Some("test-docs") {
+        let paths = args.skip(1).map(PathBuf::from).collect::<Vec<_>>();
+        if paths.is_empty() {
+            usage_error("test-docs requires at least one file");
+        }
+        std::process::exit(doctest::test_docs(&paths));
+    }
+
+    if args.peek().map(String::as_str) == Some("--fmt") {
+        match (args.nth(1), args.next()) {
+            (Some(path), None) => std::process::exit(format_file(Path::new(&path))),
+            _ => usage_error("--fmt requires a single file"),
+        }
+    }
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--init" => match args.next() {
+                Some(path) => repl_options.init_file = Some(PathBuf::from(path)),
+                None => usage_error("--init requires a file path"),
+            },
+            "--transcript" => match args.next() {
+                Some(path) => repl_options.transcript = Some(PathBuf::from(path)),
+                None => usage_error("--transcript requires a file path"),
+            },
+            "-e" => match args.next() {
+                Some(source) => expr = Some(source),
+                None => usage_error("-e requires an expression"),
+            },
+            "--listen" => match args.next() {
+                Some(addr) => listen_addr = Some(addr),
+                None => usage_error("--listen requires an address"),
+            },
+            "--backtrace" => options.backtrace = true,
+            "--one-shot-continuations" => interp_options.one_shot_continuations = true,
+            "--lock-stdlib" => interp_options.lock_stdlib = true,
+            "--freeze-stdlib" => interp_options.freeze_stdlib = true,
+            "--warn-shadowing" => interp_options.warn_shadowing = true,
+            "--seed" => match args.next().and_then(|seed| seed.parse().ok()) {
+                Some(seed) => interp_options.seed = Some(seed),
+                None => usage_error("--seed requires a non-negative integer"),
+            },
+            "--max-allocations" => match args.next().and_then(|limit| limit.parse().ok()) {
+                Some(limit) => interp_options.max_allocations_per_eval = Some(limit),
+                None => usage_error("--max-allocations requires a non-negative integer"),
+            },
+            "--error-format" => match args.next().map(|format| format.parse::<ErrorFormat>()) {
+                Some(Ok(format)) => options.error_format = format,
+                Some(Err(message)) => usage_error(&message),
+                None => usage_error("--error-format requires a format"),
+            },
+            "--lang" => match args.next().map(|lang| lang.parse::<Language>()) {
+                Some(Ok(language)) => interp_options.language = language,
+                Some(Err(message)) => usage_error(&message),
+                None => usage_error("--lang requires a language level"),
+            },
+            "--error-exit-code" => match args.next().and_then(|code| code.parse().ok()) {
+                Some(code) => options.error_exit_code = code,
+                None => usage_error("--error-exit-code requires an integer"),
+            },
+            _ if !arg.starts_with('-') && script_file.is_none() => {
+                script_file = Some(PathBuf::from(arg));
+                script_args = args.by_ref().collect();
+            }
+            _ => usage_error(&format!("unrecognized argument: {}", arg)),
+        }
+    }
+
+    if let Some(addr) = listen_addr {
+        if expr.is_some() || script_file.is_some() {
+            usage_error("--listen cannot be combined with -e or a script file");
+        }
+        if let Err(err) = repl::listen(&addr, &repl_options, interp_options) {
+            eprintln!("Unable to listen on {}: {}", addr, err);
+            std::process::exit(EXIT_UNAVAILABLE);
+        }
+        return;
+    }
+
+    match (expr, script_file) {
+        (Some(_), Some(_)) => usage_error("-e cannot be combined with a script file"),
+        (Some(source), None) => {
+            std::process::exit(script::run_source(&source, &options, interp_options))
+        }
+        (None, Some(path)) => std::process::exit(script::run_file(
+            &path,
+            &script_args,
+            &options,
+            interp_options,
+        )),
+        (None, None) => repl(&repl_options, interp_options),
+    }
 }
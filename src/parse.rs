@@ -1,78 +1,334 @@
-use crate::lex::{AnnotatedToken, Token};
+use crate::lex::{self, AnnotatedToken, CharSource, Lexer, Span, Token};
+use std::collections::HashSet;
+use std::fmt;
 
+// Location is where in the source a node was read from
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Location {
+    pub line: u64,
+    pub column: u64,
+    pub span: Span,
+}
+
+impl Location {
+    fn of(token: &AnnotatedToken) -> Self {
+        Location {
+            line: token.line,
+            column: token.column,
+            span: token.span,
+        }
+    }
+
+    // through extends the location to end at the byte offset end
+    fn through(self, end: usize) -> Self {
+        Location {
+            span: Span {
+                start: self.span.start,
+                end,
+            },
+            ..self
+        }
+    }
+}
+
+// Node is an AST node with the location it was read from
+#[derive(PartialEq, Debug, Clone)]
+pub struct Node {
+    pub ast: AST,
+    pub location: Location,
+}
+
+#[allow(clippy::upper_case_acronyms)]
 #[derive(PartialEq, Debug, Clone)]
 pub enum AST {
-    List(Vec<AST>),
+    List(Vec<Node>),
+    Vector(Vec<Node>),
+    Bytevector(Vec<u8>),
+    // a datum named by #n=, and a reference #n# to a datum named earlier in the same datum
+    Labeled(u64, Box<Node>),
+    LabelRef(u64),
     Integer(i64),
+    Float(f64),
+    Rational(i64, i64),
     Bool(bool),
     Symbol(String),
+    Keyword(String),
+    String(String),
+    Char(char),
 }
 
 #[derive(Debug)]
 pub struct Error {
-    line: u64,
-    column: u64,
-    message: &'static str,
+    pub line: u64,
+    pub column: u64,
+    // the source the error refers to
+    pub span: Span,
+    pub message: &'static str,
 }
 
 const UNMATCHED_RPAREN_ERROR: &str = "unmatched ')'";
+const UNMATCHED_RBRACKET_ERROR: &str = "unmatched ']'";
 const MISSING_RPAREN_ERROR: &str = "missing matching ')'";
+const MISSING_RBRACKET_ERROR: &str = "missing matching ']'";
+const MISMATCHED_RPAREN_ERROR: &str = "')' cannot close '[', expected ']'";
+const MISMATCHED_RBRACKET_ERROR: &str = "']' cannot close '(', expected ')'";
+const MISSING_QUOTED_ERROR: &str = "expected a datum after quote shorthand";
+const MISSING_LABELED_ERROR: &str = "expected a datum after datum label";
+const UNDEFINED_LABEL_ERROR: &str = "reference to an undefined datum label";
+const DUPLICATE_LABEL_ERROR: &str = "datum label is defined more than once";
+const SELF_LABEL_ERROR: &str = "datum label cannot refer only to itself";
+const BYTEVECTOR_ELEMENT_ERROR: &str = "bytevector elements must be integers from 0 to 255";
 
-fn parse_node(tokens: &[AnnotatedToken]) -> Result<(AST, &[AnnotatedToken]), Error> {
+fn parse_node(tokens: &[AnnotatedToken]) -> Result<(Node, &[AnnotatedToken]), Error> {
     let (first, rest) = tokens.split_first().unwrap();
+    let location = Location::of(first);
 
-    match &first.token {
-        Token::Integer(i) => Ok((AST::Integer(*i), rest)),
-        Token::Bool(b) => Ok((AST::Bool(*b), rest)),
-        Token::Symbol(s) => Ok((AST::Symbol(s.clone()), rest)),
-        Token::Rparen => Err(Error {
-            line: first.line,
-            column: first.column,
-            message: UNMATCHED_RPAREN_ERROR,
-        }),
-        Token::Lparen => {
+    let ast = match &first.token {
+        Token::Integer(i) => AST::Integer(*i),
+        Token::Float(f) => AST::Float(*f),
+        Token::Rational(num, den) => AST::Rational(*num, *den),
+        Token::Bool(b) => AST::Bool(*b),
+        Token::Symbol(s) => AST::Symbol(s.clone()),
+        Token::Keyword(s) => AST::Keyword(s.clone()),
+        Token::String(s) => AST::String(s.clone()),
+        Token::Char(c) => AST::Char(*c),
+        Token::Quote => return parse_quoted("quote", first, rest),
+        Token::Quasiquote => return parse_quoted("quasiquote", first, rest),
+        Token::Unquote => return parse_quoted("unquote", first, rest),
+        Token::UnquoteSplicing => return parse_quoted("unquote-splicing", first, rest),
+        Token::Label(label) => {
+            if rest.is_empty() || is_closing(&rest[0].token) {
+                return Err(error_at(
+                    first.line,
+                    first.column,
+                    first.span,
+                    MISSING_LABELED_ERROR,
+                ));
+            }
+            let (datum, rest) = parse_node(rest)?;
+            let end = datum.location.span.end;
+            let node = Node {
+                ast: AST::Labeled(*label, Box::new(datum)),
+                location: location.through(end),
+            };
+            return Ok((node, rest));
+        }
+        Token::LabelRef(label) => AST::LabelRef(*label),
+        Token::Rparen | Token::Rbracket => {
+            return Err(Error {
+                line: first.line,
+                column: first.column,
+                span: first.span,
+                message: if first.token == Token::Rparen {
+                    UNMATCHED_RPAREN_ERROR
+                } else {
+                    UNMATCHED_RBRACKET_ERROR
+                },
+            })
+        }
+        Token::Lparen | Token::Lbracket | Token::VectorStart | Token::BytevectorStart => {
+            let (close, missing, mismatched) = if first.token != Token::Lbracket {
+                (
+                    Token::Rparen,
+                    MISSING_RPAREN_ERROR,
+                    MISMATCHED_RBRACKET_ERROR,
+                )
+            } else {
+                (
+                    Token::Rbracket,
+                    MISSING_RBRACKET_ERROR,
+                    MISMATCHED_RPAREN_ERROR,
+                )
+            };
             let mut remaining_toks = rest;
             let mut items = Vec::new();
 
             loop {
                 if remaining_toks.is_empty() {
+                    // the unclosed list runs to the end of the input
                     return Err(Error {
                         line: first.line,
                         column: first.column,
-                        message: MISSING_RPAREN_ERROR,
+                        span: Span {
+                            start: first.span.start,
+                            end: tokens.last().unwrap().span.end,
+                        },
+                        message: missing,
                     });
                 }
 
-                if remaining_toks[0].token == Token::Rparen {
-                    remaining_toks = &remaining_toks[1..];
-                    break;
+                let next = &remaining_toks[0];
+                if next.token == close {
+                    let ast = match first.token {
+                        Token::VectorStart => AST::Vector(items),
+                        Token::BytevectorStart => AST::Bytevector(bytes(items)?),
+                        _ => AST::List(items),
+                    };
+                    let node = Node {
+                        ast,
+                        location: location.through(next.span.end),
+                    };
+                    return Ok((node, &remaining_toks[1..]));
+                }
+                if is_closing(&next.token) {
+                    return Err(Error {
+                        line: next.line,
+                        column: next.column,
+                        span: next.span,
+                        message: mismatched,
+                    });
                 }
 
                 let (item, rest) = parse_node(remaining_toks)?;
                 items.push(item);
                 remaining_toks = rest;
             }
-
-            Ok((AST::List(items), remaining_toks))
         }
-    }
+    };
+    Ok((Node { ast, location }, rest))
 }
 
-impl ToString for Error {
-    fn to_string(&self) -> String {
-        format!(
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
             "Syntax error at line {}, col {}: {}",
             self.line, self.column, self.message
         )
     }
 }
 
-pub fn parse(tokens: &[AnnotatedToken]) -> Result<Vec<AST>, Error> {
+// bytes checks the elements of a bytevector literal are all bytes
+fn bytes(items: Vec<Node>) -> Result<Vec<u8>, Error> {
+    items
+        .into_iter()
+        .map(|item| match item.ast {
+            AST::Integer(i) if (0..=255).contains(&i) => Ok(i as u8),
+            _ => Err(Error {
+                line: item.location.line,
+                column: item.location.column,
+                span: item.location.span,
+                message: BYTEVECTOR_ELEMENT_ERROR,
+            }),
+        })
+        .collect()
+}
+
+fn error_at(line: u64, column: u64, span: Span, message: &'static str) -> Error {
+    Error {
+        line,
+        column,
+        span,
+        message,
+    }
+}
+
+// check_labels checks every label node refers to was defined before it, in the order read
+fn check_labels(node: &Node, defined: &mut HashSet<u64>) -> Result<(), Error> {
+    let Location { line, column, span } = node.location;
+    match &node.ast {
+        AST::Labeled(label, datum) => {
+            if let AST::LabelRef(_) = datum.ast {
+                return Err(error_at(line, column, span, SELF_LABEL_ERROR));
+            }
+            if !defined.insert(*label) {
+                return Err(error_at(line, column, span, DUPLICATE_LABEL_ERROR));
+            }
+            check_labels(datum, defined)
+        }
+        AST::LabelRef(label) if !defined.contains(label) => {
+            Err(error_at(line, column, span, UNDEFINED_LABEL_ERROR))
+        }
+        AST::List(entries) | AST::Vector(entries) => entries
+            .iter()
+            .try_for_each(|entry| check_labels(entry, defined)),
+        _ => Ok(()),
+    }
+}
+
+fn is_closing(token: &Token) -> bool {
+    matches!(token, Token::Rparen | Token::Rbracket)
+}
+
+// parse_quoted expands the shorthand token first to the list (name datum)
+fn parse_quoted<'a>(
+    name: &str,
+    first: &AnnotatedToken,
+    rest: &'a [AnnotatedToken],
+) -> Result<(Node, &'a [AnnotatedToken]), Error> {
+    if rest.is_empty() || is_closing(&rest[0].token) {
+        return Err(Error {
+            line: first.line,
+            column: first.column,
+            span: first.span,
+            message: MISSING_QUOTED_ERROR,
+        });
+    }
+
+    let (datum, rest) = parse_node(rest)?;
+    let location = Location::of(first);
+    let keyword = Node {
+        ast: AST::Symbol(name.to_string()),
+        location,
+    };
+    let end = datum.location.span.end;
+    let node = Node {
+        ast: AST::List(vec![keyword, datum]),
+        location: location.through(end),
+    };
+    Ok((node, rest))
+}
+
+// Partial is the result of parsing input which may stop partway through a form
+#[derive(Debug)]
+pub enum Partial {
+    // every form in the input is complete
+    Complete(Vec<Node>),
+    // the input ends inside a form, after the complete forms given
+    Incomplete(Vec<Node>),
+}
+
+// parse_partial parses tokens like parse, but reports input ending inside a form
+// as incomplete rather than an error, so more input can be read to finish it
+pub fn parse_partial(tokens: &[AnnotatedToken]) -> Result<Partial, Error> {
+    let mut res = Vec::new();
+    let mut remaining = tokens;
+
+    while !remaining.is_empty() {
+        match parse_node(remaining) {
+            Ok((node, rest)) => {
+                check_labels(&node, &mut HashSet::new())?;
+                res.push(node);
+                remaining = rest;
+            }
+            Err(err) if is_unfinished(&err, tokens) => return Ok(Partial::Incomplete(res)),
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(Partial::Complete(res))
+}
+
+// is_unfinished checks whether err is the input ending inside a form
+fn is_unfinished(err: &Error, tokens: &[AnnotatedToken]) -> bool {
+    match err.message {
+        MISSING_RPAREN_ERROR | MISSING_RBRACKET_ERROR => true,
+        // shorthand with nothing after it is unfinished only at the end of the input
+        MISSING_QUOTED_ERROR | MISSING_LABELED_ERROR => {
+            tokens.last().map(|token| token.span) == Some(err.span)
+        }
+        _ => false,
+    }
+}
+
+pub fn parse(tokens: &[AnnotatedToken]) -> Result<Vec<Node>, Error> {
     let mut res = Vec::new();
     let mut tokens = tokens;
 
     while !tokens.is_empty() {
         let (ast, remaining) = parse_node(tokens)?;
+        // labels are scoped to the outermost datum they appear in
+        check_labels(&ast, &mut HashSet::new())?;
         res.push(ast);
         tokens = remaining
     }
@@ -80,6 +336,139 @@ pub fn parse(tokens: &[AnnotatedToken]) -> Result<Vec<AST>, Error> {
     Ok(res)
 }
 
+// Parser reads the forms of a source one at a time, as they are needed,
+// so only the tokens of the current form are held in memory
+// iteration ends after the first syntax error
+pub struct Parser<S: CharSource> {
+    lexer: Lexer<S>,
+    failed: bool,
+}
+
+impl<S: CharSource> Parser<S> {
+    pub fn new(lexer: Lexer<S>) -> Self {
+        Parser {
+            lexer,
+            failed: false,
+        }
+    }
+
+    // next_form reads the tokens of the next top-level datum, or None at the end of the input
+    // a datum cut short by the end of the input is returned for parse_node to report
+    fn next_form(&mut self) -> Result<Option<Vec<AnnotatedToken>>, Error> {
+        let mut tokens = Vec::new();
+        let mut depth = 0;
+        loop {
+            let token = match self.lexer.next_token() {
+                Ok(Some(token)) => token,
+                Ok(None) if tokens.is_empty() => return Ok(None),
+                Ok(None) => return Ok(Some(tokens)),
+                Err(err) => return Err(self.lex_error(err)),
+            };
+            match token.token {
+                Token::Lparen | Token::Lbracket | Token::VectorStart | Token::BytevectorStart => {
+                    depth += 1
+                }
+                Token::Rparen | Token::Rbracket => depth -= 1,
+                _ => {}
+            }
+            // shorthand and labels are part of the datum after them
+            let prefix = matches!(
+                token.token,
+                Token::Quote
+                    | Token::Quasiquote
+                    | Token::Unquote
+                    | Token::UnquoteSplicing
+                    | Token::Label(_)
+            );
+            tokens.push(token);
+            if depth <= 0 && !prefix {
+                return Ok(Some(tokens));
+            }
+        }
+    }
+
+    // lex_error converts err to a parse error, located where the lexer stopped
+    fn lex_error(&self, err: lex::Error) -> Error {
+        let offset = self.lexer.offset();
+        Error {
+            line: err.line,
+            column: err.column,
+            span: Span {
+                start: offset,
+                end: offset,
+            },
+            message: err.message,
+        }
+    }
+}
+
+impl<S: CharSource> Iterator for Parser<S> {
+    type Item = Result<Node, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let res = self.next_form().and_then(|tokens| {
+            tokens
+                .map(|tokens| {
+                    let (node, _) = parse_node(&tokens)?;
+                    check_labels(&node, &mut HashSet::new())?;
+                    Ok(node)
+                })
+                .transpose()
+        });
+        self.failed = res.is_err();
+        res.transpose()
+    }
+}
+
+// parse_all parses tokens, recovering from syntax errors,
+// returning the forms read and every error found
+// after an error, parsing resumes at the next token starting a line after the error,
+// which in conventionally formatted source is the next top-level form
+pub fn parse_all(tokens: &[AnnotatedToken]) -> (Vec<Node>, Vec<Error>) {
+    let mut nodes = Vec::new();
+    let mut errors = Vec::new();
+    let mut remaining = tokens;
+
+    while !remaining.is_empty() {
+        match parse_node(remaining) {
+            Ok((node, rest)) => {
+                match check_labels(&node, &mut HashSet::new()) {
+                    Ok(()) => nodes.push(node),
+                    Err(err) => errors.push(err),
+                }
+                remaining = rest;
+            }
+            Err(err) => {
+                // an unmatched closing paren is skipped on its own
+                remaining = if is_closing(&remaining[0].token) {
+                    &remaining[1..]
+                } else {
+                    let after = remaining
+                        .iter()
+                        .position(|token| token.span.start > err.span.start)
+                        .unwrap_or(remaining.len());
+                    synchronize(&remaining[after..])
+                };
+                errors.push(err);
+            }
+        }
+    }
+
+    (nodes, errors)
+}
+
+// synchronize skips to the first token at the start of a line which isn't a closing paren
+fn synchronize(tokens: &[AnnotatedToken]) -> &[AnnotatedToken] {
+    let start = tokens
+        .iter()
+        .position(|token| token.column == 0 && !is_closing(&token.token))
+        .unwrap_or(tokens.len());
+    &tokens[start..]
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -91,10 +480,38 @@ mod test {
                 token,
                 line: 0,
                 column: 0,
+                span: Span::default(),
             })
             .collect()
     }
 
+    fn node(ast: AST) -> Node {
+        Node {
+            ast,
+            location: Location::default(),
+        }
+    }
+
+    fn list(entries: Vec<AST>) -> AST {
+        AST::List(entries.into_iter().map(node).collect())
+    }
+
+    // unlocated clears the locations of node and its entries
+    fn unlocated(node: Node) -> Node {
+        match node.ast {
+            AST::List(entries) => {
+                self::node(AST::List(entries.into_iter().map(unlocated).collect()))
+            }
+            AST::Vector(entries) => {
+                self::node(AST::Vector(entries.into_iter().map(unlocated).collect()))
+            }
+            AST::Labeled(label, datum) => {
+                self::node(AST::Labeled(label, Box::new(unlocated(*datum))))
+            }
+            ast => self::node(ast),
+        }
+    }
+
     #[test]
     fn generates_correct_ast() {
         use Token::*;
@@ -114,19 +531,47 @@ mod test {
         let ast = parse(&tokens).unwrap();
         assert_eq!(
             ast,
-            vec![AST::List(vec![
+            vec![node(list(vec![
                 AST::Symbol("+".to_string()),
-                AST::List(vec![
+                list(vec![
                     AST::Symbol("+".to_string()),
                     AST::Integer(1),
                     AST::Integer(2),
                     AST::Bool(true),
                 ]),
                 AST::Integer(3)
-            ])]
+            ]))]
         );
     }
 
+    #[test]
+    fn expands_quote_shorthand() {
+        use Token::*;
+        // '(a ,@b)
+        let tokens = annotate_tokens(vec![
+            Quote,
+            Lparen,
+            Symbol("a".to_string()),
+            UnquoteSplicing,
+            Symbol("b".to_string()),
+            Rparen,
+        ]);
+        let sym = |s: &str| AST::Symbol(s.to_string());
+        assert_eq!(
+            parse(&tokens).unwrap(),
+            vec![node(list(vec![
+                sym("quote"),
+                list(vec![
+                    sym("a"),
+                    list(vec![sym("unquote-splicing"), sym("b")])
+                ])
+            ]))]
+        );
+
+        let tokens = annotate_tokens(vec![Lparen, Quasiquote, Rparen]);
+        assert_eq!(parse(&tokens).unwrap_err().message, MISSING_QUOTED_ERROR);
+    }
+
     #[test]
     fn handles_unmatched_lparen() {
         let tokens = annotate_tokens(vec![Token::Lparen]);
@@ -134,6 +579,210 @@ mod test {
         assert!(res.is_err())
     }
 
+    #[test]
+    fn reports_error_spans() {
+        let tokens = crate::lex::tokenize("(a) ) (b").unwrap();
+        let err = parse(&tokens).unwrap_err();
+        assert_eq!(err.span, Span { start: 4, end: 5 });
+
+        let tokens = crate::lex::tokenize("(a (b c)").unwrap();
+        let err = parse(&tokens).unwrap_err();
+        assert_eq!(err.span, Span { start: 0, end: 8 });
+    }
+
+    #[test]
+    fn matches_brackets() {
+        let parse_str = |source| parse(&crate::lex::tokenize(source).unwrap());
+        let sym = |s: &str| AST::Symbol(s.to_string());
+        assert_eq!(
+            unlocated(parse_str("(let ([x 1]) x)").unwrap().remove(0)),
+            node(list(vec![
+                sym("let"),
+                list(vec![list(vec![sym("x"), AST::Integer(1)])]),
+                sym("x")
+            ]))
+        );
+
+        let err = parse_str("(let ([x 1)) x)").unwrap_err();
+        assert_eq!((err.column, err.message), (10, MISMATCHED_RPAREN_ERROR));
+        let err = parse_str("(a]").unwrap_err();
+        assert_eq!((err.column, err.message), (2, MISMATCHED_RBRACKET_ERROR));
+        assert_eq!(parse_str("[a").unwrap_err().message, MISSING_RBRACKET_ERROR);
+        assert_eq!(
+            parse_str("]").unwrap_err().message,
+            UNMATCHED_RBRACKET_ERROR
+        );
+    }
+
+    #[test]
+    fn parses_vector_literals() {
+        let parse_str = |source| parse(&crate::lex::tokenize(source).unwrap());
+        assert_eq!(
+            unlocated(parse_str("#(1 (2) #())").unwrap().remove(0)),
+            node(AST::Vector(vec![
+                node(AST::Integer(1)),
+                node(list(vec![AST::Integer(2)])),
+                node(AST::Vector(vec![])),
+            ]))
+        );
+        assert_eq!(
+            parse_str("#(1]").unwrap_err().message,
+            MISMATCHED_RBRACKET_ERROR
+        );
+        assert_eq!(parse_str("#(1").unwrap_err().message, MISSING_RPAREN_ERROR);
+    }
+
+    #[test]
+    fn parses_bytevector_literals() {
+        let parse_str = |source| parse(&crate::lex::tokenize(source).unwrap());
+        assert_eq!(
+            unlocated(parse_str("#u8(0 255 16)").unwrap().remove(0)),
+            node(AST::Bytevector(vec![0, 255, 16]))
+        );
+        let err = parse_str("#u8(1 256)").unwrap_err();
+        assert_eq!((err.column, err.message), (6, BYTEVECTOR_ELEMENT_ERROR));
+        assert_eq!(
+            parse_str("#u8((1))").unwrap_err().message,
+            BYTEVECTOR_ELEMENT_ERROR
+        );
+        assert!(crate::lex::tokenize("#u9(1)").is_err());
+    }
+
+    #[test]
+    fn parses_datum_labels() {
+        let parse_str = |source| parse(&crate::lex::tokenize(source).unwrap());
+        assert_eq!(
+            unlocated(parse_str("#0=(a #0#)").unwrap().remove(0)),
+            node(AST::Labeled(
+                0,
+                Box::new(node(list(vec![
+                    AST::Symbol("a".to_string()),
+                    AST::LabelRef(0)
+                ])))
+            ))
+        );
+        assert!(parse_str("(#12=a #12#)").is_ok());
+
+        let err = parse_str("(#1# #1=a)").unwrap_err();
+        assert_eq!((err.column, err.message), (1, UNDEFINED_LABEL_ERROR));
+        // labels are scoped to one top-level datum
+        assert_eq!(
+            parse_str("#1=a #1#").unwrap_err().message,
+            UNDEFINED_LABEL_ERROR
+        );
+        assert_eq!(
+            parse_str("(#1=a #1=b)").unwrap_err().message,
+            DUPLICATE_LABEL_ERROR
+        );
+        assert_eq!(parse_str("#1=#1#").unwrap_err().message, SELF_LABEL_ERROR);
+        assert_eq!(
+            parse_str("(#1=)").unwrap_err().message,
+            MISSING_LABELED_ERROR
+        );
+        assert!(crate::lex::tokenize("#1x").is_err());
+        assert!(crate::lex::tokenize("#1#a").is_err());
+    }
+
+    #[test]
+    fn parses_partial_input() {
+        let parse_str = |source| parse_partial(&crate::lex::tokenize(source).unwrap());
+        let complete = |partial| match partial {
+            Ok(Partial::Complete(nodes)) => Some(nodes.len()),
+            _ => None,
+        };
+        let incomplete = |partial| match partial {
+            Ok(Partial::Incomplete(nodes)) => Some(nodes.len()),
+            _ => None,
+        };
+        assert_eq!(complete(parse_str("a (b c)")), Some(2));
+        assert_eq!(incomplete(parse_str("a (define (f x)")), Some(1));
+        assert_eq!(incomplete(parse_str("[a (b")), Some(0));
+        assert_eq!(incomplete(parse_str("(a '")), Some(0));
+        assert_eq!(incomplete(parse_str("a #1=")), Some(1));
+        assert!(parse_str("(a ')").is_err());
+        assert!(parse_str("a)").is_err());
+        assert!(parse_str("(a]").is_err());
+    }
+
+    #[test]
+    fn recovers_from_errors() {
+        let source = "(define (f x)\n  (g x]\n(define y 1))\n) (h)\n\n(define z\n#u8(300))\n'";
+        let (nodes, errors) = parse_all(&crate::lex::tokenize(source).unwrap());
+        let errors = errors
+            .iter()
+            .map(|err| (err.line, err.column, err.message))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            vec![
+                (1, 6, MISMATCHED_RBRACKET_ERROR),
+                (2, 12, UNMATCHED_RPAREN_ERROR),
+                (3, 0, UNMATCHED_RPAREN_ERROR),
+                (6, 4, BYTEVECTOR_ELEMENT_ERROR),
+                (7, 0, MISSING_QUOTED_ERROR),
+            ]
+        );
+        // the forms between the errors are still read
+        let lines = nodes
+            .iter()
+            .map(|node| node.location.line)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, vec![2, 3]);
+    }
+
+    #[test]
+    fn parses_forms_lazily() {
+        let input = std::io::Cursor::new("(define x\n  '[1 #(2)])\n#0=(a . #0#)\n(b))\n(c)");
+        let mut parser = Parser::new(Lexer::new(lex::ReadSource::new(input)));
+        let node = parser.next().unwrap().unwrap();
+        assert_eq!(node.location.span, Span { start: 0, end: 22 });
+        assert_eq!(parser.lexer.offset(), 22);
+
+        assert!(matches!(
+            parser.next(),
+            Some(Ok(Node {
+                ast: AST::Labeled(0, _),
+                ..
+            }))
+        ));
+        assert!(parser.next().unwrap().is_ok());
+        let err = parser.next().unwrap().unwrap_err();
+        assert_eq!(
+            (err.line, err.column, err.message),
+            (3, 3, UNMATCHED_RPAREN_ERROR)
+        );
+        assert!(parser.next().is_none());
+
+        let mut parser = Parser::new(Lexer::new(lex::StrSource::new("a \"b")));
+        assert!(parser.next().unwrap().is_ok());
+        let err = parser.next().unwrap().unwrap_err();
+        assert_eq!((err.column, err.span.start), (2, 4));
+        let mut parser = Parser::new(Lexer::new(lex::StrSource::new("(a '")));
+        assert_eq!(
+            parser.next().unwrap().unwrap_err().message,
+            MISSING_QUOTED_ERROR
+        );
+    }
+
+    #[test]
+    fn records_node_locations() {
+        let nodes = parse(&crate::lex::tokenize("a\n ('b [c])").unwrap()).unwrap();
+        let location = |line, column, start, end| Location {
+            line,
+            column,
+            span: Span { start, end },
+        };
+        assert_eq!(nodes[0].location, location(0, 0, 0, 1));
+        assert_eq!(nodes[1].location, location(1, 1, 3, 11));
+        let entries = match &nodes[1].ast {
+            AST::List(entries) => entries,
+            _ => panic!("expected a list"),
+        };
+        // quote shorthand is located at the quote mark, expanded to cover the quoted datum
+        assert_eq!(entries[0].location, location(1, 2, 4, 6));
+        assert_eq!(entries[1].location, location(1, 5, 7, 10));
+    }
+
     #[test]
     fn handles_extra_rparen() {
         let tokens = annotate_tokens(vec![Token::Rparen]);
@@ -0,0 +1,16 @@
+use scheme_interp_rs::interpreter::doctest;
+use std::path::Path;
+
+// every Scheme program in examples/ has examples in its comments, which must pass
+#[test]
+fn examples_pass_their_doc_tests() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+    let mut paths = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "scm"))
+        .collect::<Vec<_>>();
+    paths.sort();
+    assert_eq!(paths.len(), 3);
+    assert_eq!(doctest::test_docs(&paths), 0);
+}
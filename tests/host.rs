@@ -0,0 +1,62 @@
+use scheme_interp_rs::interpreter::{
+    Arity, Environment, Interpreter, Options, Plugin, Ptr, StdlibRegistry, Value,
+};
+
+struct Twice;
+
+// apply-twice calls back into Scheme, applying its first argument to the result of
+// applying it to the second
+fn apply_twice(interp: &mut Interpreter, env: Ptr<Environment>, args: &[Ptr<Value>]) {
+    let mut val = args[1];
+    for _ in 0..2 {
+        val = match interp.call(args[0], &[val], env) {
+            Ok(val) => val,
+            Err(err) => return interp.raise(err.message),
+        };
+    }
+    interp.return_value(val)
+}
+
+impl Plugin for Twice {
+    fn register(&self, registry: &mut StdlibRegistry) {
+        registry.native("apply-twice", apply_twice, Arity::exactly(2));
+    }
+}
+
+// natives outside the crate can call Scheme procedures, including ones which call them back
+#[test]
+fn plugin_natives_call_back_into_scheme() {
+    let mut interp = Interpreter::new(Options::default());
+    interp.add_plugin(Box::new(Twice));
+
+    let res = interp
+        .eval("(+ 1 (apply-twice (lambda (x) (* x 3)) 2))")
+        .unwrap();
+    assert_eq!(interp.to_string(&res), "19");
+
+    let res = interp
+        .eval("(apply-twice (lambda (x) (apply-twice (lambda (y) (cons 'a y)) x)) '())")
+        .unwrap();
+    assert_eq!(interp.to_string(&res), "(a a a a)");
+
+    let err = interp.eval("(apply-twice car 1)").err().unwrap();
+    assert_eq!(err.message, "the first argument to 'car' must be a pair");
+    // the failed call leaves the interpreter usable
+    let res = interp.eval("(apply-twice (lambda (x) (+ x 1)) 0)").unwrap();
+    assert_eq!(interp.to_string(&res), "2");
+}
+
+// hosts can call procedures directly, in the environment of their choice
+#[test]
+fn hosts_call_procedures() {
+    let mut interp = Interpreter::new(Options::default());
+    interp.eval("(define (add a b) (+ a b))").unwrap();
+    let env = interp.global_env();
+    let add = interp.lookup_global("add").unwrap();
+    let args = [interp.eval("20").unwrap(), interp.eval("22").unwrap()];
+    let res = interp
+        .call(add.ptr(), &[args[0].ptr(), args[1].ptr()], env)
+        .unwrap();
+    let res = interp.handle(res);
+    assert_eq!(interp.to_string(&res), "42");
+}